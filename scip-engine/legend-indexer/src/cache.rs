@@ -0,0 +1,317 @@
+//! Content-addressed cache of SCIP indexes
+//!
+//! CI often indexes the same commit in several pipelines. With `--cache-dir`,
+//! every indexer's output is stored under a key derived from what shapes it:
+//! the language's input files (fingerprinted as for `--incremental`), the
+//! contents of the indexer binary, and its arguments, working directory and
+//! environment with the codebase and work directory paths factored out, so
+//! checkouts in different directories share entries. On a hit the `.scip`
+//! file is restored, with its project root set to the codebase, and the
+//! indexer does not run.
+//!
+//! Entries are plain files named `<language>-<key>.scip`. Their modification
+//! time records the last use, and the least recently used are evicted once
+//! the cache outgrows its size limit.
+
+use crate::detect::Language;
+use crate::incremental::{content_digest, Fingerprint, Fnv64};
+use crate::orchestrate::IndexerPlan;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::debug;
+
+/// Size limit when none is given
+pub const DEFAULT_MAX_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Bumped when the key derivation changes, so old entries are never hit
+const KEY_VERSION: &str = "2";
+
+/// Placeholder for the codebase path in hashed arguments
+const ROOT_PLACEHOLDER: &str = "$ROOT";
+
+/// Placeholder for the work directory path, which may be inside the codebase
+const WORK_DIR_PLACEHOLDER: &str = "$WORK";
+
+/// A size limit such as `500M` or `5G` (binary units; a bare number is bytes)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let upper = trimmed.to_uppercase();
+    let number = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (digits, shift) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 10),
+        Some('M') => (&number[..number.len() - 1], 20),
+        Some('G') => (&number[..number.len() - 1], 30),
+        Some('T') => (&number[..number.len() - 1], 40),
+        _ => (number, 0),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1u64 << shift))
+        .ok_or_else(|| format!("invalid size '{}' (expected e.g. 500M, 5G or a number of bytes)", trimmed))
+}
+
+/// What an indexer run is stored under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    language: Language,
+    hash: String,
+}
+
+impl CacheKey {
+    /// The key for running `plan`, on inputs with fingerprint `inputs`, for
+    /// the codebase at `root` with work directory `work_dir` (both absolute)
+    pub fn new(plan: &IndexerPlan, inputs: &Fingerprint, root: &Path, work_dir: &Path) -> Self {
+        let (root, work_dir) = (root.to_string_lossy(), work_dir.to_string_lossy());
+        let relative = |s: &str| {
+            s.replace(work_dir.as_ref(), WORK_DIR_PLACEHOLDER)
+                .replace(root.as_ref(), ROOT_PLACEHOLDER)
+        };
+        let mut hash = Fnv64::new();
+        hash.write_str(KEY_VERSION);
+        hash.write_str(env!("CARGO_PKG_VERSION"));
+        hash.write_str(plan.language.name());
+        hash.write_str(&inputs.hash);
+        for invocation in &plan.invocations {
+            // The binary's contents, wherever it is installed; for launchers
+            // like `npx` the name is all there is
+            let program = std::fs::canonicalize(&invocation.program).ok().filter(|p| p.is_file());
+            match program {
+                Some(path) => hash.write_str(&format!("{:016x}", content_digest(&path))),
+                None => hash.write_str(&invocation.program.to_string_lossy()),
+            }
+            hash.write_str(&format!("{:?}", invocation.source));
+            for arg in &invocation.args {
+                hash.write_str(&relative(arg));
+            }
+            hash.write_str(&relative(&invocation.working_dir.to_string_lossy()));
+            for (name, value) in &invocation.env {
+                hash.write_str(name);
+                hash.write_str(&relative(value));
+            }
+        }
+        for step in &plan.steps {
+            hash.write_str(&relative(step));
+        }
+        Self {
+            language: plan.language,
+            hash: hash.hex(),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}-{}.scip", self.language.scip_output_stem(), self.hash)
+    }
+}
+
+/// Entry counts and sizes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub size: u64,
+}
+
+/// What `cache stats` reports
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub dir: PathBuf,
+    pub max_size: u64,
+    #[serde(flatten)]
+    pub total: CacheUsage,
+    /// By language, keyed by `.scip` file stem
+    pub languages: BTreeMap<String, CacheUsage>,
+}
+
+/// What a prune removed and left
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    pub removed: CacheUsage,
+    pub remaining: CacheUsage,
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// A cache directory with a size limit
+#[derive(Debug, Clone)]
+pub struct ScipCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl ScipCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy the entry for `key` to `dest`; false on a miss
+    pub fn restore(&self, key: &CacheKey, dest: &Path) -> bool {
+        let path = self.dir.join(key.file_name());
+        if !path.is_file() {
+            debug!("Cache miss: {:?}", path);
+            return false;
+        }
+        // Another process may evict it in the meantime; that is a miss too
+        if let Err(e) = std::fs::copy(&path, dest) {
+            debug!("Cannot restore {:?}: {}", path, e);
+            return false;
+        }
+        if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        true
+    }
+
+    /// Store `index` under `key`, then evict down to the size limit
+    pub fn store(&self, key: &CacheKey, index: &Path) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {:?}", self.dir))?;
+        let path = self.dir.join(key.file_name());
+        // Copy under a temporary name so concurrent runs never see half an entry
+        let partial = self.dir.join(format!(".{}.{}.partial", key.file_name(), std::process::id()));
+        std::fs::copy(index, &partial).with_context(|| format!("Failed to copy {:?} to {:?}", index, partial))?;
+        std::fs::rename(&partial, &path).with_context(|| format!("Failed to move {:?} to {:?}", partial, path))?;
+        debug!("Cached {:?}", path);
+        self.prune(self.max_size)?;
+        Ok(())
+    }
+
+    /// Remove least recently used entries until the cache is at most `max_size`
+    pub fn prune(&self, max_size: u64) -> Result<PruneReport> {
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|e| e.last_used);
+        let mut report = PruneReport {
+            removed: CacheUsage::default(),
+            remaining: CacheUsage {
+                entries: entries.len(),
+                size: entries.iter().map(|e| e.size).sum(),
+            },
+        };
+        for entry in entries {
+            if report.remaining.size <= max_size {
+                break;
+            }
+            std::fs::remove_file(&entry.path).with_context(|| format!("Failed to remove {:?}", entry.path))?;
+            debug!("Evicted {:?}", entry.path);
+            report.removed.entries += 1;
+            report.removed.size += entry.size;
+            report.remaining.entries -= 1;
+            report.remaining.size -= entry.size;
+        }
+        Ok(report)
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats {
+            dir: self.dir.clone(),
+            max_size: self.max_size,
+            total: CacheUsage::default(),
+            languages: BTreeMap::new(),
+        };
+        for entry in self.entries()? {
+            let stem = entry.path.file_stem().unwrap_or_default().to_string_lossy();
+            let language = stem.rsplit_once('-').map_or(stem.as_ref(), |(language, _)| language);
+            for usage in [&mut stats.total, stats.languages.entry(language.to_string()).or_default()] {
+                usage.entries += 1;
+                usage.size += entry.size;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Every entry; an absent directory is an empty cache
+    fn entries(&self) -> Result<Vec<Entry>> {
+        let read_dir = match std::fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read cache directory {:?}", self.dir)),
+        };
+        Ok(read_dir
+            .flatten()
+            .filter(|e| {
+                let name = e.file_name();
+                let name = name.to_string_lossy();
+                !name.starts_with('.') && name.ends_with(".scip")
+            })
+            .filter_map(|e| {
+                let metadata = e.metadata().ok().filter(|m| m.is_file())?;
+                Some(Entry {
+                    path: e.path(),
+                    size: metadata.len(),
+                    last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn key(language: Language, hash: &str) -> CacheKey {
+        CacheKey {
+            language,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("5g"), Ok(5 << 30));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("64KB"), Ok(64 << 10));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn test_store_restore_and_lru_eviction() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let index = temp.path().join("index.scip");
+        std::fs::write(&index, [0u8; 100]).unwrap();
+        let cache = ScipCache::new(temp.path().join("cache"), 250);
+
+        let (a, b, c) = (key(Language::Go, "a"), key(Language::Go, "b"), key(Language::Python, "c"));
+        cache.store(&a, &index).unwrap();
+        cache.store(&b, &index).unwrap();
+        // Using `a` makes `b` the least recently used
+        let age = |k: &CacheKey, secs| {
+            let file = std::fs::File::options().write(true).open(cache.dir().join(k.file_name())).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(secs)).unwrap();
+        };
+        age(&a, 20);
+        age(&b, 10);
+        let restored = temp.path().join("restored.scip");
+        assert!(cache.restore(&a, &restored));
+        assert_eq!(std::fs::read(&restored).unwrap(), vec![0u8; 100]);
+        assert!(!cache.restore(&c, &restored));
+
+        cache.store(&c, &index).unwrap();
+        assert!(cache.restore(&a, &restored));
+        assert!(!cache.restore(&b, &restored), "least recently used entry evicted");
+
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.total, CacheUsage { entries: 2, size: 200 });
+        assert_eq!(stats.languages["go"], CacheUsage { entries: 1, size: 100 });
+        assert_eq!(stats.languages["python"], CacheUsage { entries: 1, size: 100 });
+
+        let report = cache.prune(0).unwrap();
+        assert_eq!(report.removed, CacheUsage { entries: 2, size: 200 });
+        assert_eq!(cache.stats().unwrap().total, CacheUsage::default());
+    }
+}
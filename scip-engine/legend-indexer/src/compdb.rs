@@ -0,0 +1,398 @@
+//! Compilation database discovery for C/C++ indexing
+//!
+//! scip-clang needs a `compile_commands.json` to know how each translation
+//! unit is built. We reuse one already present in the codebase, generate one
+//! with CMake or Meson when the project uses them, and otherwise synthesize a
+//! best-effort database from the sources and header directories on disk.
+//! Entries for files left out by the include/exclude patterns are dropped, so
+//! scip-clang indexes the same files detection counted.
+
+use crate::pathfilter::PathFilter;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+const COMPDB_FILE: &str = "compile_commands.json";
+
+/// Build directories checked for an existing compilation database
+const BUILD_DIRS: &[&str] = &[
+    "build",
+    "builddir",
+    "out",
+    "cmake-build-debug",
+    "cmake-build-release",
+    "out/build",
+];
+
+/// Translation unit extensions (headers are not compiled on their own)
+const C_SOURCES: &[&str] = &["c"];
+const CPP_SOURCES: &[&str] = &["cpp", "cxx", "cc", "c++"];
+const HEADERS: &[&str] = &["h", "hpp", "hxx", "hh", "h++"];
+
+/// Directories never descended into when synthesizing
+const SKIP_DIRS: &[&str] = &["node_modules", "target", "build", "dist", "out"];
+
+/// Where the compilation database handed to scip-clang came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompdbSource {
+    Existing,
+    CMake,
+    Meson,
+    Synthesized,
+}
+
+/// A compilation database ready to pass via `--compdb-path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilationDatabase {
+    pub path: PathBuf,
+    pub source: CompdbSource,
+    /// The database `path` was filtered from, when excluded entries were dropped
+    pub filtered_from: Option<PathBuf>,
+}
+
+impl CompilationDatabase {
+    fn new(path: PathBuf, source: CompdbSource) -> Self {
+        Self { path, source, filtered_from: None }
+    }
+}
+
+/// One entry of `compile_commands.json`
+#[derive(Debug, Serialize)]
+struct CompileCommand {
+    directory: String,
+    file: String,
+    arguments: Vec<String>,
+}
+
+/// Locate or produce a compilation database for `root`, using `work_dir`
+/// for generated build trees and synthesized output, without the entries
+/// `filter` excludes
+pub fn resolve(root: &Path, work_dir: &Path, filter: &PathFilter) -> Result<CompilationDatabase> {
+    if let Some(path) = find_existing(root) {
+        info!("Using existing compilation database {:?}", path);
+        return filter_entries(CompilationDatabase::new(path, CompdbSource::Existing), root, work_dir, filter);
+    }
+
+    if root.join("CMakeLists.txt").is_file() {
+        match generate_cmake(root, &work_dir.join("cmake-build")) {
+            Ok(path) => {
+                return filter_entries(CompilationDatabase::new(path, CompdbSource::CMake), root, work_dir, filter)
+            }
+            Err(e) => warn!("CMake compilation database generation failed: {}", e),
+        }
+    }
+
+    if root.join("meson.build").is_file() {
+        match generate_meson(root, &work_dir.join("meson-build")) {
+            Ok(path) => {
+                return filter_entries(CompilationDatabase::new(path, CompdbSource::Meson), root, work_dir, filter)
+            }
+            Err(e) => warn!("Meson compilation database generation failed: {}", e),
+        }
+    }
+
+    let path = work_dir.join(COMPDB_FILE);
+    let count = synthesize(root, &path, filter)?;
+    info!("Synthesized compilation database with {} entries at {:?}", count, path);
+    Ok(CompilationDatabase::new(path, CompdbSource::Synthesized))
+}
+
+/// The database `resolve` is expected to use, without running anything: an
+/// existing one, else the CMake or Meson build tree when that tool is on
+/// PATH, else the synthesized file
+pub fn planned(root: &Path, work_dir: &Path, filter: &PathFilter) -> CompilationDatabase {
+    let generated = [
+        ("CMakeLists.txt", "cmake", "cmake-build", CompdbSource::CMake),
+        ("meson.build", "meson", "meson-build", CompdbSource::Meson),
+    ];
+    let found = find_existing(root)
+        .map(|path| CompilationDatabase::new(path, CompdbSource::Existing))
+        .or_else(|| {
+            generated.iter().find_map(|&(marker, tool, build_dir, source)| {
+                (root.join(marker).is_file() && which::which(tool).is_ok())
+                    .then(|| CompilationDatabase::new(work_dir.join(build_dir).join(COMPDB_FILE), source))
+            })
+        });
+    match found {
+        Some(db) if !filter.is_empty() => CompilationDatabase {
+            path: filtered_path(work_dir),
+            source: db.source,
+            filtered_from: Some(db.path),
+        },
+        Some(db) => db,
+        None => CompilationDatabase::new(work_dir.join(COMPDB_FILE), CompdbSource::Synthesized),
+    }
+}
+
+fn filtered_path(work_dir: &Path) -> PathBuf {
+    work_dir.join("filtered").join(COMPDB_FILE)
+}
+
+/// Copy `db` into `work_dir` without the entries for files `filter`
+/// excludes. Entries for files outside `root` are kept.
+fn filter_entries(
+    db: CompilationDatabase,
+    root: &Path,
+    work_dir: &Path,
+    filter: &PathFilter,
+) -> Result<CompilationDatabase> {
+    if filter.is_empty() {
+        return Ok(db);
+    }
+    let text = std::fs::read_to_string(&db.path)
+        .with_context(|| format!("Failed to read {:?}", db.path))?;
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse {:?}", db.path))?;
+
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let total = entries.len();
+    let kept: Vec<serde_json::Value> = entries
+        .into_iter()
+        .filter(|entry| {
+            let Some(file) = entry["file"].as_str() else {
+                return true;
+            };
+            let path = Path::new(entry["directory"].as_str().unwrap_or("")).join(file);
+            let rel = path.strip_prefix(&canonical_root).or_else(|_| path.strip_prefix(root));
+            !rel.is_ok_and(|rel| filter.is_excluded(rel))
+        })
+        .collect();
+    if kept.is_empty() {
+        return Err(anyhow!("every entry of {:?} is excluded", db.path));
+    }
+    debug!("Kept {} of {} compilation database entries", kept.len(), total);
+
+    let path = filtered_path(work_dir);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    let json = serde_json::to_string_pretty(&kept)
+        .context("Failed to serialize compilation database")?;
+    std::fs::write(&path, json)
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(CompilationDatabase { path, source: db.source, filtered_from: Some(db.path) })
+}
+
+/// Find a `compile_commands.json` in the root or a conventional build directory
+pub fn find_existing(root: &Path) -> Option<PathBuf> {
+    std::iter::once(root.to_path_buf())
+        .chain(BUILD_DIRS.iter().map(|d| root.join(d)))
+        .map(|dir| dir.join(COMPDB_FILE))
+        .find(|p| p.is_file())
+}
+
+/// Configure the project with CMake, exporting compile commands
+fn generate_cmake(root: &Path, build_dir: &Path) -> Result<PathBuf> {
+    let cmake = which::which("cmake").map_err(|_| anyhow!("cmake not found in PATH"))?;
+    run_tool(
+        &cmake,
+        root,
+        &[
+            "-S".as_ref(),
+            root.as_os_str(),
+            "-B".as_ref(),
+            build_dir.as_os_str(),
+            "-DCMAKE_EXPORT_COMPILE_COMMANDS=ON".as_ref(),
+        ],
+    )?;
+    produced(build_dir)
+}
+
+/// Set up a Meson build directory, which always writes compile commands
+fn generate_meson(root: &Path, build_dir: &Path) -> Result<PathBuf> {
+    let meson = which::which("meson").map_err(|_| anyhow!("meson not found in PATH"))?;
+    run_tool(&meson, root, &["setup".as_ref(), build_dir.as_os_str()])?;
+    produced(build_dir)
+}
+
+fn run_tool(binary: &Path, cwd: &Path, args: &[&std::ffi::OsStr]) -> Result<()> {
+    debug!("Executing: {:?} {:?}", binary, args);
+    let status = Command::new(binary)
+        .current_dir(cwd)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {:?}", binary))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{:?} exited with status: {:?}", binary, status.code()))
+    }
+}
+
+fn produced(build_dir: &Path) -> Result<PathBuf> {
+    let path = build_dir.join(COMPDB_FILE);
+    path.is_file()
+        .then_some(path)
+        .ok_or_else(|| anyhow!("no {} written to {:?}", COMPDB_FILE, build_dir))
+}
+
+/// Write a compilation database covering every C/C++ source under `root`
+/// that `filter` keeps, with each directory that holds headers on the
+/// include path. Returns the number of entries written.
+pub fn synthesize(root: &Path, output: &Path, filter: &PathFilter) -> Result<usize> {
+    let mut sources = Vec::new();
+    let mut include_dirs = BTreeSet::new();
+    collect(root, root, filter, &mut sources, &mut include_dirs);
+    sources.sort();
+
+    if sources.is_empty() {
+        return Err(anyhow!("no C/C++ translation units found under {:?}", root));
+    }
+
+    let includes: Vec<String> = include_dirs
+        .iter()
+        .map(|d| format!("-I{}", d.display()))
+        .collect();
+    let directory = root.to_string_lossy().into_owned();
+    let commands: Vec<CompileCommand> = sources
+        .iter()
+        .map(|file| {
+            let is_c = ext_in(file, C_SOURCES);
+            let mut arguments = vec![
+                if is_c { "cc" } else { "c++" }.to_string(),
+                if is_c { "-std=c11" } else { "-std=c++17" }.to_string(),
+            ];
+            arguments.extend(includes.iter().cloned());
+            arguments.extend(["-c".to_string(), file.to_string_lossy().into_owned()]);
+            CompileCommand {
+                directory: directory.clone(),
+                file: file.to_string_lossy().into_owned(),
+                arguments,
+            }
+        })
+        .collect();
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    let json = serde_json::to_string_pretty(&commands)
+        .context("Failed to serialize compilation database")?;
+    std::fs::write(output, json)
+        .with_context(|| format!("Failed to write {:?}", output))?;
+    Ok(commands.len())
+}
+
+fn collect(
+    root: &Path,
+    dir: &Path,
+    filter: &PathFilter,
+    sources: &mut Vec<PathBuf>,
+    include_dirs: &mut BTreeSet<PathBuf>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.') && !SKIP_DIRS.contains(&name.as_ref()) {
+                collect(root, &path, filter, sources, include_dirs);
+            }
+        } else if file_type.is_file() {
+            if path.strip_prefix(root).is_ok_and(|rel| filter.is_excluded(rel)) {
+                continue;
+            }
+            if ext_in(&path, C_SOURCES) || ext_in(&path, CPP_SOURCES) {
+                sources.push(path);
+            } else if ext_in(&path, HEADERS) {
+                include_dirs.insert(dir.to_path_buf());
+            }
+        }
+    }
+}
+
+fn ext_in(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn write(root: &Path, rel: &str) {
+        let path = root.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_existing_database_preferred() {
+        let temp = tempfile::TempDir::new().unwrap();
+        write(temp.path(), "build/compile_commands.json");
+        write(temp.path(), "main.c");
+
+        let db = resolve(temp.path(), &temp.path().join(".work"), &PathFilter::default()).unwrap();
+        assert_eq!(db.source, CompdbSource::Existing);
+        assert_eq!(db.path, temp.path().join("build/compile_commands.json"));
+    }
+
+    #[test]
+    fn test_synthesized_database() {
+        let temp = tempfile::TempDir::new().unwrap();
+        write(temp.path(), "src/main.c");
+        write(temp.path(), "src/util.cpp");
+        write(temp.path(), "include/util.h");
+        write(temp.path(), "node_modules/dep/dep.c");
+        let output = temp.path().join(".work/compile_commands.json");
+
+        assert_eq!(synthesize(temp.path(), &output, &PathFilter::default()).unwrap(), 2);
+        let db: Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        let entries = db.as_array().unwrap();
+        assert!(entries[0]["file"].as_str().unwrap().ends_with("main.c"));
+        assert_eq!(entries[0]["arguments"][0], "cc");
+        assert_eq!(entries[1]["arguments"][0], "c++");
+        let include = format!("-I{}", temp.path().join("include").display());
+        assert!(entries[1]["arguments"].as_array().unwrap().iter().any(|a| a == &include));
+    }
+
+    #[test]
+    fn test_synthesize_without_sources_fails() {
+        let temp = tempfile::TempDir::new().unwrap();
+        write(temp.path(), "include/only.h");
+        assert!(synthesize(temp.path(), &temp.path().join("out.json"), &PathFilter::default()).is_err());
+    }
+
+    #[test]
+    fn test_excluded_entries_dropped() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        let db = serde_json::json!([
+            {"directory": root, "file": "src/main.c", "command": "cc -c src/main.c"},
+            {"directory": root, "file": "third_party/zlib/inflate.c", "command": "cc -c inflate.c"},
+            {"directory": root.join("build"), "file": root.join("build/gen/version.c"), "arguments": ["cc"]},
+        ]);
+        std::fs::write(root.join("compile_commands.json"), db.to_string()).unwrap();
+        write(root, "third_party/vendored.c");
+
+        let filter = PathFilter::new(&[], &["build/**".into(), "third_party/**".into(), "!build/gen/**".into()]);
+        let resolved = resolve(root, &root.join(".work"), &filter).unwrap();
+        assert_eq!(resolved.source, CompdbSource::Existing);
+        assert_eq!(resolved.filtered_from, Some(root.join("compile_commands.json")));
+        assert_eq!(resolved, planned(root, &root.join(".work"), &filter));
+
+        let entries: Value = serde_json::from_str(&std::fs::read_to_string(&resolved.path).unwrap()).unwrap();
+        let files: Vec<&str> = entries.as_array().unwrap().iter().map(|e| e["file"].as_str().unwrap()).collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0], "src/main.c");
+        assert!(files[1].ends_with("build/gen/version.c"));
+
+        // Synthesis skips excluded sources too
+        std::fs::remove_file(root.join("compile_commands.json")).unwrap();
+        write(root, "src/main.c");
+        let output = root.join(".work/synth.json");
+        assert_eq!(synthesize(root, &output, &filter).unwrap(), 1);
+    }
+}
@@ -1,6 +1,46 @@
 //! Configuration handling for scip-engine
 
+use crate::cache::{ScipCache, DEFAULT_MAX_SIZE};
+use crate::detect::SymlinkPolicy;
+use crate::identity::{IdentityMode, PackageIdentity};
+use crate::pathfilter::PathFilter;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Directory inside the codebase where indexes and generated inputs are
+/// written; never part of the analyzed sources
+pub const WORK_DIR_NAME: &str = ".legend-indexer";
+
+/// Which indexing outcomes make `analyze` exit non-zero
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailOn {
+    /// Any detected language left unindexed, even one without a SCIP indexer
+    Any,
+    /// An indexer that is missing or failed, or nothing indexed at all
+    #[default]
+    Partial,
+    /// Never, whatever the indexers did
+    None,
+}
+
+impl FromStr for FailOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "any" => Ok(Self::Any),
+            "partial" => Ok(Self::Partial),
+            "none" | "never" => Ok(Self::None),
+            other => Err(format!(
+                "unknown failure policy '{}' (expected partial, any or none)",
+                other
+            )),
+        }
+    }
+}
 
 /// Configuration for the indexer
 #[derive(Debug, Clone)]
@@ -14,12 +54,46 @@ pub struct Config {
     /// Languages to analyze (empty means auto-detect all)
     pub languages: Vec<String>,
 
-    /// Glob patterns to exclude
+    /// Glob patterns restricting analysis to sub-trees (empty means everything)
+    pub include_patterns: Vec<String>,
+
+    /// Glob patterns to exclude; `!pattern` re-includes what earlier ones excluded
     pub exclude_patterns: Vec<String>,
 
+    /// How symlinks are treated while walking the codebase
+    pub symlink_policy: SymlinkPolicy,
+
     /// Path to bundled indexers
     pub indexers_path: Option<PathBuf>,
 
+    /// How SCIP package identities are filled in after indexing
+    pub package_identity: IdentityMode,
+
+    /// Package identity to use instead of the detected manifests
+    pub package: Option<PackageIdentity>,
+
+    /// Which indexing outcomes fail the run
+    pub fail_on: FailOn,
+
+    /// Re-use previous indexes of languages whose inputs are unchanged
+    pub incremental: bool,
+
+    /// Git ref to compare against instead of hashing inputs (implies `incremental`)
+    pub changed_since: Option<String>,
+
+    /// Directory caching indexer outputs (None = no cache)
+    pub cache_dir: Option<PathBuf>,
+
+    /// Size limit of the cache in bytes
+    pub cache_max_size: u64,
+
+    /// How long to wait for another run to release the work or output directory
+    pub lock_timeout: Duration,
+
+    /// Work directory outside the codebase (None = `.legend-indexer/` in it);
+    /// when set, nothing is written into the codebase
+    pub work_dir: Option<PathBuf>,
+
     /// Verbosity level
     pub verbose: bool,
 }
@@ -30,6 +104,7 @@ impl Default for Config {
             input_path: PathBuf::from("."),
             output_path: None,
             languages: Vec::new(),
+            include_patterns: Vec::new(),
             exclude_patterns: vec![
                 "node_modules/**".to_string(),
                 ".git/**".to_string(),
@@ -40,8 +115,35 @@ impl Default for Config {
                 "*.min.js".to_string(),
                 "*.min.css".to_string(),
             ],
+            symlink_policy: SymlinkPolicy::default(),
             indexers_path: None,
+            package_identity: IdentityMode::default(),
+            package: None,
+            fail_on: FailOn::default(),
+            incremental: false,
+            changed_since: None,
+            cache_dir: None,
+            cache_max_size: DEFAULT_MAX_SIZE,
+            lock_timeout: Duration::ZERO,
+            work_dir: None,
             verbose: false,
         }
     }
 }
+
+impl Config {
+    /// The include and exclude patterns, compiled
+    pub fn path_filter(&self) -> PathFilter {
+        PathFilter::new(&self.include_patterns, &self.exclude_patterns)
+    }
+
+    /// Where work files and indexes (before any copy to `output_path`) go
+    pub fn effective_work_dir(&self) -> PathBuf {
+        self.work_dir.clone().unwrap_or_else(|| self.input_path.join(WORK_DIR_NAME))
+    }
+
+    /// The indexer output cache, if one is configured
+    pub fn scip_cache(&self) -> Option<ScipCache> {
+        self.cache_dir.clone().map(|dir| ScipCache::new(dir, self.cache_max_size))
+    }
+}
@@ -3,15 +3,25 @@
 //! Detects programming languages in a codebase by examining file extensions
 //! and configuration files.
 
+use crate::config::WORK_DIR_NAME;
+use crate::framework::{self, DetectedFramework};
+use crate::manifest::{self, ProjectManifest};
+use crate::pathfilter::PathFilter;
+use crate::pyenv;
 use anyhow::Result;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
 use std::path::{Path, PathBuf};
-use tracing::warn;
-use walkdir::WalkDir;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use tracing::{debug, warn};
 
 /// Supported programming languages with their SCIP indexer mappings
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
     TypeScript,
@@ -28,6 +38,14 @@ pub enum Language {
     Cpp,
     C,
     Dart,
+    Swift,
+    Elixir,
+    Haskell,
+    Lua,
+    Zig,
+    Shell,
+    VisualBasic,
+    FSharp,
 }
 
 /// Config files that indicate language presence (exact filename match)
@@ -38,22 +56,42 @@ const CONFIG_FILES: &[(&str, Language)] = &[
     ("setup.py", Language::Python),
     ("requirements.txt", Language::Python),
     ("Pipfile", Language::Python),
+    ("environment.yml", Language::Python),
+    ("environment.yaml", Language::Python),
     ("Cargo.toml", Language::Rust),
     ("go.mod", Language::Go),
     ("go.sum", Language::Go),
+    ("go.work", Language::Go),
     ("pom.xml", Language::Java),
     ("build.gradle", Language::Java),
-    ("build.gradle.kts", Language::Java),
+    ("build.gradle.kts", Language::Kotlin),
     ("Gemfile", Language::Ruby),
     ("composer.json", Language::Php),
     ("pubspec.yaml", Language::Dart),
+    ("Package.swift", Language::Swift),
+    ("mix.exs", Language::Elixir),
+    ("stack.yaml", Language::Haskell),
+    ("cabal.project", Language::Haskell),
+    ("build.zig", Language::Zig),
+    ("build.zig.zon", Language::Zig),
+];
+
+/// Config files matched by suffix rather than exact name
+const CONFIG_SUFFIXES: &[(&str, Language)] = &[
+    (".csproj", Language::CSharp),
+    (".sln", Language::CSharp),
+    (".vbproj", Language::VisualBasic),
+    (".fsproj", Language::FSharp),
+    (".cabal", Language::Haskell),
+    (".rockspec", Language::Lua),
 ];
 
 /// Static metadata for each language — replaces 8 separate match methods.
 struct LanguageSpec {
     language: Language,
     display_name: &'static str,
-    scip_indexer: &'static str,
+    /// None for languages that are detected but have no SCIP indexer
+    scip_indexer: Option<&'static str>,
     scip_output_stem: &'static str,
     is_bundled: bool,
     extensions: &'static [&'static str],
@@ -66,7 +104,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::TypeScript,
         display_name: "TypeScript",
-        scip_indexer: "scip-typescript",
+        scip_indexer: Some("scip-typescript"),
         scip_output_stem: "typescript",
         is_bundled: true,
         extensions: &["ts", "tsx", "mts", "cts"],
@@ -77,7 +115,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::JavaScript,
         display_name: "JavaScript",
-        scip_indexer: "scip-typescript",
+        scip_indexer: Some("scip-typescript"),
         scip_output_stem: "javascript",
         is_bundled: true,
         extensions: &["js", "jsx", "mjs", "cjs"],
@@ -88,7 +126,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Python,
         display_name: "Python",
-        scip_indexer: "scip-python",
+        scip_indexer: Some("scip-python"),
         scip_output_stem: "python",
         is_bundled: true,
         extensions: &["py", "pyi", "pyw"],
@@ -99,7 +137,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::CSharp,
         display_name: "C#",
-        scip_indexer: "scip-dotnet",
+        scip_indexer: Some("scip-dotnet"),
         scip_output_stem: "csharp",
         is_bundled: true,
         extensions: &["cs", "csx"],
@@ -110,7 +148,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Java,
         display_name: "Java",
-        scip_indexer: "scip-java",
+        scip_indexer: Some("scip-java"),
         scip_output_stem: "java",
         is_bundled: true,
         extensions: &["java"],
@@ -121,7 +159,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Kotlin,
         display_name: "Kotlin",
-        scip_indexer: "scip-java",
+        scip_indexer: Some("scip-java"),
         scip_output_stem: "kotlin",
         is_bundled: false,
         extensions: &["kt", "kts"],
@@ -132,7 +170,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Scala,
         display_name: "Scala",
-        scip_indexer: "scip-java",
+        scip_indexer: Some("scip-java"),
         scip_output_stem: "scala",
         is_bundled: false,
         extensions: &["scala", "sc"],
@@ -143,7 +181,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Go,
         display_name: "Go",
-        scip_indexer: "scip-go",
+        scip_indexer: Some("scip-go"),
         scip_output_stem: "go",
         is_bundled: true,
         extensions: &["go"],
@@ -154,18 +192,18 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Rust,
         display_name: "Rust",
-        scip_indexer: "rust-analyzer",
+        scip_indexer: Some("rust-analyzer"),
         scip_output_stem: "rust",
-        is_bundled: false,
+        is_bundled: true,
         extensions: &["rs"],
-        install_command: "cargo install scip-rust (via rust-analyzer)",
+        install_command: "rustup component add rust-analyzer rust-src",
         binary_names: &["rust-analyzer"],
         aliases: &["rust", "rs"],
     },
     LanguageSpec {
         language: Language::Ruby,
         display_name: "Ruby",
-        scip_indexer: "scip-ruby",
+        scip_indexer: Some("scip-ruby"),
         scip_output_stem: "ruby",
         is_bundled: false,
         extensions: &["rb", "rake", "gemspec"],
@@ -176,7 +214,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Php,
         display_name: "PHP",
-        scip_indexer: "scip-php",
+        scip_indexer: Some("scip-php"),
         scip_output_stem: "php",
         is_bundled: false,
         extensions: &["php", "phtml", "php3", "php4", "php5", "phps"],
@@ -187,7 +225,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Cpp,
         display_name: "C++",
-        scip_indexer: "scip-clang",
+        scip_indexer: Some("scip-clang"),
         scip_output_stem: "cpp",
         is_bundled: false,
         extensions: &["cpp", "cxx", "cc", "c++", "hpp", "hxx", "hh", "h++"],
@@ -198,7 +236,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::C,
         display_name: "C",
-        scip_indexer: "scip-clang",
+        scip_indexer: Some("scip-clang"),
         scip_output_stem: "c",
         is_bundled: false,
         extensions: &["c", "h"],
@@ -209,7 +247,7 @@ const SPECS: &[LanguageSpec] = &[
     LanguageSpec {
         language: Language::Dart,
         display_name: "Dart",
-        scip_indexer: "scip-dart",
+        scip_indexer: Some("scip-dart"),
         scip_output_stem: "dart",
         is_bundled: false,
         extensions: &["dart"],
@@ -217,6 +255,94 @@ const SPECS: &[LanguageSpec] = &[
         binary_names: &["scip-dart"],
         aliases: &["dart"],
     },
+    LanguageSpec {
+        language: Language::Swift,
+        display_name: "Swift",
+        scip_indexer: None,
+        scip_output_stem: "swift",
+        is_bundled: false,
+        extensions: &["swift"],
+        install_command: "No SCIP indexer available for Swift",
+        binary_names: &[],
+        aliases: &["swift"],
+    },
+    LanguageSpec {
+        language: Language::Elixir,
+        display_name: "Elixir",
+        scip_indexer: None,
+        scip_output_stem: "elixir",
+        is_bundled: false,
+        extensions: &["ex", "exs"],
+        install_command: "No SCIP indexer available for Elixir",
+        binary_names: &[],
+        aliases: &["elixir", "ex"],
+    },
+    LanguageSpec {
+        language: Language::Haskell,
+        display_name: "Haskell",
+        scip_indexer: None,
+        scip_output_stem: "haskell",
+        is_bundled: false,
+        extensions: &["hs", "lhs"],
+        install_command: "No SCIP indexer available for Haskell",
+        binary_names: &[],
+        aliases: &["haskell", "hs"],
+    },
+    LanguageSpec {
+        language: Language::Lua,
+        display_name: "Lua",
+        scip_indexer: None,
+        scip_output_stem: "lua",
+        is_bundled: false,
+        extensions: &["lua"],
+        install_command: "No SCIP indexer available for Lua",
+        binary_names: &[],
+        aliases: &["lua"],
+    },
+    LanguageSpec {
+        language: Language::Zig,
+        display_name: "Zig",
+        scip_indexer: Some("scip-zig"),
+        scip_output_stem: "zig",
+        is_bundled: false,
+        extensions: &["zig"],
+        install_command: "Build from source: https://github.com/sourcegraph/scip-zig",
+        binary_names: &["scip-zig"],
+        aliases: &["zig"],
+    },
+    LanguageSpec {
+        language: Language::Shell,
+        display_name: "Shell",
+        scip_indexer: None,
+        scip_output_stem: "shell",
+        is_bundled: false,
+        extensions: &["sh", "bash", "zsh"],
+        install_command: "No SCIP indexer available for shell scripts",
+        binary_names: &[],
+        aliases: &["shell", "sh", "bash"],
+    },
+    LanguageSpec {
+        language: Language::VisualBasic,
+        display_name: "Visual Basic",
+        scip_indexer: Some("scip-dotnet"),
+        scip_output_stem: "vb",
+        is_bundled: true,
+        extensions: &["vb"],
+        install_command: "dotnet tool install -g scip-dotnet",
+        binary_names: &["scip-dotnet"],
+        aliases: &["vb", "vbnet", "visualbasic"],
+    },
+    LanguageSpec {
+        language: Language::FSharp,
+        display_name: "F#",
+        scip_indexer: Some("scip-dotnet"),
+        scip_output_stem: "fsharp",
+        is_bundled: true,
+        extensions: &["fs", "fsi", "fsx"],
+        install_command: "dotnet tool install -g scip-dotnet",
+        binary_names: &["scip-dotnet"],
+        aliases: &["fsharp", "f#"],
+    },
 ];
 
 impl Language {
@@ -236,13 +362,22 @@ impl Language {
         Language::Cpp,
         Language::C,
         Language::Dart,
+        Language::Swift,
+        Language::Elixir,
+        Language::Haskell,
+        Language::Lua,
+        Language::Zig,
+        Language::Shell,
+        Language::VisualBasic,
+        Language::FSharp,
     ];
 
     fn spec(&self) -> &'static LanguageSpec {
         SPECS.iter().find(|s| s.language == *self).unwrap()
     }
 
-    pub fn scip_indexer(&self) -> &'static str { self.spec().scip_indexer }
+    pub fn scip_indexer(&self) -> Option<&'static str> { self.spec().scip_indexer }
+    pub fn has_scip_indexer(&self) -> bool { self.spec().scip_indexer.is_some() }
     pub fn display_name(&self) -> &'static str { self.spec().display_name }
     pub fn scip_output_stem(&self) -> &'static str { self.spec().scip_output_stem }
     pub fn is_bundled(&self) -> bool { self.spec().is_bundled }
@@ -257,6 +392,160 @@ impl Language {
             .find(|spec| spec.aliases.contains(&lower.as_str()))
             .map(|s| s.language)
     }
+
+    /// This language and those sharing its indexer, whose files it reads
+    /// too (scip-typescript indexes `.js` files)
+    fn indexed_with(&self) -> impl Iterator<Item = Language> + '_ {
+        Language::ALL.iter().copied().filter(move |&lang| {
+            lang == *self || (self.has_scip_indexer() && lang.scip_indexer() == self.scip_indexer())
+        })
+    }
+
+    /// Whether a file is a source file this language's indexer reads, by extension
+    pub fn is_source(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|e| e.to_str());
+        self.indexed_with()
+            .any(|lang| extension.is_some_and(|ext| lang.extensions().contains(&ext)))
+    }
+
+    /// Whether a file is one of the inputs of this language's indexer: a
+    /// source file, or a config file of a language it indexes
+    pub fn is_input(&self, path: &Path) -> bool {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.is_source(path)
+            || self.indexed_with().any(|lang| {
+                CONFIG_FILES.iter().any(|&(name, l)| l == lang && name == file_name)
+                    || CONFIG_SUFFIXES.iter().any(|&(suffix, l)| l == lang && file_name.ends_with(suffix))
+            })
+    }
+
+    /// Canonical `--languages` name (the first alias)
+    pub fn name(&self) -> &'static str { self.spec().aliases[0] }
+
+    /// The alias or filter keyword closest to an unrecognized name, if any is close
+    pub fn suggest(s: &str) -> Option<&'static str> {
+        let lower = s.to_lowercase();
+        SPECS.iter()
+            .flat_map(|spec| spec.aliases.iter().copied())
+            .chain(std::iter::once(ALL_AVAILABLE))
+            .map(|alias| (strsim::jaro_winkler(&lower, alias), alias))
+            .filter(|(score, _)| *score >= 0.8)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, alias)| alias)
+    }
+}
+
+/// `--languages` keyword for every detected language whose indexer is installed
+pub const ALL_AVAILABLE: &str = "all-available";
+
+/// A validated `--languages` filter
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LanguageFilter {
+    /// No filter: every detected language
+    #[default]
+    Detected,
+    /// Detected languages whose indexer can be found
+    AllAvailable,
+    /// Only these languages, where detected
+    Only(Vec<Language>),
+}
+
+impl LanguageFilter {
+    /// Validate `--languages` values. Unknown names are all reported at once,
+    /// each with the closest alias when one is near.
+    pub fn parse(values: &[String]) -> Result<Self, String> {
+        let values: Vec<&str> = values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()).collect();
+        if values.is_empty() {
+            return Ok(Self::Detected);
+        }
+        if values.iter().any(|v| v.eq_ignore_ascii_case(ALL_AVAILABLE)) {
+            if values.len() > 1 {
+                return Err(format!("'{}' cannot be combined with other languages", ALL_AVAILABLE));
+            }
+            return Ok(Self::AllAvailable);
+        }
+
+        let mut languages = Vec::new();
+        let mut unknown = Vec::new();
+        for value in values {
+            match Language::parse(value) {
+                Some(language) if !languages.contains(&language) => languages.push(language),
+                Some(_) => {}
+                None => unknown.push(match Language::suggest(value) {
+                    Some(alias) => format!("unknown language '{}' (did you mean '{}'?)", value, alias),
+                    None => format!("unknown language '{}'", value),
+                }),
+            }
+        }
+        if !unknown.is_empty() {
+            let names: Vec<&str> = Language::ALL.iter().map(|l| l.name()).collect();
+            return Err(format!(
+                "{}\nvalid languages: {}, or {}",
+                unknown.join("\n"),
+                names.join(", "),
+                ALL_AVAILABLE
+            ));
+        }
+        Ok(Self::Only(languages))
+    }
+
+    /// Keep the detected languages this filter selects; `available` says
+    /// whether a language's indexer can be found
+    pub fn apply(
+        &self,
+        detected: Vec<LanguageInfo>,
+        available: impl Fn(Language) -> bool,
+    ) -> Vec<LanguageInfo> {
+        match self {
+            Self::Detected => detected,
+            Self::AllAvailable => detected.into_iter().filter(|info| available(info.language)).collect(),
+            Self::Only(languages) => detected
+                .into_iter()
+                .filter(|info| languages.contains(&info.language))
+                .collect(),
+        }
+    }
+
+    /// Requested languages that were not detected
+    pub fn undetected(&self, detected: &[LanguageInfo]) -> Vec<Language> {
+        match self {
+            Self::Only(languages) => languages
+                .iter()
+                .copied()
+                .filter(|l| !detected.iter().any(|info| info.language == *l))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// How the directory walk treats symbolic links
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Follow links whose target resolves inside the root; skip the rest
+    #[default]
+    WithinRoot,
+    /// Follow every link, wherever it points
+    All,
+    /// Never follow links
+    Never,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "within-root" | "within_root" | "root" => Ok(Self::WithinRoot),
+            "all" | "follow" => Ok(Self::All),
+            "never" | "none" => Ok(Self::Never),
+            other => Err(format!(
+                "unknown symlink policy '{}' (expected within-root, all or never)",
+                other
+            )),
+        }
+    }
 }
 
 /// Information about detected language presence
@@ -265,12 +554,23 @@ pub struct LanguageInfo {
     pub language: Language,
     pub file_count: usize,
     pub config_files: Vec<PathBuf>,
+    /// Metadata parsed from the build manifests among `config_files`
+    #[serde(default)]
+    pub manifests: Vec<ProjectManifest>,
 }
 
 /// Summary of detection coverage across the codebase
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectionReport {
     pub languages: Vec<LanguageInfo>,
+    /// Languages with config files but no source files (file_count is 0),
+    /// sorted by language
+    #[serde(default)]
+    pub configured_empty: Vec<LanguageInfo>,
+    /// Frameworks and runtimes found via manifests and marker files,
+    /// sorted by location then framework
+    #[serde(default)]
+    pub frameworks: Vec<DetectedFramework>,
     /// Every file walked (after directory filtering, before extension matching)
     pub total_files: usize,
     /// Files matching a known language extension
@@ -279,10 +579,22 @@ pub struct DetectionReport {
     pub excluded_files: usize,
     /// Extensions that were not recognized, sorted descending by count
     pub unrecognized_extensions: Vec<ExtensionCount>,
-    /// Permission errors, broken symlinks, etc.
-    pub walk_errors: usize,
+    /// Entries that could not be walked, by cause
+    pub walk_errors: WalkErrors,
+    /// Symlink policy the walk ran with
+    #[serde(default)]
+    pub symlink_policy: SymlinkPolicy,
+    /// Symlinks whose target resolves outside the root, sorted by path
+    #[serde(default)]
+    pub escaping_symlinks: Vec<EscapingSymlink>,
+    /// Symlinks not followed because of the symlink policy
+    #[serde(default)]
+    pub skipped_symlinks: usize,
     /// supported_files / total_files * 100
     pub coverage_percent: f64,
+    /// Wall-clock time spent walking the tree, in milliseconds
+    #[serde(default)]
+    pub walk_duration_ms: u64,
 }
 
 /// Count of files with a particular unrecognized extension
@@ -293,62 +605,177 @@ pub struct ExtensionCount {
     pub count: usize,
 }
 
+/// Walk errors broken down by cause
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalkErrors {
+    /// Directories or links that could not be read
+    pub permission_denied: usize,
+    /// Symlinks that point back at one of their ancestors
+    pub symlink_loops: usize,
+    /// Symlinks whose target does not exist
+    pub broken_symlinks: usize,
+    /// Any other I/O error
+    pub other: usize,
+}
+
+impl WalkErrors {
+    /// Total number of walk errors across all causes
+    pub fn total(&self) -> usize {
+        self.permission_denied + self.symlink_loops + self.broken_symlinks + self.other
+    }
+
+    fn record(&mut self, kind: WalkErrorKind) {
+        match kind {
+            WalkErrorKind::PermissionDenied => self.permission_denied += 1,
+            WalkErrorKind::SymlinkLoop => self.symlink_loops += 1,
+            WalkErrorKind::BrokenSymlink => self.broken_symlinks += 1,
+            WalkErrorKind::Other => self.other += 1,
+        }
+    }
+
+    fn merge(&mut self, other: WalkErrors) {
+        self.permission_denied += other.permission_denied;
+        self.symlink_loops += other.symlink_loops;
+        self.broken_symlinks += other.broken_symlinks;
+        self.other += other.other;
+    }
+}
+
+/// A symlink found during the walk whose target lies outside the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscapingSymlink {
+    /// Link path relative to the root
+    pub path: PathBuf,
+    /// Canonical target of the link
+    pub target: PathBuf,
+    /// Whether the walk descended through the link
+    pub followed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum WalkErrorKind {
+    PermissionDenied,
+    SymlinkLoop,
+    BrokenSymlink,
+    Other,
+}
+
+impl WalkErrorKind {
+    /// Classify an I/O error; `via_symlink` is set when resolving a link target.
+    fn classify(e: &io::Error, via_symlink: bool) -> Self {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::NotFound if via_symlink => Self::BrokenSymlink,
+            _ if is_eloop(e) => Self::SymlinkLoop,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// `io::ErrorKind::FilesystemLoop` is unstable, so match ELOOP by errno.
+#[cfg(unix)]
+fn is_eloop(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ELOOP)
+}
+
+#[cfg(not(unix))]
+fn is_eloop(_e: &io::Error) -> bool {
+    false
+}
+
+/// Per-subtree counters produced by the parallel walk, merged bottom-up
+#[derive(Default)]
+struct WalkStats {
+    language_counts: HashMap<Language, usize>,
+    config_files: HashMap<Language, Vec<PathBuf>>,
+    unrecognized: HashMap<String, usize>,
+    total_files: usize,
+    excluded_files: usize,
+    walk_errors: WalkErrors,
+    escaping_symlinks: Vec<EscapingSymlink>,
+    skipped_symlinks: usize,
+}
+
+impl WalkStats {
+    fn merge(mut self, other: WalkStats) -> WalkStats {
+        for (lang, count) in other.language_counts {
+            *self.language_counts.entry(lang).or_insert(0) += count;
+        }
+        for (lang, files) in other.config_files {
+            self.config_files.entry(lang).or_default().extend(files);
+        }
+        for (ext, count) in other.unrecognized {
+            *self.unrecognized.entry(ext).or_insert(0) += count;
+        }
+        self.total_files += other.total_files;
+        self.excluded_files += other.excluded_files;
+        self.walk_errors.merge(other.walk_errors);
+        self.escaping_symlinks.extend(other.escaping_symlinks);
+        self.skipped_symlinks += other.skipped_symlinks;
+        self
+    }
+}
+
+/// Read-only state shared by every walker thread
+struct WalkContext<'a> {
+    root: &'a Path,
+    /// Canonical form of `root`, used to decide whether a link escapes it
+    root_canonical: PathBuf,
+    ext_to_lang: HashMap<&'static str, Language>,
+    config_lookup: HashMap<&'static str, Language>,
+    /// Number of walk errors logged so far (only the first few are warned)
+    warned_errors: AtomicUsize,
+}
+
+impl WalkContext<'_> {
+    fn walk_error(&self, stats: &mut WalkStats, kind: WalkErrorKind, detail: fmt::Arguments) {
+        stats.walk_errors.record(kind);
+        if self.warned_errors.fetch_add(1, Ordering::Relaxed) < 5 {
+            warn!("Skipping inaccessible path ({:?}): {}", kind, detail);
+        } else {
+            debug!("Skipping inaccessible path ({:?}): {}", kind, detail);
+        }
+    }
+}
+
 /// Language detector for codebases
 pub struct LanguageDetector {
-    exclude_patterns: Vec<glob::Pattern>,
+    path_filter: PathFilter,
+    symlink_policy: SymlinkPolicy,
 }
 
 impl LanguageDetector {
     /// Create a new language detector with exclusion patterns
     pub fn new(exclude_patterns: &[String]) -> Self {
-        let patterns = exclude_patterns
-            .iter()
-            .filter_map(|p| glob::Pattern::new(p).ok())
-            .collect();
+        Self::from_filter(PathFilter::new(&[], exclude_patterns))
+    }
+
+    /// Create a detector with include and (possibly negated) exclude patterns
+    pub fn from_filter(path_filter: PathFilter) -> Self {
         Self {
-            exclude_patterns: patterns,
+            path_filter,
+            symlink_policy: SymlinkPolicy::default(),
         }
     }
 
-    /// Check if a path should be excluded.
-    ///
-    /// In addition to standard glob matching against the full relative path,
-    /// this also does component-based matching: for patterns like `dirname/**`,
-    /// any path containing `dirname` as a component is excluded. This ensures
-    /// nested occurrences (e.g. `packages/foo/node_modules/bar/`) are caught.
-    fn should_exclude(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-        let file_name = path.file_name().unwrap_or_default().to_str().unwrap_or("");
-
-        self.exclude_patterns.iter().any(|p| {
-            // Standard glob match against full relative path or filename
-            if p.matches(&path_str) || p.matches(file_name) {
-                return true;
-            }
-
-            // Component-based match: if the pattern looks like `dirname/**`,
-            // check if any path component equals `dirname`.
-            let pattern_str = p.as_str();
-            if let Some(dirname) = pattern_str.strip_suffix("/**") {
-                if !dirname.contains('/') {
-                    return path.components().any(|c| {
-                        c.as_os_str().to_str() == Some(dirname)
-                    });
-                }
-            }
+    /// Set how symlinks are treated during the walk
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = policy;
+        self
+    }
 
-            false
-        })
+    /// Check if a path (relative to the root) should be excluded
+    fn should_exclude(&self, path: &Path) -> bool {
+        self.path_filter.is_excluded(path)
     }
 
     /// Detect all languages present in the codebase
+    ///
+    /// Sibling directories are walked in parallel. Every list in the returned
+    /// report is sorted with a total order, so the output does not depend on
+    /// thread scheduling (only `walk_duration_ms` varies between runs).
     pub fn detect(&self, root_path: &Path) -> Result<DetectionReport> {
-        let mut language_counts: HashMap<Language, usize> = HashMap::new();
-        let mut config_files: HashMap<Language, Vec<PathBuf>> = HashMap::new();
-        let mut unrecognized_map: HashMap<String, usize> = HashMap::new();
-        let mut total_files: usize = 0;
-        let mut excluded_files: usize = 0;
-        let mut walk_errors: usize = 0;
+        let start = Instant::now();
 
         // Build extension to language mapping
         let ext_to_lang: HashMap<&str, Language> = Language::ALL
@@ -359,97 +786,124 @@ impl LanguageDetector {
         // Build config file lookup
         let config_lookup: HashMap<&str, Language> = CONFIG_FILES.iter().copied().collect();
 
-        // Walk the directory tree
-        let walker = WalkDir::new(root_path).follow_links(true).into_iter();
-        for entry_result in walker {
-            let entry = match entry_result {
-                Ok(e) => e,
-                Err(e) => {
-                    walk_errors += 1;
-                    if walk_errors <= 5 {
-                        warn!("Skipping inaccessible path: {}", e);
-                    }
-                    continue;
-                }
-            };
-            let path = entry.path();
-
-            // Skip excluded paths (count excluded files)
-            if let Ok(rel_path) = path.strip_prefix(root_path) {
-                if self.should_exclude(rel_path) {
-                    if path.is_file() {
-                        excluded_files += 1;
-                    }
-                    continue;
-                }
-            }
-
-            if !path.is_file() {
-                continue;
-            }
-
-            total_files += 1;
-
-            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let root_canonical = std::fs::canonicalize(root_path)
+            .unwrap_or_else(|_| root_path.to_path_buf());
+        let ctx = WalkContext {
+            root: root_path,
+            root_canonical: root_canonical.clone(),
+            ext_to_lang,
+            config_lookup,
+            warned_errors: AtomicUsize::new(0),
+        };
 
-            // Check for config files that indicate language
-            if let Some(&lang) = config_lookup.get(file_name) {
-                config_files
-                    .entry(lang)
-                    .or_default()
-                    .push(path.to_path_buf());
+        // Walk the directory tree
+        let stats = match std::fs::metadata(root_path) {
+            Ok(meta) if meta.is_dir() => self.walk_dir(&ctx, root_path, &[root_canonical]),
+            Ok(_) => {
+                let mut stats = WalkStats::default();
+                self.visit_file(&ctx, root_path, &mut stats);
+                stats
             }
-
-            // Check for .csproj / .sln files (suffix match, not exact)
-            if file_name.ends_with(".csproj") || file_name.ends_with(".sln") {
-                config_files
-                    .entry(Language::CSharp)
-                    .or_default()
-                    .push(path.to_path_buf());
+            Err(e) => {
+                let mut stats = WalkStats::default();
+                let kind = WalkErrorKind::classify(&e, false);
+                ctx.walk_error(&mut stats, kind, format_args!("{}: {}", root_path.display(), e));
+                stats
             }
+        };
+        let walk_duration_ms = start.elapsed().as_millis() as u64;
+        debug!("Walked {:?} in {}ms", root_path, walk_duration_ms);
 
-            // Count files by extension
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if let Some(&lang) = ext_to_lang.get(ext) {
-                    *language_counts.entry(lang).or_insert(0) += 1;
-                } else {
-                    *unrecognized_map.entry(ext.to_lowercase()).or_insert(0) += 1;
-                }
-            } else {
-                // Files with no extension (e.g. Makefile, Dockerfile)
-                *unrecognized_map.entry(String::new()).or_insert(0) += 1;
-            }
-        }
+        let WalkStats {
+            language_counts,
+            mut config_files,
+            unrecognized: unrecognized_map,
+            total_files,
+            excluded_files,
+            walk_errors,
+            mut escaping_symlinks,
+            skipped_symlinks,
+        } = stats;
 
-        if walk_errors > 0 {
+        if walk_errors.total() > 0 {
             warn!(
-                "Skipped {} inaccessible entries during directory walk (permission errors, broken symlinks, etc.)",
-                walk_errors
+                "Skipped {} inaccessible entries during directory walk ({} permission denied, {} symlink loops, {} broken symlinks, {} other)",
+                walk_errors.total(),
+                walk_errors.permission_denied,
+                walk_errors.symlink_loops,
+                walk_errors.broken_symlinks,
+                walk_errors.other
             );
         }
+        escaping_symlinks.sort_by(|a, b| a.path.cmp(&b.path));
 
         // Build language result
         let mut languages: Vec<LanguageInfo> = language_counts
             .into_iter()
-            .map(|(language, file_count)| LanguageInfo {
-                language,
-                file_count,
-                config_files: config_files.remove(&language).unwrap_or_default(),
+            .map(|(language, file_count)| {
+                let mut files = config_files.remove(&language).unwrap_or_default();
+                files.sort();
+                LanguageInfo {
+                    language,
+                    file_count,
+                    config_files: files,
+                    manifests: Vec::new(),
+                }
             })
             .collect();
 
-        // Sort by file count (descending)
-        languages.sort_by(|a, b| b.file_count.cmp(&a.file_count));
+        // Sort by file count (descending), ties broken by language
+        languages.sort_by(|a, b| {
+            b.file_count
+                .cmp(&a.file_count)
+                .then_with(|| a.language.cmp(&b.language))
+        });
+
+        // Config files left over belong to languages without sources. Hand them
+        // to a detected language sharing the same indexer (package.json in a
+        // TypeScript-only repo); anything else is configured but empty.
+        let mut leftover: Vec<(Language, Vec<PathBuf>)> = config_files.into_iter().collect();
+        leftover.sort_by_key(|(language, _)| *language);
+        let mut configured_empty = Vec::new();
+        for (language, mut files) in leftover {
+            let sibling = languages.iter_mut().find(|l| {
+                language.has_scip_indexer() && l.language.scip_indexer() == language.scip_indexer()
+            });
+            match sibling {
+                Some(info) => {
+                    info.config_files.append(&mut files);
+                    info.config_files.sort();
+                }
+                None => {
+                    files.sort();
+                    configured_empty.push(LanguageInfo {
+                        language,
+                        file_count: 0,
+                        config_files: files,
+                        manifests: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        for info in languages.iter_mut().chain(configured_empty.iter_mut()) {
+            info.manifests = parse_manifests(&info.config_files);
+        }
+        let frameworks = framework::detect_frameworks(languages.iter().chain(&configured_empty));
 
         let supported_files: usize = languages.iter().map(|l| l.file_count).sum();
 
-        // Build unrecognized extensions list, sorted desc by count
+        // Build unrecognized extensions list, sorted desc by count then by name
         let mut unrecognized_extensions: Vec<ExtensionCount> = unrecognized_map
             .into_iter()
             .filter(|(ext, _)| !ext.is_empty()) // skip no-extension files from the named list
             .map(|(extension, count)| ExtensionCount { extension, count })
             .collect();
-        unrecognized_extensions.sort_by(|a, b| b.count.cmp(&a.count));
+        unrecognized_extensions.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
 
         let coverage_percent = if total_files > 0 {
             (supported_files as f64 / total_files as f64) * 100.0
@@ -459,16 +913,196 @@ impl LanguageDetector {
 
         Ok(DetectionReport {
             languages,
+            configured_empty,
+            frameworks,
             total_files,
             supported_files,
             excluded_files,
             unrecognized_extensions,
             walk_errors,
+            symlink_policy: self.symlink_policy,
+            escaping_symlinks,
+            skipped_symlinks,
             coverage_percent,
+            walk_duration_ms,
         })
     }
 
+    /// Walk one directory, then recurse into its subdirectories in parallel.
+    ///
+    /// `ancestors` holds the canonical paths of every directory on the way
+    /// down from the root (including `dir`), used to detect symlink loops.
+    fn walk_dir(&self, ctx: &WalkContext, dir: &Path, ancestors: &[PathBuf]) -> WalkStats {
+        let mut stats = WalkStats::default();
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let kind = WalkErrorKind::classify(&e, false);
+                ctx.walk_error(&mut stats, kind, format_args!("{}: {}", dir.display(), e));
+                return stats;
+            }
+        };
+
+        let parent = ancestors.last().expect("ancestors always contains the walked dir");
+        let mut subdirs: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for entry_result in entries {
+            let entry = match entry_result {
+                Ok(e) => e,
+                Err(e) => {
+                    let kind = WalkErrorKind::classify(&e, false);
+                    ctx.walk_error(&mut stats, kind, format_args!("{}: {}", dir.display(), e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(e) => {
+                    let kind = WalkErrorKind::classify(&e, false);
+                    ctx.walk_error(&mut stats, kind, format_args!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            let (is_dir, is_file, canonical) = if file_type.is_symlink() {
+                match self.resolve_symlink(ctx, &path, &mut stats) {
+                    Some(resolved) => resolved,
+                    None => continue,
+                }
+            } else {
+                (file_type.is_dir(), file_type.is_file(), parent.join(entry.file_name()))
+            };
+
+            if is_dir {
+                // Our own output (indexes, generated tsconfig, CMake build trees)
+                if dir == ctx.root && entry.file_name() == WORK_DIR_NAME {
+                    continue;
+                }
+                if ancestors.contains(&canonical) {
+                    ctx.walk_error(
+                        &mut stats,
+                        WalkErrorKind::SymlinkLoop,
+                        format_args!("{}: symlink loop to {}", path.display(), canonical.display()),
+                    );
+                    continue;
+                }
+                // Excluded directories are still descended so that their
+                // files are counted in `excluded_files`.
+                subdirs.push((path, canonical));
+            } else if is_file {
+                self.visit_file(ctx, &path, &mut stats);
+            }
+        }
+
+        subdirs
+            .par_iter()
+            .map(|(path, canonical)| {
+                let mut chain = ancestors.to_vec();
+                chain.push(canonical.clone());
+                self.walk_dir(ctx, path, &chain)
+            })
+            .reduce(WalkStats::default, WalkStats::merge)
+            .merge(stats)
+    }
+
+    /// Apply the symlink policy to a link found during the walk.
+    ///
+    /// Returns `(is_dir, is_file, canonical_target)` when the link should be
+    /// followed, or `None` when it was skipped or could not be resolved.
+    fn resolve_symlink(
+        &self,
+        ctx: &WalkContext,
+        path: &Path,
+        stats: &mut WalkStats,
+    ) -> Option<(bool, bool, PathBuf)> {
+        if self.symlink_policy == SymlinkPolicy::Never {
+            stats.skipped_symlinks += 1;
+            return None;
+        }
+
+        let resolved = std::fs::canonicalize(path).and_then(|target| {
+            std::fs::metadata(&target).map(|meta| (target, meta))
+        });
+        let (target, metadata) = match resolved {
+            Ok(r) => r,
+            Err(e) => {
+                let kind = WalkErrorKind::classify(&e, true);
+                ctx.walk_error(stats, kind, format_args!("{}: {}", path.display(), e));
+                return None;
+            }
+        };
+
+        if !target.starts_with(&ctx.root_canonical) {
+            let followed = self.symlink_policy == SymlinkPolicy::All;
+            debug!(
+                "Symlink {:?} escapes the root (-> {:?}){}",
+                path,
+                target,
+                if followed { "" } else { ", not following" }
+            );
+            stats.escaping_symlinks.push(EscapingSymlink {
+                path: path.strip_prefix(ctx.root).unwrap_or(path).to_path_buf(),
+                target: target.clone(),
+                followed,
+            });
+            if !followed {
+                stats.skipped_symlinks += 1;
+                return None;
+            }
+        }
+
+        Some((metadata.is_dir(), metadata.is_file(), target))
+    }
+
+    /// Record a single file: exclusion, config-file and extension matching.
+    fn visit_file(&self, ctx: &WalkContext, path: &Path, stats: &mut WalkStats) {
+        // Skip excluded paths (count excluded files)
+        if let Ok(rel_path) = path.strip_prefix(ctx.root) {
+            if self.should_exclude(rel_path) {
+                stats.excluded_files += 1;
+                return;
+            }
+        }
+
+        stats.total_files += 1;
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+
+        // Check for config files that indicate language
+        if let Some(&lang) = ctx.config_lookup.get(file_name) {
+            stats
+                .config_files
+                .entry(lang)
+                .or_default()
+                .push(path.to_path_buf());
+        }
+
+        // Check for .csproj / .sln / .cabal etc. files (suffix match, not exact)
+        if let Some(&(_, lang)) = CONFIG_SUFFIXES.iter().find(|(suffix, _)| file_name.ends_with(suffix)) {
+            stats
+                .config_files
+                .entry(lang)
+                .or_default()
+                .push(path.to_path_buf());
+        }
+
+        // Count files by extension
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            if let Some(&lang) = ctx.ext_to_lang.get(ext) {
+                *stats.language_counts.entry(lang).or_insert(0) += 1;
+            } else {
+                *stats.unrecognized.entry(ext.to_lowercase()).or_insert(0) += 1;
+            }
+        } else {
+            // Files with no extension (e.g. Makefile, Dockerfile)
+            *stats.unrecognized.entry(String::new()).or_insert(0) += 1;
+        }
+    }
+
     /// Filter detected languages to only include specified ones
+    ///
+    /// Unrecognized names are ignored; `LanguageFilter::parse` rejects them instead.
     pub fn filter_languages(
         detected: Vec<LanguageInfo>,
         filter: &[String],
@@ -489,6 +1123,27 @@ impl LanguageDetector {
     }
 }
 
+/// Parse every manifest among a language's config files, in path order.
+///
+/// Unparseable manifests are logged and skipped rather than failing detection.
+fn parse_manifests(config_files: &[PathBuf]) -> Vec<ProjectManifest> {
+    config_files
+        .par_iter()
+        .filter_map(|path| match manifest::parse_manifest(path) {
+            Ok(mut parsed) => {
+                if let Some(m) = parsed.as_mut().filter(|m| m.kind.is_python()) {
+                    m.python_environment = pyenv::discover(m);
+                }
+                parsed
+            }
+            Err(e) => {
+                warn!("{:#}", e);
+                None
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -511,6 +1166,27 @@ mod tests {
         assert_eq!(Language::parse("unknown"), None);
     }
 
+    #[test]
+    fn test_language_filter_parse() {
+        let values = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(LanguageFilter::parse(&[]), Ok(LanguageFilter::Detected));
+        assert_eq!(LanguageFilter::parse(&values(&["All-Available"])), Ok(LanguageFilter::AllAvailable));
+        assert_eq!(
+            LanguageFilter::parse(&values(&["ts", "typescript", "py"])),
+            Ok(LanguageFilter::Only(vec![Language::TypeScript, Language::Python]))
+        );
+
+        let err = LanguageFilter::parse(&values(&["typscript", "pyhton", "qqq"])).unwrap_err();
+        assert!(err.contains("unknown language 'typscript' (did you mean 'typescript'?)"), "{}", err);
+        assert!(err.contains("unknown language 'pyhton' (did you mean 'python'?)"), "{}", err);
+        assert!(err.contains("unknown language 'qqq'\n"), "{}", err);
+        assert!(err.contains("valid languages: typescript, javascript,"), "{}", err);
+
+        assert!(LanguageFilter::parse(&values(&["all-available", "go"])).is_err());
+        assert_eq!(Language::suggest("all-avilable"), Some(ALL_AVAILABLE));
+    }
+
     #[test]
     fn test_detect_typescript() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -0,0 +1,484 @@
+//! Environment diagnostics
+//!
+//! `doctor` checks what a run depends on outside this binary: the indexers
+//! and the toolchains they drive, at versions known to work, and an output
+//! directory that is writable and has room for the indexes. Each problem
+//! comes with the command or setting that fixes it.
+
+use crate::detect::Language;
+use crate::orchestrate::{BinarySource, IndexerOrchestrator};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long a `--version` query may take before the tool is reported as hung
+const VERSION_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Free space below which indexing is expected to fail
+const DISK_FAIL_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Free space below which large codebases may not fit
+const DISK_WARN_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// What a check is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Indexer,
+    Toolchain,
+    Output,
+}
+
+/// One diagnostic and, if it is not ok, how to fix it
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub category: Category,
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+/// A `major.minor.patch` version read from a tool's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Version(pub u64, pub u64, pub u64);
+
+impl Version {
+    /// The first `x.y` or `x.y.z` in `text`, e.g. `17.0.2` from
+    /// `openjdk version "17.0.2"` or `1.22.1` from `go version go1.22.1`
+    pub fn find_in(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        for start in 0..bytes.len() {
+            if !bytes[start].is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_digit()) {
+                continue;
+            }
+            let token: String = text[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            let parts: Vec<u64> = token.split('.').map_while(|p| p.parse().ok()).collect();
+            if parts.len() >= 2 {
+                return Some(Self(parts[0], parts[1], parts.get(2).copied().unwrap_or(0)));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Versions of an indexer known to produce usable indexes
+struct IndexerRequirement {
+    binary: &'static str,
+    min: Version,
+    /// First version with breaking changes, if any are known
+    below: Option<Version>,
+}
+
+const INDEXER_REQUIREMENTS: &[IndexerRequirement] = &[
+    IndexerRequirement { binary: "scip-typescript", min: Version(0, 3, 0), below: Some(Version(1, 0, 0)) },
+    IndexerRequirement { binary: "scip-python", min: Version(0, 6, 0), below: Some(Version(1, 0, 0)) },
+    IndexerRequirement { binary: "scip-dotnet", min: Version(0, 2, 0), below: None },
+    IndexerRequirement { binary: "scip-java", min: Version(0, 10, 0), below: None },
+    IndexerRequirement { binary: "scip-go", min: Version(0, 1, 0), below: None },
+    // rustup reports the toolchain version (1.x), standalone builds 0.3.x
+    IndexerRequirement { binary: "rust-analyzer", min: Version(0, 3, 1300), below: None },
+    IndexerRequirement { binary: "scip-ruby", min: Version(0, 3, 0), below: None },
+    IndexerRequirement { binary: "scip-clang", min: Version(0, 3, 0), below: None },
+];
+
+/// A toolchain an indexer drives
+struct Toolchain {
+    binary: &'static str,
+    version_args: &'static [&'static str],
+    min: Version,
+    languages: &'static [Language],
+    install: &'static str,
+}
+
+const TOOLCHAINS: &[Toolchain] = &[
+    Toolchain {
+        binary: "node",
+        version_args: &["--version"],
+        min: Version(18, 0, 0),
+        languages: &[Language::TypeScript, Language::JavaScript, Language::Python],
+        install: "install Node.js 18 or newer (https://nodejs.org)",
+    },
+    Toolchain {
+        binary: "java",
+        version_args: &["-version"],
+        min: Version(11, 0, 0),
+        languages: &[Language::Java, Language::Kotlin, Language::Scala],
+        install: "install a JDK 11 or newer (e.g. apt install openjdk-17-jdk) and put `java` on PATH",
+    },
+    Toolchain {
+        binary: "dotnet",
+        version_args: &["--version"],
+        min: Version(6, 0, 0),
+        languages: &[Language::CSharp, Language::VisualBasic, Language::FSharp],
+        install: "install the .NET SDK 6 or newer (https://dotnet.microsoft.com/download)",
+    },
+    Toolchain {
+        binary: "go",
+        version_args: &["version"],
+        min: Version(1, 19, 0),
+        languages: &[Language::Go],
+        install: "install Go 1.19 or newer (https://go.dev/dl)",
+    },
+    Toolchain {
+        binary: "cargo",
+        version_args: &["--version"],
+        min: Version(1, 70, 0),
+        languages: &[Language::Rust],
+        install: "install Rust with rustup (https://rustup.rs), then `rustup update`",
+    },
+    Toolchain {
+        binary: "php",
+        version_args: &["--version"],
+        min: Version(8, 2, 0),
+        languages: &[Language::Php],
+        install: "install PHP 8.2 or newer",
+    },
+    Toolchain {
+        binary: "composer",
+        version_args: &["--version"],
+        min: Version(2, 0, 0),
+        languages: &[Language::Php],
+        install: "install Composer 2 (https://getcomposer.org/download)",
+    },
+];
+
+/// Run every check. With `languages` (detected in a target codebase) only
+/// the indexers and toolchains they need are checked and anything missing is
+/// a failure; without, everything is checked and missing tools are warnings.
+/// `output_dirs` are where work files and indexes would be written, if known.
+pub fn run(
+    orchestrator: &IndexerOrchestrator,
+    languages: Option<&[Language]>,
+    output_dirs: &[PathBuf],
+) -> Vec<Check> {
+    let needed = |lang: &Language| languages.is_none_or(|l| l.contains(lang));
+    let missing = if languages.is_some() { Status::Fail } else { Status::Warn };
+
+    let mut checks = Vec::new();
+    let mut seen = Vec::new();
+    for &language in Language::ALL.iter().filter(|l| needed(l)) {
+        let Some(indexer) = language.scip_indexer() else {
+            continue;
+        };
+        if seen.contains(&indexer) {
+            continue;
+        }
+        seen.push(indexer);
+        checks.push(check_indexer(orchestrator, language, indexer, missing));
+    }
+
+    for toolchain in TOOLCHAINS {
+        if toolchain.languages.iter().any(needed) {
+            checks.push(check_toolchain(toolchain, missing));
+        }
+    }
+
+    for dir in output_dirs {
+        checks.push(check_writable(dir));
+        checks.push(check_disk_space(dir));
+    }
+    checks
+}
+
+fn check_indexer(orchestrator: &IndexerOrchestrator, language: Language, indexer: &str, missing: Status) -> Check {
+    let check = |status, detail: String, remediation: Option<String>| Check {
+        category: Category::Indexer,
+        name: indexer.to_string(),
+        status,
+        detail,
+        remediation,
+    };
+    let install = language.install_command().to_string();
+
+    let (program, source) = match orchestrator.resolve_indexer(language) {
+        Some((_, BinarySource::Missing)) | None => {
+            return check(missing, "not found".into(), Some(install));
+        }
+        Some(resolved) => resolved,
+    };
+    let location = format!("{} ({})", program.display(), source_label(source));
+
+    // Launchers would fetch or look up the tool at run time; asking them for
+    // a version could itself download it
+    if matches!(source, BinarySource::Npx | BinarySource::DotnetToolRun) {
+        return check(
+            Status::Warn,
+            format!("runs through {}; version not checked", location),
+            Some(format!("install it so the version is pinned: {}", install)),
+        );
+    }
+
+    let Some(output) = version_output(&program, &["--version"]) else {
+        return check(
+            Status::Warn,
+            format!("{}: `--version` failed or timed out", location),
+            Some(format!("reinstall: {}", install)),
+        );
+    };
+    let Some(version) = Version::find_in(&output) else {
+        return check(Status::Warn, format!("{}: version not recognized", location), None);
+    };
+
+    let detail = format!("{} at {}", version, location);
+    let Some(requirement) = INDEXER_REQUIREMENTS.iter().find(|r| r.binary == indexer) else {
+        return check(Status::Ok, detail, None);
+    };
+    if version < requirement.min {
+        return check(
+            Status::Fail,
+            format!("{}; {} or newer is required", detail, requirement.min),
+            Some(format!("upgrade: {}", install)),
+        );
+    }
+    if let Some(below) = requirement.below.filter(|b| version >= *b) {
+        return check(
+            Status::Warn,
+            format!("{}; only versions below {} are known to work", detail, below),
+            Some(format!("if indexing fails, install a {}.x release", requirement.min.0)),
+        );
+    }
+    check(Status::Ok, detail, None)
+}
+
+fn check_toolchain(toolchain: &Toolchain, missing: Status) -> Check {
+    let check = |status, detail: String, remediation: Option<&str>| Check {
+        category: Category::Toolchain,
+        name: toolchain.binary.to_string(),
+        status,
+        detail,
+        remediation: remediation.map(str::to_string),
+    };
+
+    let Ok(program) = which::which(toolchain.binary) else {
+        return check(missing, "not found in PATH".into(), Some(toolchain.install));
+    };
+    let version = version_output(&program, toolchain.version_args)
+        .as_deref()
+        .and_then(Version::find_in);
+    match version {
+        None => check(
+            Status::Warn,
+            format!("{}: version not recognized", program.display()),
+            Some(toolchain.install),
+        ),
+        Some(v) if v < toolchain.min => check(
+            Status::Fail,
+            format!("{} at {}; {} or newer is required", v, program.display(), toolchain.min),
+            Some(toolchain.install),
+        ),
+        Some(v) => check(Status::Ok, format!("{} at {}", v, program.display()), None),
+    }
+}
+
+/// The directory itself, or the nearest ancestor that exists (where it
+/// would be created)
+fn existing_ancestor(dir: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(dir).ok()?;
+    absolute.ancestors().find(|d| d.is_dir()).map(Path::to_path_buf)
+}
+
+fn check_writable(dir: &Path) -> Check {
+    let check = |status, detail: String, remediation: Option<String>| Check {
+        category: Category::Output,
+        name: "output directory".into(),
+        status,
+        detail,
+        remediation,
+    };
+
+    let Some(existing) = existing_ancestor(dir) else {
+        return check(Status::Fail, format!("{} cannot be resolved", dir.display()), None);
+    };
+    let probe = existing.join(format!(".legend-indexer-doctor-{}", std::process::id()));
+    let written = std::fs::write(&probe, b"");
+    let _ = std::fs::remove_file(&probe);
+
+    let target = if dir.is_dir() { "".to_string() } else { format!(" (would be created in {})", existing.display()) };
+    match written {
+        Ok(()) => check(Status::Ok, format!("{} is writable{}", dir.display(), target), None),
+        Err(e) => check(
+            Status::Fail,
+            format!("{} is not writable{}: {}", dir.display(), target, e),
+            Some(format!("fix permissions on {} or write elsewhere with --work-dir <DIR> and --output <DIR>", existing.display())),
+        ),
+    }
+}
+
+fn check_disk_space(dir: &Path) -> Check {
+    let check = |status, detail: String, remediation: Option<String>| Check {
+        category: Category::Output,
+        name: "disk space".into(),
+        status,
+        detail,
+        remediation,
+    };
+
+    let Some(existing) = existing_ancestor(dir) else {
+        return check(Status::Warn, format!("{} cannot be resolved", dir.display()), None);
+    };
+    let Some(free) = available_space(&existing) else {
+        return check(Status::Warn, "free space could not be determined".into(), None);
+    };
+
+    let detail = format!("{} free on {}", format_bytes(free), existing.display());
+    let remediation = Some("free up space or pass --output <DIR> on a larger volume".to_string());
+    if free < DISK_FAIL_BYTES {
+        check(Status::Fail, detail, remediation)
+    } else if free < DISK_WARN_BYTES {
+        check(Status::Warn, format!("{}; large codebases may not fit", detail), remediation)
+    } else {
+        check(Status::Ok, detail, None)
+    }
+}
+
+#[cfg(unix)]
+fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// A size in MiB, or GiB from 1 GiB up
+pub fn format_bytes(bytes: u64) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB)
+    } else {
+        format!("{:.0} MiB", bytes as f64 / MIB)
+    }
+}
+
+fn source_label(source: BinarySource) -> &'static str {
+    match source {
+        BinarySource::Bundled => "bundled",
+        BinarySource::Path => "PATH",
+        BinarySource::Npx => "npx",
+        BinarySource::DotnetGlobalTool => "dotnet global tool",
+        BinarySource::DotnetToolRun => "dotnet tool run",
+        BinarySource::Missing => "missing",
+    }
+}
+
+/// Combined stdout and stderr of a version query, or `None` if the program
+/// could not run, failed, or did not finish within `VERSION_TIMEOUT`
+fn version_output(program: &Path, args: &[&str]) -> Option<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + VERSION_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_found_in_tool_output() {
+        assert_eq!(Version::find_in("v20.11.1\n"), Some(Version(20, 11, 1)));
+        assert_eq!(Version::find_in("go version go1.22.1 linux/amd64"), Some(Version(1, 22, 1)));
+        assert_eq!(
+            Version::find_in("openjdk version \"17.0.2\" 2022-01-18"),
+            Some(Version(17, 0, 2))
+        );
+        assert_eq!(Version::find_in("cargo 1.75.0 (1d8b05cdd 2023-10-20)"), Some(Version(1, 75, 0)));
+        assert_eq!(Version::find_in("8.0"), Some(Version(8, 0, 0)));
+        assert_eq!(Version::find_in("no version here 42"), None);
+        assert!(Version(1, 79, 0) > Version(0, 3, 1300));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_indexer_version_checked_against_requirement() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let indexers = tempfile::TempDir::new().unwrap();
+        let install = |version: &str| {
+            let path = indexers.path().join("scip-go");
+            std::fs::write(&path, format!("#!/bin/sh\necho \"scip-go v{}\"\n", version)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        let orch = IndexerOrchestrator::for_plan(PathBuf::from("."), Some(indexers.path().to_path_buf()));
+
+        install("0.1.4");
+        let ok = check_indexer(&orch, Language::Go, "scip-go", Status::Fail);
+        assert_eq!(ok.status, Status::Ok, "{}", ok.detail);
+        assert!(ok.detail.starts_with("0.1.4 at "));
+
+        install("0.0.9");
+        let old = check_indexer(&orch, Language::Go, "scip-go", Status::Fail);
+        assert_eq!(old.status, Status::Fail);
+        assert!(old.remediation.unwrap().contains("go install"));
+    }
+
+    #[test]
+    fn test_output_checks() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let missing = temp.path().join("repo/.legend-indexer");
+
+        let writable = check_writable(&missing);
+        assert_eq!(writable.status, Status::Ok, "{}", writable.detail);
+        assert!(writable.detail.contains("would be created in"));
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+
+        let space = check_disk_space(temp.path());
+        assert_ne!(space.detail, "free space could not be determined");
+    }
+}
@@ -0,0 +1,480 @@
+//! Framework and runtime detection
+//!
+//! Layered on top of language detection: matches the dependencies parsed from
+//! build manifests and marker files next to them against a static table of
+//! known frameworks.
+
+use crate::detect::{Language, LanguageInfo};
+use crate::manifest::ProjectManifest;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Frameworks and runtimes we recognize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framework {
+    NextJs,
+    Nuxt,
+    Remix,
+    NestJs,
+    Express,
+    Angular,
+    React,
+    ReactNative,
+    Vue,
+    Svelte,
+    Electron,
+    Bun,
+    Django,
+    Flask,
+    FastApi,
+    SpringBoot,
+    Quarkus,
+    Micronaut,
+    Ktor,
+    AspNetCore,
+    Gin,
+    Echo,
+    Fiber,
+    Rails,
+    Laravel,
+    Symfony,
+    ActixWeb,
+    Axum,
+    Rocket,
+    Flutter,
+}
+
+/// How strongly the evidence points at a framework
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// Marker file only
+    Low,
+    /// Declared dependency only
+    Medium,
+    /// Declared dependency and marker file
+    High,
+}
+
+/// A framework found in one project of the codebase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectedFramework {
+    pub framework: Framework,
+    pub display_name: String,
+    pub language: Language,
+    pub confidence: Confidence,
+    /// Project root (directory of the manifest) the framework was found in
+    pub location: PathBuf,
+    /// What matched, e.g. `dependency next@^14.0.0`, `marker next.config.js`
+    pub evidence: Vec<String>,
+}
+
+const JS: &[Language] = &[Language::TypeScript, Language::JavaScript];
+const JVM: &[Language] = &[Language::Java, Language::Kotlin, Language::Scala];
+
+/// Static metadata for each framework.
+///
+/// Dependency names ending in `*` match by prefix; all matching is
+/// case-insensitive. The MSBuild project SDK of .NET manifests matches like a
+/// dependency. Marker files are relative to the manifest's directory.
+struct FrameworkSpec {
+    framework: Framework,
+    display_name: &'static str,
+    languages: &'static [Language],
+    dependencies: &'static [&'static str],
+    marker_files: &'static [&'static str],
+}
+
+const FRAMEWORKS: &[FrameworkSpec] = &[
+    FrameworkSpec {
+        framework: Framework::NextJs,
+        display_name: "Next.js",
+        languages: JS,
+        dependencies: &["next"],
+        marker_files: &["next.config.js", "next.config.mjs", "next.config.ts"],
+    },
+    FrameworkSpec {
+        framework: Framework::Nuxt,
+        display_name: "Nuxt",
+        languages: JS,
+        dependencies: &["nuxt"],
+        marker_files: &["nuxt.config.ts", "nuxt.config.js"],
+    },
+    FrameworkSpec {
+        framework: Framework::Remix,
+        display_name: "Remix",
+        languages: JS,
+        dependencies: &["@remix-run/*"],
+        marker_files: &["remix.config.js"],
+    },
+    FrameworkSpec {
+        framework: Framework::NestJs,
+        display_name: "NestJS",
+        languages: JS,
+        dependencies: &["@nestjs/core"],
+        marker_files: &["nest-cli.json"],
+    },
+    FrameworkSpec {
+        framework: Framework::Express,
+        display_name: "Express",
+        languages: JS,
+        dependencies: &["express"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Angular,
+        display_name: "Angular",
+        languages: JS,
+        dependencies: &["@angular/core"],
+        marker_files: &["angular.json"],
+    },
+    FrameworkSpec {
+        framework: Framework::React,
+        display_name: "React",
+        languages: JS,
+        dependencies: &["react"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::ReactNative,
+        display_name: "React Native",
+        languages: JS,
+        dependencies: &["react-native"],
+        marker_files: &["metro.config.js"],
+    },
+    FrameworkSpec {
+        framework: Framework::Vue,
+        display_name: "Vue",
+        languages: JS,
+        dependencies: &["vue"],
+        marker_files: &["vue.config.js"],
+    },
+    FrameworkSpec {
+        framework: Framework::Svelte,
+        display_name: "Svelte",
+        languages: JS,
+        dependencies: &["svelte"],
+        marker_files: &["svelte.config.js"],
+    },
+    FrameworkSpec {
+        framework: Framework::Electron,
+        display_name: "Electron",
+        languages: JS,
+        dependencies: &["electron"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Bun,
+        display_name: "Bun",
+        languages: JS,
+        dependencies: &["@types/bun", "bun-types"],
+        marker_files: &["bun.lockb", "bun.lock", "bunfig.toml"],
+    },
+    FrameworkSpec {
+        framework: Framework::Django,
+        display_name: "Django",
+        languages: &[Language::Python],
+        dependencies: &["django"],
+        marker_files: &["manage.py"],
+    },
+    FrameworkSpec {
+        framework: Framework::Flask,
+        display_name: "Flask",
+        languages: &[Language::Python],
+        dependencies: &["flask"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::FastApi,
+        display_name: "FastAPI",
+        languages: &[Language::Python],
+        dependencies: &["fastapi"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::SpringBoot,
+        display_name: "Spring Boot",
+        languages: JVM,
+        dependencies: &["org.springframework.boot:*"],
+        marker_files: &["src/main/resources/application.properties", "src/main/resources/application.yml"],
+    },
+    FrameworkSpec {
+        framework: Framework::Quarkus,
+        display_name: "Quarkus",
+        languages: JVM,
+        dependencies: &["io.quarkus:*"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Micronaut,
+        display_name: "Micronaut",
+        languages: JVM,
+        dependencies: &["io.micronaut:*"],
+        marker_files: &["micronaut-cli.yml"],
+    },
+    FrameworkSpec {
+        framework: Framework::Ktor,
+        display_name: "Ktor",
+        languages: JVM,
+        dependencies: &["io.ktor:*"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::AspNetCore,
+        display_name: "ASP.NET Core",
+        languages: &[Language::CSharp, Language::FSharp, Language::VisualBasic],
+        dependencies: &["microsoft.net.sdk.web", "microsoft.aspnetcore.*"],
+        marker_files: &["appsettings.json"],
+    },
+    FrameworkSpec {
+        framework: Framework::Gin,
+        display_name: "Gin",
+        languages: &[Language::Go],
+        dependencies: &["github.com/gin-gonic/gin"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Echo,
+        display_name: "Echo",
+        languages: &[Language::Go],
+        dependencies: &["github.com/labstack/echo*"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Fiber,
+        display_name: "Fiber",
+        languages: &[Language::Go],
+        dependencies: &["github.com/gofiber/fiber*"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Rails,
+        display_name: "Ruby on Rails",
+        languages: &[Language::Ruby],
+        dependencies: &["rails"],
+        marker_files: &["config/application.rb", "bin/rails"],
+    },
+    FrameworkSpec {
+        framework: Framework::Laravel,
+        display_name: "Laravel",
+        languages: &[Language::Php],
+        dependencies: &["laravel/framework"],
+        marker_files: &["artisan"],
+    },
+    FrameworkSpec {
+        framework: Framework::Symfony,
+        display_name: "Symfony",
+        languages: &[Language::Php],
+        dependencies: &["symfony/framework-bundle"],
+        marker_files: &["symfony.lock"],
+    },
+    FrameworkSpec {
+        framework: Framework::ActixWeb,
+        display_name: "Actix Web",
+        languages: &[Language::Rust],
+        dependencies: &["actix-web"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Axum,
+        display_name: "Axum",
+        languages: &[Language::Rust],
+        dependencies: &["axum"],
+        marker_files: &[],
+    },
+    FrameworkSpec {
+        framework: Framework::Rocket,
+        display_name: "Rocket",
+        languages: &[Language::Rust],
+        dependencies: &["rocket"],
+        marker_files: &["Rocket.toml"],
+    },
+    FrameworkSpec {
+        framework: Framework::Flutter,
+        display_name: "Flutter",
+        languages: &[Language::Dart],
+        dependencies: &["flutter"],
+        marker_files: &[],
+    },
+];
+
+impl Framework {
+    fn spec(&self) -> &'static FrameworkSpec {
+        FRAMEWORKS.iter().find(|s| s.framework == *self).unwrap()
+    }
+
+    pub fn display_name(&self) -> &'static str { self.spec().display_name }
+}
+
+fn dependency_matches(pattern: &str, name: &str) -> bool {
+    let name = name.to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Match one manifest against every framework of its language
+fn detect_in_manifest(language: Language, manifest: &ProjectManifest) -> Vec<DetectedFramework> {
+    let root = manifest.project_root();
+
+    FRAMEWORKS
+        .iter()
+        .filter(|spec| spec.languages.contains(&language))
+        .filter_map(|spec| {
+            let matches = |name: &str| spec.dependencies.iter().any(|p| dependency_matches(p, name));
+            let mut evidence: Vec<String> = manifest
+                .sdk
+                .iter()
+                .filter(|sdk| matches(sdk))
+                .map(|sdk| format!("sdk {}", sdk))
+                .collect();
+            evidence.extend(
+                manifest
+                    .dependencies
+                    .iter()
+                    .filter(|d| matches(&d.name))
+                    .map(|d| match &d.version {
+                        Some(v) => format!("dependency {}@{}", d.name, v),
+                        None => format!("dependency {}", d.name),
+                    }),
+            );
+            let dependency_hits = evidence.len();
+
+            evidence.extend(
+                spec.marker_files
+                    .iter()
+                    .filter(|marker| root.join(marker).exists())
+                    .map(|marker| format!("marker {}", marker)),
+            );
+            let has_dependency = dependency_hits > 0;
+            let has_marker = evidence.len() > dependency_hits;
+
+            let confidence = match (has_dependency, has_marker) {
+                (true, true) => Confidence::High,
+                (true, false) => Confidence::Medium,
+                (false, true) => Confidence::Low,
+                (false, false) => return None,
+            };
+
+            Some(DetectedFramework {
+                framework: spec.framework,
+                display_name: spec.display_name.to_string(),
+                language,
+                confidence,
+                location: root.to_path_buf(),
+                evidence,
+            })
+        })
+        .collect()
+}
+
+/// Detect frameworks across every manifest of the detected languages.
+///
+/// Results are deduplicated per (framework, location), keeping the highest
+/// confidence, and sorted by location then framework.
+pub fn detect_frameworks<'a>(languages: impl IntoIterator<Item = &'a LanguageInfo>) -> Vec<DetectedFramework> {
+    let mut found: Vec<DetectedFramework> = languages
+        .into_iter()
+        .flat_map(|info| {
+            info.manifests
+                .iter()
+                .flat_map(move |m| detect_in_manifest(info.language, m))
+        })
+        .collect();
+
+    found.sort_by(|a, b| {
+        a.location
+            .cmp(&b.location)
+            .then_with(|| a.framework.cmp(&b.framework))
+            .then_with(|| b.confidence.cmp(&a.confidence))
+    });
+    found.dedup_by(|later, first| later.location == first.location && later.framework == first.framework);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+    use tempfile::TempDir;
+
+    fn info_for(language: Language, manifest: &std::path::Path) -> LanguageInfo {
+        LanguageInfo {
+            language,
+            file_count: 1,
+            config_files: vec![manifest.to_path_buf()],
+            manifests: parse_manifest(manifest).unwrap().into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_dependency_and_marker_is_high_confidence() {
+        let temp = TempDir::new().unwrap();
+        let manifest = temp.path().join("package.json");
+        std::fs::write(&manifest, r#"{"dependencies":{"next":"14.1.0","react":"18.2.0"}}"#).unwrap();
+        std::fs::write(temp.path().join("next.config.mjs"), "export default {};").unwrap();
+
+        let found = detect_frameworks(&[info_for(Language::TypeScript, &manifest)]);
+        let names: Vec<Framework> = found.iter().map(|f| f.framework).collect();
+        assert_eq!(names, vec![Framework::NextJs, Framework::React]);
+        assert_eq!(found[0].confidence, Confidence::High);
+        assert_eq!(found[0].evidence, vec!["dependency next@14.1.0", "marker next.config.mjs"]);
+        assert_eq!(found[1].confidence, Confidence::Medium);
+        assert_eq!(found[0].location, temp.path());
+    }
+
+    #[test]
+    fn test_prefix_and_case_insensitive_matching() {
+        let temp = TempDir::new().unwrap();
+        let manifest = temp.path().join("requirements.txt");
+        std::fs::write(&manifest, "Django>=4.2\n").unwrap();
+        let pom = temp.path().join("pom.xml");
+        std::fs::write(
+            &pom,
+            "<project><dependencies><dependency><groupId>org.springframework.boot</groupId>\
+             <artifactId>spring-boot-starter-web</artifactId></dependency></dependencies></project>",
+        )
+        .unwrap();
+
+        let found = detect_frameworks(&[
+            info_for(Language::Python, &manifest),
+            info_for(Language::Java, &pom),
+        ]);
+        let names: Vec<Framework> = found.iter().map(|f| f.framework).collect();
+        assert_eq!(names, vec![Framework::Django, Framework::SpringBoot]);
+    }
+
+    #[test]
+    fn test_marker_only_is_low_confidence() {
+        let temp = TempDir::new().unwrap();
+        let manifest = temp.path().join("composer.json");
+        std::fs::write(&manifest, r#"{"name":"acme/app"}"#).unwrap();
+        std::fs::write(temp.path().join("artisan"), "#!/usr/bin/env php").unwrap();
+
+        let found = detect_frameworks(&[info_for(Language::Php, &manifest)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].framework, Framework::Laravel);
+        assert_eq!(found[0].confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn test_web_sdk_detects_aspnet_core() {
+        let temp = TempDir::new().unwrap();
+        let manifest = temp.path().join("Api.fsproj");
+        std::fs::write(
+            &manifest,
+            r#"<Project Sdk="Microsoft.NET.Sdk.Web"><PropertyGroup><TargetFramework>net8.0</TargetFramework></PropertyGroup></Project>"#,
+        )
+        .unwrap();
+
+        let found = detect_frameworks(&[info_for(Language::FSharp, &manifest)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].framework, Framework::AspNetCore);
+        assert_eq!(found[0].confidence, Confidence::Medium);
+        assert_eq!(found[0].evidence, vec!["sdk Microsoft.NET.Sdk.Web"]);
+        assert_eq!(serde_json::to_value(Framework::AspNetCore).unwrap(), "asp-net-core");
+        assert_eq!(serde_json::to_value(Framework::NextJs).unwrap(), "next-js");
+    }
+}
@@ -0,0 +1,343 @@
+//! SCIP package identity injection
+//!
+//! Every global SCIP symbol names the package it belongs to
+//! (`<scheme> <manager> <name> <version> <descriptors>`). Indexers leave that
+//! empty or as a placeholder when they cannot find a package name, which stops
+//! references in one repository's index from linking to definitions in
+//! another's. This pass rewrites the package of each symbol a document
+//! defines, using the nearest detected manifest (or a configured identity),
+//! and applies the same rewrite to every reference in the index.
+
+use crate::manifest::{ManifestKind, ProjectManifest};
+use crate::scip;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// When to rewrite a symbol's package
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdentityMode {
+    /// Leave indexer output untouched
+    Off,
+    /// Fill in only empty or placeholder (`.`) package fields
+    #[default]
+    Fill,
+    /// Replace the package of every symbol defined in the codebase
+    Override,
+}
+
+impl FromStr for IdentityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(Self::Off),
+            "fill" => Ok(Self::Fill),
+            "override" => Ok(Self::Override),
+            other => Err(format!(
+                "unknown package identity mode '{}' (expected off, fill or override)",
+                other
+            )),
+        }
+    }
+}
+
+/// A package identity: manager, name and optional version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageIdentity {
+    pub manager: String,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// Parses `manager:name@version` (version optional), e.g. `npm:@acme/web@1.2.0`
+impl FromStr for PackageIdentity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (manager, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid package '{}' (expected manager:name[@version])", s))?;
+        // A leading `@` belongs to a scoped npm name, not the version
+        let (name, version) = match rest.rfind('@').filter(|&i| i > 0) {
+            Some(i) => (&rest[..i], Some(rest[i + 1..].to_string())),
+            None => (rest, None),
+        };
+        if manager.is_empty() || name.is_empty() {
+            return Err(format!("invalid package '{}' (expected manager:name[@version])", s));
+        }
+        Ok(Self {
+            manager: manager.to_string(),
+            name: name.to_string(),
+            version: version.filter(|v| !v.is_empty()),
+        })
+    }
+}
+
+impl fmt::Display for PackageIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.manager, self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{}", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Package manager name SCIP indexers use for a manifest format
+fn manager_for(kind: ManifestKind) -> &'static str {
+    match kind {
+        ManifestKind::PackageJson => "npm",
+        ManifestKind::Cargo => "cargo",
+        ManifestKind::Pyproject
+        | ManifestKind::Requirements
+        | ManifestKind::Pipfile
+        | ManifestKind::CondaEnv => "python",
+        ManifestKind::GoMod | ManifestKind::GoWork => "gomod",
+        ManifestKind::Maven | ManifestKind::Gradle => "maven",
+        ManifestKind::Gemfile => "gem",
+        ManifestKind::Composer => "composer",
+        ManifestKind::Pubspec => "pub",
+        ManifestKind::MsBuild => "nuget",
+    }
+}
+
+impl PackageIdentity {
+    /// Identity declared by a manifest, if it names its package
+    pub fn from_manifest(manifest: &ProjectManifest) -> Option<Self> {
+        Some(Self {
+            manager: manager_for(manifest.kind).to_string(),
+            name: manifest.name.clone()?,
+            version: manifest.version.clone(),
+        })
+    }
+}
+
+/// A global symbol split into scheme, package fields and descriptors
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbol<'a> {
+    scheme: String,
+    manager: String,
+    name: String,
+    version: String,
+    descriptors: &'a str,
+}
+
+/// Read one space-terminated symbol field; a doubled space is a literal space
+fn take_field(s: &str) -> Option<(String, &str)> {
+    let mut field = String::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != ' ' {
+            field.push(c);
+        } else if chars.peek().is_some_and(|&(_, next)| next == ' ') {
+            chars.next();
+            field.push(' ');
+        } else {
+            return Some((field, &s[i + 1..]));
+        }
+    }
+    None
+}
+
+fn escape_field(field: &str) -> String {
+    if field.is_empty() {
+        ".".to_string()
+    } else {
+        field.replace(' ', "  ")
+    }
+}
+
+/// Read a package field, treating the `.` placeholder as empty
+fn take_package_field(s: &str) -> Option<(String, &str)> {
+    take_field(s).map(|(field, rest)| (if field == "." { String::new() } else { field }, rest))
+}
+
+impl<'a> Symbol<'a> {
+    fn parse(symbol: &'a str) -> Option<Self> {
+        if symbol.starts_with("local ") {
+            return None;
+        }
+        let (scheme, rest) = take_field(symbol)?;
+        let (manager, rest) = take_package_field(rest)?;
+        let (name, rest) = take_package_field(rest)?;
+        let (version, descriptors) = take_package_field(rest)?;
+        Some(Self { scheme, manager, name, version, descriptors })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            escape_field(&self.scheme),
+            escape_field(&self.manager),
+            escape_field(&self.name),
+            escape_field(&self.version),
+            self.descriptors
+        )
+    }
+
+    /// Apply `identity` under `mode`; `None` when nothing changes
+    fn with_identity(&self, identity: &PackageIdentity, mode: IdentityMode) -> Option<String> {
+        let version = identity.version.as_deref().unwrap_or("");
+        let mut updated = self.clone();
+        match mode {
+            IdentityMode::Off => return None,
+            IdentityMode::Fill => {
+                if self.manager.is_empty() {
+                    updated.manager = identity.manager.clone();
+                }
+                if self.name.is_empty() {
+                    updated.name = identity.name.clone();
+                }
+                if self.version.is_empty() {
+                    updated.version = version.to_string();
+                }
+            }
+            IdentityMode::Override => {
+                updated.manager = identity.manager.clone();
+                updated.name = identity.name.clone();
+                if !version.is_empty() {
+                    updated.version = version.to_string();
+                }
+            }
+        }
+        (updated != *self).then(|| updated.format())
+    }
+}
+
+/// Rewrite package identities in `index`. Each document takes the identity of
+/// `configured` if given, otherwise of the deepest manifest whose project
+/// contains it (`codebase` resolves document paths). Returns the number of
+/// symbol strings rewritten.
+pub fn apply(
+    index: &mut scip::Index,
+    codebase: &Path,
+    manifests: &[&ProjectManifest],
+    mode: IdentityMode,
+    configured: Option<&PackageIdentity>,
+) -> usize {
+    if mode == IdentityMode::Off {
+        return 0;
+    }
+
+    let mut owners: Vec<(&Path, PackageIdentity)> = manifests
+        .iter()
+        .filter_map(|m| Some((m.project_root(), PackageIdentity::from_manifest(m)?)))
+        .collect();
+    // Deepest project first, so nested packages win over their workspace
+    owners.sort_by_key(|(root, _)| std::cmp::Reverse(root.components().count()));
+
+    let mut renames: HashMap<String, String> = HashMap::new();
+    for doc in &index.documents {
+        let path = codebase.join(doc.relative_path());
+        let identity = configured.or_else(|| {
+            owners.iter().find(|(root, _)| path.starts_with(root)).map(|(_, id)| id)
+        });
+        let Some(identity) = identity else {
+            continue;
+        };
+        for symbol in doc.defined_symbols() {
+            if let Some(renamed) = Symbol::parse(&symbol).and_then(|s| s.with_identity(identity, mode)) {
+                renames.insert(symbol, renamed);
+            }
+        }
+    }
+
+    if renames.is_empty() {
+        return 0;
+    }
+    index.rewrite_symbols(&|symbol| renames.get(symbol).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> PackageIdentity {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_package_identity() {
+        let scoped = id("npm:@acme/web@1.2.0");
+        assert_eq!(scoped.name, "@acme/web");
+        assert_eq!(scoped.version.as_deref(), Some("1.2.0"));
+        assert_eq!(id("gomod:github.com/acme/api").version, None);
+        assert!("web@1.0".parse::<PackageIdentity>().is_err());
+    }
+
+    #[test]
+    fn test_symbol_round_trip_with_escaped_spaces() {
+        let raw = "scip-typescript npm my  pkg 1.0.0 src/`index.ts`/foo().";
+        let symbol = Symbol::parse(raw).unwrap();
+        assert_eq!(symbol.name, "my pkg");
+        assert_eq!(symbol.descriptors, "src/`index.ts`/foo().");
+        assert_eq!(symbol.format(), raw);
+        assert!(Symbol::parse("local 4").is_none());
+    }
+
+    #[test]
+    fn test_apply_rewrites_definitions_and_references() {
+        use crate::manifest::parse_manifest;
+        use crate::scip::{encode_fields, Document, Field, FieldValue, Index};
+
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(temp.path().join("web")).unwrap();
+        std::fs::write(temp.path().join("web/package.json"), r#"{"name":"@acme/web","version":"3.1.0"}"#).unwrap();
+        let manifest = parse_manifest(&temp.path().join("web/package.json")).unwrap().unwrap();
+
+        let local = "scip-typescript npm . . `index.ts`/App#";
+        let external = "scip-typescript npm react 18.2.0 `index.d.ts`/useState().";
+        let occurrence = |symbol: &str, roles: u64| {
+            Field::bytes(2, encode_fields(&[
+                Field::bytes(2, symbol.as_bytes().to_vec()),
+                Field { number: 3, value: FieldValue::Varint(roles) },
+            ]))
+        };
+        let doc = |path: &str, fields: Vec<Field>| {
+            let mut all = vec![Field::bytes(1, path.as_bytes().to_vec())];
+            all.extend(fields);
+            Document { fields: all }
+        };
+        let mut index = Index {
+            documents: vec![
+                doc("web/index.ts", vec![occurrence(local, 1), occurrence(external, 0)]),
+                doc("web/main.ts", vec![occurrence(local, 0)]),
+            ],
+            ..Default::default()
+        };
+
+        let count = apply(&mut index, temp.path(), &[&manifest], IdentityMode::Fill, None);
+        assert_eq!(count, 2);
+        let renamed = "scip-typescript npm @acme/web 3.1.0 `index.ts`/App#";
+        assert_eq!(index.documents[1].defined_symbols(), Vec::<String>::new());
+        assert_eq!(index.documents[0].defined_symbols(), vec![renamed]);
+        let encoded = index.encode();
+        let text = String::from_utf8_lossy(&encoded);
+        assert!(text.contains(external));
+        assert_eq!(text.matches(renamed).count(), 2);
+    }
+
+    #[test]
+    fn test_fill_only_replaces_placeholders() {
+        let web = id("npm:web@2.0.0");
+        let placeholder = Symbol::parse("scip-typescript npm . . `a.ts`/x.").unwrap();
+        assert_eq!(
+            placeholder.with_identity(&web, IdentityMode::Fill).as_deref(),
+            Some("scip-typescript npm web 2.0.0 `a.ts`/x.")
+        );
+
+        let unversioned = Symbol::parse("scip-go gomod acme/api . `acme/api`/Run().").unwrap();
+        assert_eq!(unversioned.with_identity(&id("gomod:acme/api"), IdentityMode::Fill), None);
+
+        let named = Symbol::parse("scip-typescript npm other 1.0.0 `a.ts`/x.").unwrap();
+        assert_eq!(named.with_identity(&web, IdentityMode::Fill), None);
+        assert_eq!(
+            named.with_identity(&web, IdentityMode::Override).as_deref(),
+            Some("scip-typescript npm web 2.0.0 `a.ts`/x.")
+        );
+    }
+}
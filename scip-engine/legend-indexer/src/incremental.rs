@@ -0,0 +1,575 @@
+//! Incremental re-indexing
+//!
+//! With `--incremental`, `analyze` keeps an `index-manifest.json` next to the
+//! final `.scip` files, recording for each indexed language a fingerprint of
+//! its inputs: the path and contents of every source file (by extension) and
+//! config file the include/exclude patterns keep. The next run re-uses a
+//! language's previous index when its fingerprint is unchanged.
+//!
+//! `--changed-since <ref>` is the variant for CI, where hashing the whole
+//! checkout is wasted work: a language is re-indexed when `git diff` against
+//! `<ref>` (plus untracked files) touches one of its inputs, and re-used
+//! otherwise. The manifest records the commit the previous indexes were
+//! built from; when that is not `<ref>`, fingerprints are compared instead.
+//!
+//! Where only source files changed, the previous index can be patched
+//! rather than rebuilt: the manifest keeps a digest per file, so the changed
+//! and deleted files are known, and the orchestrator re-indexes the changed
+//! ones alone (for indexers that can target files) and splices their
+//! documents into the previous index.
+//!
+//! Any change to the settings that shape an index (tool version, patterns,
+//! package identity) invalidates every entry.
+
+use crate::config::{Config, WORK_DIR_NAME};
+use crate::detect::{Language, LanguageInfo};
+use crate::orchestrate::IndexerResult;
+use crate::outdir;
+use crate::pathfilter::PathFilter;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+/// Manifest file name, in the directory holding the final `.scip` files
+pub const MANIFEST_FILE: &str = "index-manifest.json";
+
+/// Version of the manifest layout; older or newer manifests are ignored
+const MANIFEST_VERSION: u32 = 2;
+
+/// What the previous incremental run produced
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexManifest {
+    pub version: u32,
+    /// Fingerprint of the settings every index depends on
+    pub settings: String,
+    /// Commit checked out when the indexes were built (git checkouts only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Files that differed from `commit` at the time (modified, deleted or
+    /// untracked)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uncommitted: Vec<String>,
+    pub languages: BTreeMap<Language, ManifestEntry>,
+}
+
+/// One language's index and the inputs it was built from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub fingerprint: String,
+    /// Digest of each input file, by path relative to the codebase root
+    pub files: BTreeMap<String, String>,
+    /// The `.scip` file, relative to the manifest's directory
+    pub scip: String,
+}
+
+impl IndexManifest {
+    /// The manifest in `dir`, if there is a readable one
+    pub fn load(dir: &Path) -> Option<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let text = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<Self>(&text) {
+            Ok(manifest) if manifest.version == MANIFEST_VERSION => Some(manifest),
+            Ok(manifest) => {
+                debug!("Ignoring manifest version {} in {:?}", manifest.version, path);
+                None
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable index manifest {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(MANIFEST_FILE);
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize index manifest")?;
+        outdir::write_atomic(&path, json)
+    }
+}
+
+/// The fingerprint of one language's inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub hash: String,
+    /// Digest of each input file
+    pub files: BTreeMap<PathBuf, u64>,
+}
+
+impl Fingerprint {
+    fn manifest_files(&self) -> BTreeMap<String, String> {
+        self.files
+            .iter()
+            .map(|(path, digest)| (path.to_string_lossy().into_owned(), format!("{:016x}", digest)))
+            .collect()
+    }
+}
+
+/// Source files changed since a previous index, for re-indexing just those
+/// files and splicing their documents into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexPatch {
+    pub previous: PathBuf,
+    /// Added or modified files, relative to the codebase root
+    pub changed: Vec<PathBuf>,
+    /// Deleted files, whose documents are dropped
+    pub removed: Vec<PathBuf>,
+}
+
+impl IndexPatch {
+    /// A patch for `touched` files of `language`; None when one of them is
+    /// not a source file (a changed config file can affect every document)
+    fn new(root: &Path, language: Language, previous: PathBuf, touched: Vec<PathBuf>) -> Option<Self> {
+        if touched.is_empty() || !touched.iter().all(|p| language.is_source(p)) || !previous.is_file() {
+            return None;
+        }
+        let (changed, removed) = touched
+            .into_iter()
+            .partition(|p| root.join(p).symlink_metadata().is_ok());
+        Some(Self { previous, changed, removed })
+    }
+}
+
+/// An incremental `analyze` run: which previous indexes are re-used or
+/// patched, and what to record once indexing is done
+#[derive(Debug)]
+pub struct IncrementalRun {
+    state_dir: PathBuf,
+    settings: String,
+    previous: IndexManifest,
+    /// HEAD and the files differing from it, as indexed by this run
+    checkout: Option<(String, Vec<String>)>,
+    /// Fingerprints of the languages being re-indexed
+    fingerprints: BTreeMap<Language, Fingerprint>,
+    /// Re-used languages and where their previous index is now
+    reuse: BTreeMap<Language, PathBuf>,
+    /// Languages where only some source files changed
+    patches: BTreeMap<Language, IndexPatch>,
+}
+
+impl IncrementalRun {
+    /// Compare the languages about to be indexed with the manifest in
+    /// `state_dir` (where the final `.scip` files go)
+    pub fn prepare(config: &Config, state_dir: &Path, languages: &[LanguageInfo]) -> Result<Self> {
+        let root = &config.input_path;
+        let filter = config.path_filter();
+        let settings = settings_fingerprint(config);
+        let previous = IndexManifest::load(state_dir)
+            .filter(|m| {
+                let same = m.settings == settings;
+                if !same {
+                    info!("Indexing settings changed since the last run; re-indexing everything");
+                }
+                same
+            })
+            .unwrap_or_default();
+        let requested: Vec<Language> = languages.iter().map(|l| l.language).collect();
+        // What this tool writes into the codebase is never an input
+        let written = written_dirs(config);
+        let ours = |p: &Path| written.iter().any(|dir| p.starts_with(dir));
+        let checkout = git_commit(root, "HEAD").ok().and_then(|head| {
+            let uncommitted = uncommitted_files(root).ok()?;
+            Some((head, uncommitted.into_iter().filter(|p| !ours(Path::new(p))).collect()))
+        });
+        let diff_base = match &config.changed_since {
+            Some(git_ref) => diff_base(root, git_ref, &previous)?,
+            None => None,
+        };
+
+        let mut unchanged = Vec::new();
+        let mut touched: BTreeMap<Language, Vec<PathBuf>> = BTreeMap::new();
+        let fingerprints = match diff_base {
+            Some(base) => {
+                // Files uncommitted at the last run may have been reverted since
+                let mut changed = changed_files(root, &base)?;
+                changed.extend(previous.uncommitted.iter().map(PathBuf::from));
+                changed.sort();
+                changed.dedup();
+                changed.retain(|p| !filter.is_excluded(p) && !ours(p));
+                debug!("{} files changed since {}", changed.len(), base);
+                let mut changed_languages = Vec::new();
+                for &language in &requested {
+                    let inputs: Vec<PathBuf> = changed.iter().filter(|p| language.is_input(p)).cloned().collect();
+                    if !previous.languages.contains_key(&language) {
+                        changed_languages.push(language);
+                    } else if inputs.is_empty() {
+                        unchanged.push(language);
+                    } else {
+                        changed_languages.push(language);
+                        touched.insert(language, inputs);
+                    }
+                }
+                fingerprint(root, &changed_languages, &filter)
+            }
+            None => {
+                let current = fingerprint(root, &requested, &filter);
+                for (&language, fingerprint) in &current {
+                    let Some(entry) = previous.languages.get(&language) else {
+                        continue;
+                    };
+                    if entry.fingerprint == fingerprint.hash {
+                        unchanged.push(language);
+                        continue;
+                    }
+                    let files = fingerprint.manifest_files();
+                    // Modified or deleted, then added
+                    let inputs = entry
+                        .files
+                        .iter()
+                        .filter(|&(path, digest)| files.get(path) != Some(digest))
+                        .map(|(path, _)| path)
+                        .chain(files.keys().filter(|p| !entry.files.contains_key(*p)))
+                        .map(PathBuf::from)
+                        .collect();
+                    touched.insert(language, inputs);
+                }
+                current
+            }
+        };
+
+        let scip_path = |language: &Language| state_dir.join(&previous.languages[language].scip);
+        let reuse: BTreeMap<Language, PathBuf> = unchanged
+            .into_iter()
+            .filter_map(|language| {
+                let path = scip_path(&language);
+                path.is_file().then_some((language, path))
+            })
+            .collect();
+        let patches: BTreeMap<Language, IndexPatch> = touched
+            .into_iter()
+            .filter_map(|(language, files)| {
+                IndexPatch::new(root, language, scip_path(&language), files).map(|patch| (language, patch))
+            })
+            .collect();
+        info!(
+            "Incremental: re-using {} of {} language indexes, {} can be patched",
+            reuse.len(),
+            requested.len(),
+            patches.len()
+        );
+
+        Ok(Self {
+            state_dir: state_dir.to_path_buf(),
+            settings,
+            previous,
+            checkout,
+            fingerprints,
+            reuse,
+            patches,
+        })
+    }
+
+    /// Previous indexes to hand to the orchestrator
+    pub fn reuse(&self) -> HashMap<Language, PathBuf> {
+        self.reuse.iter().map(|(l, p)| (*l, p.clone())).collect()
+    }
+
+    /// Previous indexes to patch with the changed files' documents
+    pub fn patches(&self) -> HashMap<Language, IndexPatch> {
+        self.patches.iter().map(|(l, p)| (*l, p.clone())).collect()
+    }
+
+    /// Record what this run produced in the manifest. Languages that failed
+    /// or are missing units are left out, so the next run indexes them again.
+    pub fn finish(self, results: &[IndexerResult]) -> Result<()> {
+        let (commit, uncommitted) = self.checkout.unzip();
+        let mut manifest = IndexManifest {
+            version: MANIFEST_VERSION,
+            settings: self.settings,
+            commit,
+            uncommitted: uncommitted.unwrap_or_default(),
+            languages: BTreeMap::new(),
+        };
+        for result in results.iter().filter(|r| r.success && !r.partial) {
+            let Some(scip) = result.scip_path.file_name() else {
+                continue;
+            };
+            let entry = if result.reused {
+                self.previous.languages.get(&result.language).cloned()
+            } else {
+                self.fingerprints.get(&result.language).map(|f| ManifestEntry {
+                    fingerprint: f.hash.clone(),
+                    files: f.manifest_files(),
+                    scip: scip.to_string_lossy().into_owned(),
+                })
+            };
+            if let Some(entry) = entry {
+                manifest.languages.insert(result.language, entry);
+            }
+        }
+        manifest.save(&self.state_dir)
+    }
+}
+
+/// Fingerprint of the settings every index depends on
+fn settings_fingerprint(config: &Config) -> String {
+    let mut hash = Fnv64::new();
+    hash.write_str(env!("CARGO_PKG_VERSION"));
+    for pattern in &config.include_patterns {
+        hash.write_str(pattern);
+    }
+    hash.write_str("--");
+    for pattern in &config.exclude_patterns {
+        hash.write_str(pattern);
+    }
+    hash.write_str(&format!("{:?}", config.symlink_policy));
+    hash.write_str(&format!("{:?}", config.package_identity));
+    hash.write_str(&config.package.as_ref().map(|p| p.to_string()).unwrap_or_default());
+    hash.hex()
+}
+
+/// Fingerprint the inputs of each language: every file it counts as input
+/// (see [`Language::is_input`]) that `filter` keeps, by path and contents,
+/// in path order. Symlinks are not followed; their target is hashed.
+pub fn fingerprint(root: &Path, languages: &[Language], filter: &PathFilter) -> BTreeMap<Language, Fingerprint> {
+    let mut hashes: BTreeMap<Language, (Fnv64, BTreeMap<PathBuf, u64>)> =
+        languages.iter().map(|l| (*l, (Fnv64::new(), BTreeMap::new()))).collect();
+    if languages.is_empty() {
+        return BTreeMap::new();
+    }
+
+    let mut files = Vec::new();
+    collect_files(root, root, filter, &mut files);
+    files.sort();
+    for rel in files {
+        if filter.is_excluded(&rel) {
+            continue;
+        }
+        let owners: Vec<Language> = languages.iter().copied().filter(|l| l.is_input(&rel)).collect();
+        if owners.is_empty() {
+            continue;
+        }
+        let content = file_digest(&root.join(&rel));
+        for language in owners {
+            let (hash, digests) = hashes.get_mut(&language).expect("every language has a hash");
+            hash.write_str(&rel.to_string_lossy());
+            hash.write(&content.to_le_bytes());
+            digests.insert(rel.clone(), content);
+        }
+    }
+
+    hashes
+        .into_iter()
+        .map(|(language, (hash, files))| (language, Fingerprint { hash: hash.hex(), files }))
+        .collect()
+}
+
+/// Every file under `dir` as a path relative to `root`, skipping `.git`, our
+/// work directory and directories `filter` excludes whole. Files are not
+/// filtered.
+pub(crate) fn collect_files(root: &Path, dir: &Path, filter: &PathFilter, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if name == ".git" || (dir == root && name == WORK_DIR_NAME) {
+                continue;
+            }
+            if path.strip_prefix(root).is_ok_and(|rel| filter.excludes_dir(rel)) {
+                continue;
+            }
+            collect_files(root, &path, filter, files);
+        } else if let Ok(rel) = path.strip_prefix(root) {
+            files.push(rel.to_path_buf());
+        }
+    }
+}
+
+/// Hash of a file's contents (a symlink's target); unreadable files hash as
+/// empty, which still changes the fingerprint when they become readable
+fn file_digest(path: &Path) -> u64 {
+    if let Ok(target) = std::fs::read_link(path) {
+        let mut hash = Fnv64::new();
+        hash.write_str(&target.to_string_lossy());
+        return hash.0;
+    }
+    content_digest(path)
+}
+
+/// Hash of a file's contents, following symlinks; unreadable files hash as empty
+pub(crate) fn content_digest(path: &Path) -> u64 {
+    let mut hash = Fnv64::new();
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return hash.0;
+    };
+    let mut buf = [0u8; 64 * 1024];
+    while let Ok(n) = file.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        hash.write(&buf[..n]);
+    }
+    hash.0
+}
+
+/// The commit to diff against for `--changed-since <git_ref>`: the one
+/// `git_ref` names, provided the previous indexes were built from it. None
+/// (compare fingerprints) otherwise, since a diff against any other commit
+/// would miss changes the previous indexes do not reflect.
+fn diff_base(root: &Path, git_ref: &str, previous: &IndexManifest) -> Result<Option<String>> {
+    let target = git_commit(root, git_ref).with_context(|| format!("Cannot resolve --changed-since {}", git_ref))?;
+    if previous.languages.is_empty() {
+        return Ok(None);
+    }
+    match previous.commit.as_deref() {
+        Some(built) if built == target => Ok(Some(target)),
+        Some(built) => {
+            warn!(
+                "The previous indexes were built from commit {}, not {} ({}); comparing fingerprints instead",
+                built, git_ref, target
+            );
+            Ok(None)
+        }
+        None => {
+            warn!(
+                "The index manifest does not record the commit its indexes were built from; \
+                 comparing fingerprints instead of diffing against {}",
+                git_ref
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Run git in `root`, returning its output lines
+fn git(root: &Path, args: &[&str]) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .current_dir(root)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().filter(|l| !l.is_empty()).map(String::from).collect())
+}
+
+/// The commit `rev` names
+fn git_commit(root: &Path, rev: &str) -> Result<String> {
+    let commit = format!("{}^{{commit}}", rev);
+    git(root, &["rev-parse", "--verify", &commit])?
+        .pop()
+        .ok_or_else(|| anyhow!("git rev-parse printed nothing for {}", rev))
+}
+
+/// Directories inside the codebase this tool writes to, relative to it: the
+/// work directory and `--output` when it is inside the codebase
+fn written_dirs(config: &Config) -> Vec<PathBuf> {
+    let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let root = absolute(&config.input_path);
+    std::iter::once(PathBuf::from(WORK_DIR_NAME))
+        .chain(
+            config
+                .output_path
+                .iter()
+                .filter_map(|out| absolute(out).strip_prefix(&root).ok().map(Path::to_path_buf)),
+        )
+        .collect()
+}
+
+/// Files differing from HEAD: modified, deleted or untracked
+fn uncommitted_files(root: &Path) -> Result<Vec<String>> {
+    Ok(changed_files(root, "HEAD")?
+        .into_iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Files changed relative to `git_ref` (committed or not, including
+/// deletions and untracked files), relative to `root`
+pub fn changed_files(root: &Path, git_ref: &str) -> Result<Vec<PathBuf>> {
+    let mut files = git(root, &["diff", "--name-only", "--no-renames", "--relative", git_ref, "--"])?;
+    files.extend(git(root, &["ls-files", "--others", "--exclude-standard"])?);
+    files.sort();
+    files.dedup();
+    Ok(files.into_iter().map(PathBuf::from).collect())
+}
+
+/// 64-bit FNV-1a: small, and stable across Rust releases (unlike `DefaultHasher`)
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Write a string with a terminator, so adjacent strings cannot run together
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    pub(crate) fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        let path = root.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_each_language_separately() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        write(root, "go.mod", "module acme\n");
+        write(root, "main.go", "package main");
+        write(root, "app.py", "x = 1");
+        write(root, "vendor/lib.go", "package lib");
+        let filter = PathFilter::new(&[], &["vendor/**".to_string()]);
+        let languages = [Language::Go, Language::Python];
+
+        let before = fingerprint(root, &languages, &filter);
+        assert_eq!(before[&Language::Go].files.len(), 2);
+        assert_eq!(before, fingerprint(root, &languages, &filter));
+
+        write(root, "app.py", "x = 2");
+        write(root, "vendor/lib.go", "package changed");
+        let after = fingerprint(root, &languages, &filter);
+        assert_eq!(before[&Language::Go], after[&Language::Go], "excluded files do not count");
+        assert_ne!(before[&Language::Python], after[&Language::Python]);
+
+        std::fs::rename(root.join("main.go"), root.join("cmd.go")).unwrap();
+        assert_ne!(after[&Language::Go], fingerprint(root, &languages, &filter)[&Language::Go]);
+
+        // scip-typescript reads JavaScript files as well
+        write(root, "web/app.ts", "export {}");
+        let before = fingerprint(root, &[Language::TypeScript], &filter);
+        write(root, "web/legacy.js", "module.exports = {}");
+        assert_ne!(before, fingerprint(root, &[Language::TypeScript], &filter));
+    }
+
+    #[test]
+    fn test_fnv_known_value() {
+        let mut hash = Fnv64::new();
+        hash.write(b"a");
+        assert_eq!(hash.hex(), "af63dc4c8601ec8c");
+    }
+}
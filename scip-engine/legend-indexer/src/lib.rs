@@ -4,9 +4,24 @@
 //! using Sourcegraph SCIP indexers. It detects languages, orchestrates
 //! indexer execution, and produces raw .scip protobuf files.
 
+pub mod cache;
+pub mod compdb;
 pub mod config;
 pub mod detect;
+pub mod doctor;
+pub mod framework;
+pub mod identity;
+pub mod incremental;
+pub mod manifest;
 pub mod orchestrate;
+pub mod outdir;
+pub mod output;
+pub mod pathfilter;
+pub mod pyenv;
+pub mod scip;
+pub mod scratch;
+pub mod tsconfig;
+pub mod watch;
 
 pub use config::Config;
 pub use detect::{DetectionReport, LanguageDetector};
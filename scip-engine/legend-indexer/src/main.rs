@@ -6,15 +6,28 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use legend_indexer::{
-    config::Config,
-    detect::{DetectionReport, Language, LanguageDetector},
-    orchestrate::IndexerOrchestrator,
+    cache::{self, ScipCache},
+    config::{Config, FailOn},
+    detect::{DetectionReport, Language, LanguageDetector, LanguageFilter, LanguageInfo, SymlinkPolicy},
+    doctor,
+    identity::{IdentityMode, PackageIdentity},
+    incremental::IncrementalRun,
+    orchestrate::{BinarySource, IndexerOrchestrator, IndexerPlan, IndexerResult},
+    outdir::{self, LockBusy, RunLock},
+    output::{self, Envelope, ErrorReport, OutputFormat, WarningCollector},
+    watch::{self, Outcome, Watcher},
 };
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
 
 #[derive(Parser)]
 #[command(name = "scip-engine")]
@@ -37,14 +50,64 @@ struct Cli {
     #[arg(short, long, value_delimiter = ',')]
     languages: Vec<String>,
 
-    /// Glob patterns to exclude (comma-separated)
+    /// Only analyze paths matching these glob patterns (comma-separated, e.g. "services/billing/**")
+    #[arg(short, long, value_delimiter = ',')]
+    include: Vec<String>,
+
+    /// Glob patterns to exclude (comma-separated); "!pattern" re-includes a path an earlier pattern excluded
     #[arg(short, long, value_delimiter = ',')]
     exclude: Vec<String>,
 
+    /// Symlink handling: within-root (follow links inside the codebase), all, or never
+    #[arg(long, global = true, default_value = "within-root")]
+    symlinks: SymlinkPolicy,
+
     /// Path to bundled indexers directory
     #[arg(long)]
     indexers_path: Option<PathBuf>,
 
+    /// SCIP package identity injection: fill (placeholders only), override, or off
+    #[arg(long, default_value = "fill")]
+    package_identity: IdentityMode,
+
+    /// Package identity for all symbols instead of detected manifests (manager:name[@version])
+    #[arg(long)]
+    package: Option<PackageIdentity>,
+
+    /// Which indexing outcomes make analyze exit non-zero: partial (default), any, or none
+    #[arg(long, default_value = "partial")]
+    fail_on: FailOn,
+
+    /// Re-use the previous index of each language whose inputs are unchanged; Python and TypeScript
+    /// indexes are patched when the changed files still define the same symbols, else rebuilt
+    #[arg(long)]
+    incremental: bool,
+
+    /// Re-index only languages with files changed since this git ref, the commit of the
+    /// previous indexes (implies --incremental)
+    #[arg(long, value_name = "REF")]
+    changed_since: Option<String>,
+
+    /// Directory caching indexer outputs by the hash of their inputs, indexer and arguments
+    #[arg(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Size limit of the cache; least recently used entries are evicted beyond it (e.g. 500M, 5G)
+    #[arg(long, global = true, value_name = "SIZE", default_value = "5G", value_parser = cache::parse_size)]
+    cache_max_size: u64,
+
+    /// Seconds to wait for another run using the same work or output directory (0 fails at once)
+    #[arg(long, global = true, value_name = "SECONDS", default_value = "0")]
+    lock_timeout: u64,
+
+    /// Keep work files outside the codebase, which is then never written to (indexers that build run on a copy)
+    #[arg(long, global = true, value_name = "DIR")]
+    work_dir: Option<PathBuf>,
+
+    /// Output format: text, or a single JSON document on stdout
+    #[arg(long, global = true, default_value = "text")]
+    format: OutputFormat,
+
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -64,164 +127,745 @@ enum Commands {
         path: PathBuf,
     },
 
+    /// Show what analyze would run, without running anything
+    Plan {
+        /// Path to the codebase
+        path: PathBuf,
+    },
+
     /// Check which SCIP indexers are available
     CheckIndexers,
+
+    /// Diagnose the environment: indexer and toolchain versions, output directory, disk space
+    Doctor {
+        /// Codebase to check for (only the tools its languages need)
+        path: Option<PathBuf>,
+    },
+
+    /// Keep indexing a codebase: re-index the affected languages whenever files change
+    Watch {
+        /// Path to the codebase
+        path: PathBuf,
+
+        /// How often to look for changes, in milliseconds
+        #[arg(long, default_value = "500")]
+        poll_ms: u64,
+
+        /// How long the files must stay unchanged before re-indexing, in milliseconds
+        #[arg(long, default_value = "300")]
+        debounce_ms: u64,
+    },
+
+    /// Inspect or shrink the --cache-dir cache of indexer outputs
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Show the number and size of cached indexes, per language
+    Stats,
 
-    // Set up logging
+    /// Evict least recently used entries down to --cache-max-size
+    Prune {
+        /// Remove every entry
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+/// Exit codes of `analyze`; 1 is any other error and clap reports usage
+/// errors with 2, like `Validation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    Success = 0,
+    /// Unexpected error (I/O, invalid output directory, ...)
+    Error = 1,
+    /// The arguments or codebase path are unusable
+    Validation = 2,
+    /// No supported language was detected, or none has a SCIP indexer
+    NoLanguages = 3,
+    /// Languages were detected, but none matched `--languages`
+    FilterMatchedNothing = 4,
+    /// Some languages were indexed, others were not
+    PartialFailure = 5,
+    /// Nothing was indexed
+    TotalFailure = 6,
+    /// Another run is using the work or output directory
+    Locked = 7,
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+fn main() -> Result<ExitCode> {
+    let mut cli = Cli::parse();
+
+    // Set up logging (warnings are also collected for JSON output, which
+    // drains them into each document)
     let log_level = if cli.verbose { Level::DEBUG } else { Level::INFO };
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(log_level)
-        .with_target(false)
-        .with_writer(io::stderr)
-        .finish();
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::from_level(log_level))
+        .with(fmt::layer().with_target(false).with_writer(io::stderr))
+        .with((cli.format == OutputFormat::Json).then_some(WarningCollector));
     tracing::subscriber::set_global_default(subscriber)
         .context("Failed to set up logging")?;
 
-    match cli.command {
-        Some(Commands::Detect { path }) => detect_languages(&path),
-        Some(Commands::CheckIndexers) => check_indexers(),
+    let format = cli.format;
+    match cli.command.take() {
+        Some(Commands::Detect { path }) => detect_languages(&cli.into_config(path), format)?,
+        Some(Commands::CheckIndexers) => check_indexers(cli.indexers_path, format)?,
+        Some(Commands::Cache { action }) => manage_cache(cli.cache_dir, cli.cache_max_size, action, format)?,
+        Some(Commands::Doctor { path }) => {
+            let config = cli.into_config(path.clone().unwrap_or_else(|| PathBuf::from(".")));
+            return json_error(run_doctor(config, path.is_some(), format), "doctor", format);
+        }
+        Some(Commands::Plan { path }) => return json_error(plan_indexing(cli.into_config(path), format), "plan", format),
+        Some(Commands::Watch { path, poll_ms, debounce_ms }) => {
+            let timing = (Duration::from_millis(poll_ms), Duration::from_millis(debounce_ms));
+            return json_error(watch_codebase(cli.into_config(path), timing, format), "watch", format);
+        }
         analyze_or_default => {
             let path = match analyze_or_default {
                 Some(Commands::Analyze { path }) => path,
-                _ => cli.path,
-            };
-            let mut exclude_patterns = Config::default().exclude_patterns;
-            exclude_patterns.extend(cli.exclude);
-            let config = Config {
-                input_path: path,
-                languages: cli.languages,
-                exclude_patterns,
-                indexers_path: cli.indexers_path,
-                verbose: cli.verbose,
-                ..Default::default()
+                _ => cli.path.clone(),
             };
-            analyze_codebase(config, cli.output)
+            let config = cli.into_config(path);
+            let output = config.output_path.clone();
+            return json_error(analyze_codebase(config, output, format), "analyze", format);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+impl Cli {
+    /// Indexing configuration for the codebase at `path`
+    fn into_config(self, path: PathBuf) -> Config {
+        let mut exclude_patterns = Config::default().exclude_patterns;
+        exclude_patterns.extend(self.exclude);
+        Config {
+            input_path: path,
+            output_path: self.output,
+            languages: self.languages,
+            include_patterns: self.include,
+            exclude_patterns,
+            symlink_policy: self.symlinks,
+            indexers_path: self.indexers_path,
+            package_identity: self.package_identity,
+            package: self.package,
+            fail_on: self.fail_on,
+            incremental: self.incremental || self.changed_since.is_some(),
+            changed_since: self.changed_since,
+            cache_dir: self.cache_dir,
+            cache_max_size: self.cache_max_size,
+            lock_timeout: Duration::from_secs(self.lock_timeout),
+            work_dir: self.work_dir,
+            verbose: self.verbose,
+        }
+    }
+}
+
+/// JSON form of `analyze`
+#[derive(Serialize)]
+struct AnalyzeReport<'a> {
+    codebase: &'a Path,
+    detection: &'a DetectionReport,
+    results: &'a [IndexerResult],
+    produced: &'a [ProducedIndex],
+    exit_code: u8,
+}
+
+/// A `.scip` file left by `analyze`, at its final location
+#[derive(Serialize)]
+struct ProducedIndex {
+    language: Language,
+    path: PathBuf,
+}
+
+/// Report a condition that ends or degrades a run: on stderr as text, or
+/// among the JSON document's warnings
+fn notice(format: OutputFormat, message: String) {
+    match format {
+        OutputFormat::Text => eprintln!("{}", message),
+        OutputFormat::Json => output::push_warning(message),
+    }
+}
+
+/// Report an error that ends `command`: on stderr as text, or as the JSON
+/// error document
+fn fail(format: OutputFormat, command: &'static str, exit: Exit, message: String) -> Result<Exit> {
+    match format {
+        OutputFormat::Text => eprintln!("Error: {}", message),
+        OutputFormat::Json => Envelope::new(command, ErrorReport::new(exit as u8, message)).print()?,
+    }
+    Ok(exit)
+}
+
+/// With `--format json`, report an unexpected error as the JSON error
+/// document rather than leaving stdout empty
+fn json_error(result: Result<Exit>, command: &'static str, format: OutputFormat) -> Result<ExitCode> {
+    match result {
+        Err(e) if format == OutputFormat::Json => {
+            fail(format, command, Exit::Error, format!("{:#}", e)).map(ExitCode::from)
         }
+        other => other.map(ExitCode::from),
+    }
+}
+
+/// Detected languages selected by `filter`, noting requested languages that
+/// were not detected
+fn select_languages(
+    filter: &LanguageFilter,
+    detected: &[LanguageInfo],
+    config: &Config,
+    format: OutputFormat,
+) -> Vec<LanguageInfo> {
+    for language in filter.undetected(detected) {
+        notice(
+            format,
+            format!("{} was requested but not detected in {:?}", language.display_name(), config.input_path),
+        );
     }
+    let orchestrator = IndexerOrchestrator::for_plan(config.input_path.clone(), config.indexers_path.clone());
+    filter.apply(detected.to_vec(), |language| {
+        matches!(orchestrator.resolve_indexer(language), Some((_, source)) if source != BinarySource::Missing)
+    })
 }
 
 /// Analyze a codebase and produce .scip files
-fn analyze_codebase(config: Config, output_dir: Option<PathBuf>) -> Result<()> {
+fn analyze_codebase(config: Config, output_dir: Option<PathBuf>, format: OutputFormat) -> Result<Exit> {
     info!("Analyzing codebase: {:?}", config.input_path);
 
+    if !config.input_path.is_dir() {
+        return fail(format, "analyze", Exit::Validation, format!("{:?} is not a directory", config.input_path));
+    }
+    let filter = match LanguageFilter::parse(&config.languages) {
+        Ok(filter) => filter,
+        Err(e) => return fail(format, "analyze", Exit::Validation, format!("invalid --languages: {}", e)),
+    };
+    if let Some(e) = work_dir_error(&config) {
+        return fail(format, "analyze", Exit::Validation, e);
+    }
+    let _locks = match lock_outputs(&config, output_dir.as_deref()) {
+        Ok(locks) => locks,
+        Err(e) if e.is::<LockBusy>() => return fail(format, "analyze", Exit::Locked, e.to_string()),
+        Err(e) => return Err(e),
+    };
+
     // Step 1: Detect languages
-    let detector = LanguageDetector::new(&config.exclude_patterns);
+    let detector = LanguageDetector::from_filter(config.path_filter())
+        .with_symlink_policy(config.symlink_policy);
     let report = detector.detect(&config.input_path)?;
 
     // Print coverage summary before running indexers
-    print_coverage_summary(&report);
-
-    if report.languages.is_empty() {
-        eprintln!("No supported programming languages detected in {:?}", config.input_path);
-        return Ok(());
+    if format == OutputFormat::Text {
+        print_coverage_summary(&report);
     }
 
-    info!(
-        "Detected languages: {:?}",
-        report.languages.iter().map(|d| d.language.display_name()).collect::<Vec<_>>()
-    );
-
-    // Serialize report JSON before consuming report.languages
-    let report_json = serde_json::to_string_pretty(&report)
-        .context("Failed to serialize detection report")?;
-
     // Filter to specified languages if provided
-    let languages = if config.languages.is_empty() {
-        report.languages
+    let languages = select_languages(&filter, &report.languages, &config, format);
+
+    let mut results = Vec::new();
+    let mut produced = Vec::new();
+    let exit = if report.languages.is_empty() {
+        notice(format, format!("No supported programming languages detected in {:?}", config.input_path));
+        Exit::NoLanguages
+    } else if languages.is_empty() {
+        let message = match filter {
+            LanguageFilter::AllAvailable => "No detected language has an installed SCIP indexer".to_string(),
+            _ => format!("None of the requested languages were detected: {}", config.languages.join(", ")),
+        };
+        notice(format, message);
+        Exit::FilterMatchedNothing
     } else {
-        LanguageDetector::filter_languages(report.languages, &config.languages)
-    };
+        info!(
+            "Detected languages: {:?}",
+            report.languages.iter().map(|d| d.language.display_name()).collect::<Vec<_>>()
+        );
 
-    if languages.is_empty() {
-        eprintln!("No matching languages found for filter: {:?}", config.languages);
-        return Ok(());
-    }
+        // Compare with the previous run
+        let incremental = if config.incremental {
+            let state_dir = output_dir.clone().unwrap_or_else(|| config.effective_work_dir());
+            match IncrementalRun::prepare(&config, &state_dir, &languages) {
+                Ok(run) => Some(run),
+                Err(e) => return fail(format, "analyze", Exit::Validation, format!("{:#}", e)),
+            }
+        } else {
+            None
+        };
 
-    // Step 2: Run indexers (this cleans stale files in .legend-indexer/)
-    let orchestrator = IndexerOrchestrator::new(
-        config.input_path.clone(),
-        config.indexers_path.clone(),
-    )?;
+        // Step 2: Run indexers (previous indexes are replaced as each one completes)
+        let orchestrator = IndexerOrchestrator::new_in(
+            config.input_path.clone(),
+            config.indexers_path.clone(),
+            config.effective_work_dir(),
+        )?
+        .with_isolated_work_dir(config.work_dir.clone())
+        .with_package_identity(config.package_identity, config.package.clone())
+        .with_path_filter(config.path_filter())
+        .with_reuse(incremental.as_ref().map(IncrementalRun::reuse).unwrap_or_default())
+        .with_patches(incremental.as_ref().map(IncrementalRun::patches).unwrap_or_default())
+        .with_cache(config.scip_cache());
 
-    // Write detection report JSON after orchestrator cleans stale files
-    let report_path = orchestrator.output_dir().join("detection-report.json");
-    fs::write(&report_path, &report_json)
-        .with_context(|| format!("Failed to write detection report: {:?}", report_path))?;
-    info!("Detection report written to {:?}", report_path);
+        // Write detection report JSON
+        let report_json = serde_json::to_string_pretty(&report)
+            .context("Failed to serialize detection report")?;
+        let report_path = orchestrator.output_dir().join("detection-report.json");
+        outdir::write_atomic(&report_path, &report_json)
+            .with_context(|| format!("Failed to write detection report: {:?}", report_path))?;
+        info!("Detection report written to {:?}", report_path);
 
-    let results = orchestrator.run_all(&languages);
+        results = orchestrator.run_all(&languages);
+        produced = collect_indexes(&orchestrator, &results, output_dir.as_deref(), format)?;
+        if let Some(run) = incremental {
+            if let Err(e) = run.finish(&results) {
+                notice(format, format!("Warning: Failed to record the index manifest: {:#}", e));
+            }
+        }
 
-    // Collect successful results
-    let successful: Vec<_> = results.iter().filter(|r| r.success).collect();
+        let successful = results.iter().filter(|r| r.success).count();
+        if !languages.iter().any(|l| l.language.has_scip_indexer()) {
+            notice(format, "No detected language has a SCIP indexer".to_string());
+        } else if successful == 0 {
+            notice(format, "No indexers completed successfully.".to_string());
+            if format == OutputFormat::Text {
+                eprintln!("\nTo install SCIP indexers:");
+                for lang_info in &languages {
+                    eprintln!("  {}: {}", lang_info.language.display_name(), lang_info.language.install_command());
+                }
+            }
+        } else {
+            info!("{} of {} indexers completed successfully", successful, languages.len());
+        }
+        indexing_exit(&results, config.fail_on)
+    };
 
-    if successful.is_empty() {
-        eprintln!("No indexers completed successfully.");
-        eprintln!("\nTo install SCIP indexers:");
-        for lang_info in &languages {
-            eprintln!("  {}: {}", lang_info.language.display_name(), lang_info.language.install_command());
+    match format {
+        OutputFormat::Json => Envelope::new(
+            "analyze",
+            AnalyzeReport {
+                codebase: &config.input_path,
+                detection: &report,
+                results: &results,
+                produced: &produced,
+                exit_code: exit as u8,
+            },
+        )
+        .print()?,
+        OutputFormat::Text => {
+            // Print paths of produced .scip files
+            if !produced.is_empty() {
+                println!("Produced SCIP index files:");
+                for index in &produced {
+                    println!("  {} -> {}", index.language.display_name(), index.path.display());
+                }
+            }
         }
-        return Ok(());
     }
+    Ok(exit)
+}
 
-    info!(
-        "{} of {} indexers completed successfully",
-        successful.len(),
-        languages.len()
-    );
+/// Index a codebase, then re-index the languages affected by each change
+/// until interrupted. Status lines go to stdout whatever the `--format`;
+/// only an error stopping the command is a JSON document.
+fn watch_codebase(config: Config, (poll, debounce): (Duration, Duration), format: OutputFormat) -> Result<Exit> {
+    if !config.input_path.is_dir() {
+        return fail(format, "watch", Exit::Validation, format!("{:?} is not a directory", config.input_path));
+    }
+    let filter = match LanguageFilter::parse(&config.languages) {
+        Ok(filter) => filter,
+        Err(e) => return fail(format, "watch", Exit::Validation, format!("invalid --languages: {}", e)),
+    };
+    if let Some(e) = work_dir_error(&config) {
+        return fail(format, "watch", Exit::Validation, e);
+    }
 
-    // Step 3: Copy .scip files to output directory (if specified)
-    if let Some(ref out_dir) = output_dir {
-        fs::create_dir_all(out_dir)
-            .with_context(|| format!("Failed to create output directory: {:?}", out_dir))?;
+    let _locks = match lock_outputs(&config, config.output_path.as_deref()) {
+        Ok(locks) => locks,
+        Err(e) if e.is::<LockBusy>() => return fail(format, "watch", Exit::Locked, e.to_string()),
+        Err(e) => return Err(e),
+    };
+    let publish_dir = config.output_path.clone().unwrap_or_else(|| config.effective_work_dir());
+    // Publishing into an output directory inside the codebase is not a change
+    let skip: Vec<PathBuf> = config
+        .output_path
+        .iter()
+        .filter_map(|out| {
+            let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+            absolute(out).strip_prefix(absolute(&config.input_path)).ok().map(Path::to_path_buf)
+        })
+        .collect();
+    let mut watcher = Watcher::new(config.input_path.clone(), config.path_filter(), skip, poll, debounce);
+    println!("[watch] Watching {} (Ctrl-C to stop)", config.input_path.display());
 
-        for result in &successful {
-            if result.scip_path.exists() {
-                if let Some(name) = result.scip_path.file_name() {
-                    let dest = out_dir.join(name);
-                    fs::copy(&result.scip_path, &dest)
-                        .with_context(|| format!("Failed to copy {:?} to {:?}", result.scip_path, dest))?;
-                    info!("Copied {:?} -> {:?}", result.scip_path, dest);
+    let mut attempted = HashSet::new();
+    let mut changed: Option<Vec<PathBuf>> = None;
+    loop {
+        let started = Instant::now();
+        let what = match &changed {
+            None => "Initial index".to_string(),
+            Some(files) if files.len() == 1 => format!("{} changed", files[0].display()),
+            Some(files) => format!("{} files changed", files.len()),
+        };
+        match watch_cycle(&config, &filter, changed.as_deref(), &mut attempted, &publish_dir) {
+            Ok(outcomes) if outcomes.is_empty() => println!("[watch] {}: no index affected", what),
+            Ok(outcomes) => {
+                let summary: Vec<String> = outcomes
+                    .iter()
+                    .map(|(language, outcome)| match outcome {
+                        Outcome::Published(_) => format!("{} indexed", language.display_name()),
+                        _ => format!("{} failed (previous index kept)", language.display_name()),
+                    })
+                    .collect();
+                println!("[watch] {}: {} in {:.1}s", what, summary.join(", "), started.elapsed().as_secs_f64());
+                for (language, outcome) in &outcomes {
+                    match outcome {
+                        Outcome::Failed(e) => eprintln!("  {}: {}", language.display_name(), e),
+                        Outcome::Invalid(e) => eprintln!("  {}: invalid index: {}", language.display_name(), e),
+                        Outcome::Published(_) => {}
+                    }
                 }
             }
+            Err(e) => println!("[watch] {}: error: {:#}", what, e),
         }
+        // Watch prints no JSON document per cycle; the cycle's warnings
+        // were already logged
+        output::take_warnings();
+        changed = Some(watcher.wait_for_changes());
     }
+}
 
-    // Print paths of produced .scip files
-    println!("Produced SCIP index files:");
-    for result in &successful {
-        if result.scip_path.exists() {
-            if let Some(name) = result.scip_path.file_name() {
-                let display_path = if let Some(ref out_dir) = output_dir {
-                    out_dir.join(name)
-                } else {
-                    result.scip_path.clone()
-                };
-                println!("  {} -> {}", result.language.display_name(), display_path.display());
+/// One watch cycle: index the selected languages that have a changed input
+/// (all of them when `changed` is None) or were not detected before, into
+/// the staging directory, and publish what validates
+fn watch_cycle(
+    config: &Config,
+    filter: &LanguageFilter,
+    changed: Option<&[PathBuf]>,
+    attempted: &mut HashSet<Language>,
+    publish_dir: &Path,
+) -> Result<Vec<(Language, Outcome)>> {
+    let detector = LanguageDetector::from_filter(config.path_filter())
+        .with_symlink_policy(config.symlink_policy);
+    let report = detector.detect(&config.input_path)?;
+    let affected: Vec<LanguageInfo> = select_languages(filter, &report.languages, config, OutputFormat::Text)
+        .into_iter()
+        .filter(|info| info.language.has_scip_indexer())
+        .filter(|info| {
+            !attempted.contains(&info.language)
+                || changed.is_none_or(|files| files.iter().any(|f| info.language.is_input(f)))
+        })
+        .collect();
+    if affected.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let staging = config.effective_work_dir().join(watch::STAGING_DIR);
+    let orchestrator = IndexerOrchestrator::new_in(config.input_path.clone(), config.indexers_path.clone(), staging)?
+        .with_isolated_work_dir(config.work_dir.clone())
+        .with_package_identity(config.package_identity, config.package.clone())
+        .with_path_filter(config.path_filter())
+        .with_cache(config.scip_cache());
+    attempted.extend(affected.iter().map(|info| info.language));
+    Ok(watch::publish(&orchestrator.run_all(&affected), publish_dir))
+}
+
+/// Why `--work-dir` cannot be used: inside the codebase, it would be
+/// detected and indexed, and the codebase written to
+fn work_dir_error(config: &Config) -> Option<String> {
+    let work_dir = config.work_dir.as_ref()?;
+    let resolve = |p: &Path| {
+        p.canonicalize()
+            .unwrap_or_else(|_| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()))
+    };
+    let (work_dir, root) = (resolve(work_dir), resolve(&config.input_path));
+    work_dir
+        .starts_with(&root)
+        .then(|| format!("--work-dir {} is inside the codebase {}", work_dir.display(), root.display()))
+}
+
+/// Lock the work directory, and `output_dir` if given, for the rest of the
+/// run; a concurrent run waits up to `--lock-timeout` for them. An in-tree
+/// work directory that `cleanup` emptied is removed with its lock.
+fn lock_outputs(config: &Config, output_dir: Option<&Path>) -> Result<Vec<RunLock>> {
+    let mut work_dir = RunLock::acquire(&config.effective_work_dir(), config.lock_timeout)?;
+    if config.work_dir.is_none() {
+        work_dir = work_dir.removing_empty_dir();
+    }
+    let mut locks = vec![work_dir];
+    if let Some(dir) = output_dir {
+        locks.push(RunLock::acquire(dir, config.lock_timeout)?);
+    }
+    Ok(locks)
+}
+
+/// Exit code for the indexer results under the `--fail-on` policy
+fn indexing_exit(results: &[IndexerResult], fail_on: FailOn) -> Exit {
+    // Nothing failed when no detected language has a SCIP indexer to run
+    if !results.iter().any(|r| r.language.has_scip_indexer()) {
+        return Exit::NoLanguages;
+    }
+    if fail_on == FailOn::None {
+        return Exit::Success;
+    }
+    if !results.iter().any(|r| r.success) {
+        return Exit::TotalFailure;
+    }
+    // Languages without any SCIP indexer only count under `any`; an index
+    // missing some units is a partial failure
+    let failed = results
+        .iter()
+        .any(|r| r.partial || (!r.success && (fail_on == FailOn::Any || r.language.has_scip_indexer())));
+    if failed {
+        Exit::PartialFailure
+    } else {
+        Exit::Success
+    }
+}
+
+/// Step 3: copy the produced .scip files to `output_dir` (if specified) and
+/// return where each one ends up
+fn collect_indexes(
+    orchestrator: &IndexerOrchestrator,
+    results: &[IndexerResult],
+    output_dir: Option<&Path>,
+    format: OutputFormat,
+) -> Result<Vec<ProducedIndex>> {
+    let mut produced = Vec::new();
+    if !results.iter().any(|r| r.success) {
+        return Ok(produced);
+    }
+
+    if let Some(out_dir) = output_dir {
+        fs::create_dir_all(out_dir)
+            .with_context(|| format!("Failed to create output directory: {:?}", out_dir))?;
+    }
+
+    for result in results.iter().filter(|r| r.success && r.scip_path.exists()) {
+        let Some(name) = result.scip_path.file_name() else {
+            continue;
+        };
+        let path = match output_dir {
+            Some(out_dir) => {
+                let dest = out_dir.join(name);
+                outdir::copy_atomic(&result.scip_path, &dest)?;
+                info!("Copied {:?} -> {:?}", result.scip_path, dest);
+                dest
             }
-        }
+            None => result.scip_path.clone(),
+        };
+        produced.push(ProducedIndex { language: result.language, path });
     }
 
-    // Cleanup .legend-indexer/ if we copied files to an output dir
+    // Cleanup .legend-indexer/ if we copied files to an output dir (a
+    // --work-dir is kept)
     if output_dir.is_some() {
         if let Err(e) = orchestrator.cleanup() {
-            eprintln!("Warning: Failed to cleanup temporary files: {}", e);
+            notice(format, format!("Warning: Failed to cleanup temporary files: {}", e));
         }
     } else {
         info!("SCIP files preserved in {:?}", orchestrator.output_dir());
     }
+    Ok(produced)
+}
 
-    Ok(())
+/// JSON form of `plan`
+#[derive(Serialize)]
+struct PlanReport<'a> {
+    codebase: &'a Path,
+    output_dir: &'a Path,
+    copy_to: Option<&'a Path>,
+    languages: &'a [IndexerPlan],
+}
+
+/// Print what analyze would run for a codebase, without running anything
+fn plan_indexing(config: Config, format: OutputFormat) -> Result<Exit> {
+    let detector = LanguageDetector::from_filter(config.path_filter())
+        .with_symlink_policy(config.symlink_policy);
+    let filter = match LanguageFilter::parse(&config.languages) {
+        Ok(filter) => filter,
+        Err(e) => return fail(format, "plan", Exit::Validation, format!("invalid --languages: {}", e)),
+    };
+    let report = detector.detect(&config.input_path)?;
+    let languages = select_languages(&filter, &report.languages, &config, format);
+
+    let orchestrator = IndexerOrchestrator::for_plan_in(
+        config.input_path.clone(),
+        config.indexers_path.clone(),
+        config.effective_work_dir(),
+    )
+    .with_isolated_work_dir(config.work_dir.clone())
+    .with_package_identity(config.package_identity, config.package.clone())
+    .with_path_filter(config.path_filter());
+    let plans = orchestrator.plan_all(&languages);
+
+    if format == OutputFormat::Json {
+        let plan = PlanReport {
+            codebase: &config.input_path,
+            output_dir: orchestrator.output_dir(),
+            copy_to: config.output_path.as_deref(),
+            languages: &plans,
+        };
+        Envelope::new("plan", plan).print()?;
+        return Ok(Exit::Success);
+    }
+
+    if plans.is_empty() {
+        println!("Nothing to index in {:?}", config.input_path);
+        return Ok(Exit::Success);
+    }
+
+    println!("Indexing plan for {:?}:", config.input_path);
+    for plan in &plans {
+        println!();
+        println!(
+            "  {} ({}) -> {}",
+            plan.language.display_name(),
+            plan.indexer.unwrap_or("-"),
+            plan.output.display()
+        );
+        if let Some(reason) = &plan.skipped {
+            println!("    skipped: {}", reason);
+            if plan.language.has_scip_indexer() {
+                println!("    install: {}", plan.language.install_command());
+            }
+            continue;
+        }
+        if let Some(primary) = plan.shared_with {
+            println!("    shared with the {} run", primary.display_name());
+            continue;
+        }
+        for (i, inv) in plan.invocations.iter().enumerate() {
+            println!("    [{}] {} ({:?})", i + 1, inv.program.display(), inv.source);
+            println!("        argv: {}", inv.argv().join(" "));
+            println!("        cwd:  {}", inv.working_dir.display());
+            for (key, value) in &inv.env {
+                println!("        env:  {}={}", key, value);
+            }
+            println!("        out:  {}", inv.output.display());
+        }
+        for step in &plan.steps {
+            println!("    - {}", step);
+        }
+    }
+
+    if let Some(out_dir) = &config.output_path {
+        println!();
+        println!("Indexes would then be copied to {}", out_dir.display());
+    }
+
+    Ok(Exit::Success)
+}
+
+/// JSON form of `doctor`
+#[derive(Serialize)]
+struct DoctorReport<'a> {
+    /// Languages detected in the target codebase, if one was given
+    languages: Option<&'a [Language]>,
+    checks: &'a [doctor::Check],
+    failures: usize,
+}
+
+/// Check the environment, for a target codebase when `targeted`
+fn run_doctor(config: Config, targeted: bool, format: OutputFormat) -> Result<Exit> {
+    let orchestrator = IndexerOrchestrator::for_plan_in(
+        config.input_path.clone(),
+        config.indexers_path.clone(),
+        config.effective_work_dir(),
+    );
+
+    let languages = if targeted {
+        let detector = LanguageDetector::from_filter(config.path_filter())
+            .with_symlink_policy(config.symlink_policy);
+        let filter = match LanguageFilter::parse(&config.languages) {
+            Ok(filter) => filter,
+            Err(e) => return fail(format, "doctor", Exit::Validation, format!("invalid --languages: {}", e)),
+        };
+        let report = detector.detect(&config.input_path)?;
+        let detected = select_languages(&filter, &report.languages, &config, format);
+        Some(detected.iter().map(|l| l.language).collect::<Vec<_>>())
+    } else {
+        None
+    };
+    // The work directory is written even with --output
+    let work_dir = (targeted || config.work_dir.is_some()).then(|| orchestrator.output_dir().to_path_buf());
+    let output_dirs: Vec<PathBuf> = work_dir.into_iter().chain(config.output_path.clone()).collect();
+
+    let checks = doctor::run(&orchestrator, languages.as_deref(), &output_dirs);
+    let failures = checks.iter().filter(|c| c.status == doctor::Status::Fail).count();
+    let warnings = checks.iter().filter(|c| c.status == doctor::Status::Warn).count();
+
+    if format == OutputFormat::Json {
+        let report = DoctorReport {
+            languages: languages.as_deref(),
+            checks: &checks,
+            failures,
+        };
+        Envelope::new("doctor", report).print()?;
+    } else {
+        if let Some(languages) = &languages {
+            let names: Vec<_> = languages.iter().map(|l| l.display_name()).collect();
+            println!("Checking environment for {:?} ({})", config.input_path, names.join(", "));
+        } else {
+            println!("Checking environment (pass a codebase path to check only what it needs)");
+        }
+        for (category, title) in [
+            (doctor::Category::Indexer, "Indexers"),
+            (doctor::Category::Toolchain, "Toolchains"),
+            (doctor::Category::Output, "Output"),
+        ] {
+            let in_category: Vec<_> = checks.iter().filter(|c| c.category == category).collect();
+            if in_category.is_empty() {
+                continue;
+            }
+            println!();
+            println!("{}:", title);
+            for check in in_category {
+                let status = match check.status {
+                    doctor::Status::Ok => "ok",
+                    doctor::Status::Warn => "warn",
+                    doctor::Status::Fail => "FAIL",
+                };
+                println!("  {:4}  {:16} {}", status, check.name, check.detail);
+                if let Some(fix) = &check.remediation {
+                    println!("        {:16} fix: {}", "", fix);
+                }
+            }
+        }
+        println!();
+        println!("{} problem(s), {} warning(s)", failures, warnings);
+    }
+
+    if failures > 0 {
+        eprintln!("Error: doctor found {} problem(s)", failures);
+        return Ok(Exit::Error);
+    }
+    Ok(Exit::Success)
+}
+
+/// JSON form of `detect`
+#[derive(Serialize)]
+struct DetectReport<'a> {
+    codebase: &'a Path,
+    detection: &'a DetectionReport,
 }
 
 /// Detect languages in a codebase
-fn detect_languages(path: &PathBuf) -> Result<()> {
-    let detector = LanguageDetector::new(&[]);
+fn detect_languages(config: &Config, format: OutputFormat) -> Result<()> {
+    let path = &config.input_path;
+    let detector = LanguageDetector::from_filter(config.path_filter())
+        .with_symlink_policy(config.symlink_policy);
     let report = detector.detect(path)?;
 
+    if format == OutputFormat::Json {
+        return Envelope::new("detect", DetectReport { codebase: path, detection: &report }).print();
+    }
+
     if report.languages.is_empty() {
         println!("No supported programming languages detected.");
         print_coverage_summary(&report);
@@ -247,14 +891,56 @@ fn detect_languages(path: &PathBuf) -> Result<()> {
 
         if !info.config_files.is_empty() {
             for config in &info.config_files {
+                let package = info
+                    .manifests
+                    .iter()
+                    .find(|m| &m.path == config)
+                    .and_then(|m| match (&m.name, &m.version) {
+                        (Some(name), Some(version)) => Some(format!(" ({} {})", name, version)),
+                        (Some(name), None) => Some(format!(" ({})", name)),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                let environment = info
+                    .manifests
+                    .iter()
+                    .find(|m| &m.path == config)
+                    .and_then(|m| m.python_environment.as_ref())
+                    .map(|env| {
+                        let prefix = env.prefix.strip_prefix(path).unwrap_or(&env.prefix);
+                        format!(" [{:?} env: {}]", env.kind, prefix.display())
+                    })
+                    .unwrap_or_default();
                 println!(
-                    "    - {:?}",
-                    config.strip_prefix(path).unwrap_or(config)
+                    "    - {:?}{}{}",
+                    config.strip_prefix(path).unwrap_or(config),
+                    package,
+                    environment
                 );
             }
         }
     }
 
+    if !report.frameworks.is_empty() {
+        println!();
+        println!("Frameworks:");
+        for fw in &report.frameworks {
+            let rel = fw.location.strip_prefix(path).unwrap_or(&fw.location);
+            let location = if rel.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                rel.display().to_string()
+            };
+            println!(
+                "  {} ({}, {:?} confidence) - {}",
+                fw.display_name,
+                fw.language.display_name(),
+                fw.confidence,
+                location
+            );
+        }
+    }
+
     println!();
     print_coverage_summary(&report);
 
@@ -272,8 +958,38 @@ fn print_coverage_summary(report: &DetectionReport) {
         println!("Excluded: {} files", report.excluded_files);
     }
 
-    if report.walk_errors > 0 {
-        println!("Walk errors: {}", report.walk_errors);
+    let errors = &report.walk_errors;
+    if errors.total() > 0 {
+        println!(
+            "Walk errors: {} ({} permission denied, {} symlink loops, {} broken symlinks, {} other)",
+            errors.total(),
+            errors.permission_denied,
+            errors.symlink_loops,
+            errors.broken_symlinks,
+            errors.other
+        );
+    }
+
+    if !report.configured_empty.is_empty() {
+        println!("Configured but no source files:");
+        for info in &report.configured_empty {
+            let files: Vec<String> = info
+                .config_files
+                .iter()
+                .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .collect::<std::collections::BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            println!("  {} ({})", info.language.display_name(), files.join(", "));
+        }
+    }
+
+    if !report.escaping_symlinks.is_empty() {
+        println!("Symlinks escaping the codebase:");
+        for link in &report.escaping_symlinks {
+            let action = if link.followed { "followed" } else { "skipped" };
+            println!("  {} -> {} ({})", link.path.display(), link.target.display(), action);
+        }
     }
 
     if !report.unrecognized_extensions.is_empty() {
@@ -297,17 +1013,52 @@ fn print_coverage_summary(report: &DetectionReport) {
     }
 }
 
+/// JSON form of `check-indexers`
+#[derive(Serialize)]
+struct IndexersReport {
+    indexers: Vec<IndexerAvailability>,
+}
+
+#[derive(Serialize)]
+struct IndexerAvailability {
+    language: Language,
+    indexer: Option<&'static str>,
+    bundled: bool,
+    available: bool,
+    install_command: &'static str,
+}
+
 /// Check which indexers are available
-fn check_indexers() -> Result<()> {
-    let available = legend_indexer::orchestrate::check_available_indexers();
+fn check_indexers(indexers_path: Option<PathBuf>, format: OutputFormat) -> Result<()> {
+    let available = legend_indexer::orchestrate::check_available_indexers(indexers_path);
+
+    if format == OutputFormat::Json {
+        let indexers: Vec<_> = Language::ALL
+            .iter()
+            .map(|&language| IndexerAvailability {
+                language,
+                indexer: language.scip_indexer(),
+                bundled: language.is_bundled(),
+                available: available.get(&language).copied().unwrap_or(false),
+                install_command: language.install_command(),
+            })
+            .collect();
+        return Envelope::new("check-indexers", IndexersReport { indexers }).print();
+    }
     println!("SCIP Indexer Availability:\n");
 
     for (header, bundled) in &[("Bundled (priority) indexers:", true), ("Additional indexers:", false)] {
         println!("{}", header);
         for &lang in Language::ALL {
             if lang.is_bundled() != *bundled { continue; }
-            let status = if available.get(&lang).copied().unwrap_or(false) { "available" } else { "not found" };
-            println!("  {:12} ({:20}) - {}", lang.display_name(), lang.scip_indexer(), status);
+            let status = if !lang.has_scip_indexer() {
+                "no SCIP indexer"
+            } else if available.get(&lang).copied().unwrap_or(false) {
+                "available"
+            } else {
+                "not found"
+            };
+            println!("  {:12} ({:20}) - {}", lang.display_name(), lang.scip_indexer().unwrap_or("-"), status);
         }
         println!();
     }
@@ -320,3 +1071,51 @@ fn check_indexers() -> Result<()> {
 
     Ok(())
 }
+
+/// JSON form of `cache prune`
+#[derive(Serialize)]
+struct CachePruneReport<'a> {
+    dir: &'a Path,
+    #[serde(flatten)]
+    report: cache::PruneReport,
+}
+
+/// `cache stats` and `cache prune`
+fn manage_cache(dir: Option<PathBuf>, max_size: u64, action: CacheAction, format: OutputFormat) -> Result<()> {
+    let dir = dir.context("No cache directory given; pass --cache-dir")?;
+    let cache = ScipCache::new(dir, max_size);
+
+    match action {
+        CacheAction::Stats => {
+            let stats = cache.stats()?;
+            if format == OutputFormat::Json {
+                return Envelope::new("cache-stats", stats).print();
+            }
+            println!("SCIP cache: {}", stats.dir.display());
+            println!(
+                "  {} entries, {} of {}",
+                stats.total.entries,
+                doctor::format_bytes(stats.total.size),
+                doctor::format_bytes(stats.max_size)
+            );
+            for (language, usage) in &stats.languages {
+                println!("  {:12} {:>5} entries  {}", language, usage.entries, doctor::format_bytes(usage.size));
+            }
+        }
+        CacheAction::Prune { all } => {
+            let report = cache.prune(if all { 0 } else { max_size })?;
+            if format == OutputFormat::Json {
+                return Envelope::new("cache-prune", CachePruneReport { dir: cache.dir(), report }).print();
+            }
+            println!(
+                "Removed {} entries ({}); {} entries ({}) remain in {}",
+                report.removed.entries,
+                doctor::format_bytes(report.removed.size),
+                report.remaining.entries,
+                doctor::format_bytes(report.remaining.size),
+                cache.dir().display()
+            );
+        }
+    }
+    Ok(())
+}
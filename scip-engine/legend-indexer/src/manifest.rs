@@ -0,0 +1,1027 @@
+//! Build manifest parsing
+//!
+//! Extracts project metadata (package name, version, workspace members,
+//! declared dependencies, target frameworks) from the config files found
+//! during language detection.
+
+use crate::pyenv::PythonEnvironment;
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::path::{Path, PathBuf};
+
+/// The manifest formats we know how to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestKind {
+    PackageJson,
+    Cargo,
+    Pyproject,
+    Requirements,
+    Pipfile,
+    CondaEnv,
+    GoMod,
+    GoWork,
+    Maven,
+    Gradle,
+    Gemfile,
+    Composer,
+    Pubspec,
+    MsBuild,
+}
+
+impl ManifestKind {
+    /// Manifest kind for a config file name, if it is one we parse
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "package.json" => Self::PackageJson,
+            "Cargo.toml" => Self::Cargo,
+            "pyproject.toml" => Self::Pyproject,
+            "requirements.txt" => Self::Requirements,
+            "Pipfile" => Self::Pipfile,
+            "environment.yml" | "environment.yaml" => Self::CondaEnv,
+            "go.mod" => Self::GoMod,
+            "go.work" => Self::GoWork,
+            "pom.xml" => Self::Maven,
+            "build.gradle" | "build.gradle.kts" => Self::Gradle,
+            "Gemfile" => Self::Gemfile,
+            "composer.json" => Self::Composer,
+            "pubspec.yaml" => Self::Pubspec,
+            n if n.ends_with(".csproj") || n.ends_with(".vbproj") || n.ends_with(".fsproj") => {
+                Self::MsBuild
+            }
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Whether this manifest describes a Python project
+    pub fn is_python(self) -> bool {
+        matches!(self, Self::Pyproject | Self::Requirements | Self::Pipfile | Self::CondaEnv)
+    }
+}
+
+/// Which part of the build a dependency is declared for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Runtime,
+    Dev,
+    Build,
+}
+
+/// A dependency declared in a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    /// Version or version requirement as written, if any
+    pub version: Option<String>,
+    pub kind: DependencyKind,
+}
+
+/// Metadata extracted from one build manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    /// Path to the manifest file
+    pub path: PathBuf,
+    pub kind: ManifestKind,
+    /// Package name (Maven and Gradle use `group:artifact`)
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Workspace members / modules as declared (paths or globs)
+    pub workspace_members: Vec<String>,
+    pub dependencies: Vec<Dependency>,
+    /// Target runtimes or frameworks, e.g. `net8.0`, `node>=18`, `go1.21`
+    pub target_frameworks: Vec<String>,
+    /// MSBuild project SDK, e.g. `Microsoft.NET.Sdk.Web` (.NET projects only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk: Option<String>,
+    /// Python environment resolved for this project (Python manifests only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python_environment: Option<PythonEnvironment>,
+}
+
+impl ProjectManifest {
+    fn new(path: &Path, kind: ManifestKind) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind,
+            name: None,
+            version: None,
+            workspace_members: Vec::new(),
+            dependencies: Vec::new(),
+            target_frameworks: Vec::new(),
+            sdk: None,
+            python_environment: None,
+        }
+    }
+
+    /// Directory containing the manifest, i.e. the project root it describes
+    pub fn project_root(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    fn add_dependency(&mut self, name: &str, version: Option<&str>, kind: DependencyKind) {
+        let version = version
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && *v != "*");
+        self.dependencies.push(Dependency {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            kind,
+        });
+    }
+}
+
+/// Parse a manifest file.
+///
+/// Returns `Ok(None)` for config files that carry no project metadata
+/// (e.g. `tsconfig.json`, `go.sum`, `.sln`).
+pub fn parse_manifest(path: &Path) -> Result<Option<ProjectManifest>> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let Some(kind) = ManifestKind::from_file_name(file_name) else {
+        return Ok(None);
+    };
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read manifest: {:?}", path))?;
+    let mut manifest = ProjectManifest::new(path, kind);
+
+    match kind {
+        ManifestKind::PackageJson => parse_package_json(&content, &mut manifest),
+        ManifestKind::Cargo => parse_cargo_toml(&content, &mut manifest),
+        ManifestKind::Pyproject => parse_pyproject(&content, &mut manifest),
+        ManifestKind::Requirements => {
+            parse_requirements(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::Pipfile => parse_pipfile(&content, &mut manifest),
+        ManifestKind::CondaEnv => {
+            parse_conda_env(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::GoMod | ManifestKind::GoWork => {
+            parse_go_mod(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::Maven => parse_pom(&content, &mut manifest),
+        ManifestKind::Gradle => {
+            parse_gradle(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::Gemfile => {
+            parse_gemfile(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::Composer => parse_composer(&content, &mut manifest),
+        ManifestKind::Pubspec => {
+            parse_pubspec(&content, &mut manifest);
+            Ok(())
+        }
+        ManifestKind::MsBuild => parse_msbuild(&content, &mut manifest),
+    }
+    .with_context(|| format!("Failed to parse manifest: {:?}", path))?;
+
+    Ok(Some(manifest))
+}
+
+// ---------------------------------------------------------------------------
+// JSON manifests
+// ---------------------------------------------------------------------------
+
+fn json_str(value: &Json, key: &str) -> Option<String> {
+    value.get(key).and_then(Json::as_str).map(str::to_string)
+}
+
+fn json_strings(value: Option<&Json>) -> Vec<String> {
+    value
+        .and_then(Json::as_array)
+        .map(|a| a.iter().filter_map(Json::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn parse_package_json(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let json: Json = serde_json::from_str(content)?;
+    m.name = json_str(&json, "name");
+    m.version = json_str(&json, "version");
+
+    // `workspaces` is either an array or `{ "packages": [...] }` (yarn)
+    m.workspace_members = match json.get("workspaces") {
+        Some(Json::Object(o)) => json_strings(o.get("packages")),
+        other => json_strings(other),
+    };
+
+    for (key, kind) in [
+        ("dependencies", DependencyKind::Runtime),
+        ("peerDependencies", DependencyKind::Runtime),
+        ("optionalDependencies", DependencyKind::Runtime),
+        ("devDependencies", DependencyKind::Dev),
+    ] {
+        if let Some(Json::Object(deps)) = json.get(key) {
+            for (name, version) in deps {
+                m.add_dependency(name, version.as_str(), kind);
+            }
+        }
+    }
+
+    if let Some(Json::Object(engines)) = json.get("engines") {
+        for (engine, range) in engines {
+            if let Some(range) = range.as_str() {
+                m.target_frameworks.push(format!("{}{}", engine, range));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_composer(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let json: Json = serde_json::from_str(content)?;
+    m.name = json_str(&json, "name");
+    m.version = json_str(&json, "version");
+
+    for (key, kind) in [("require", DependencyKind::Runtime), ("require-dev", DependencyKind::Dev)] {
+        if let Some(Json::Object(deps)) = json.get(key) {
+            for (name, version) in deps {
+                // Platform requirements are not packages
+                if name == "php" {
+                    if let Some(v) = version.as_str() {
+                        m.target_frameworks.push(format!("php{}", v));
+                    }
+                } else if !name.starts_with("ext-") {
+                    m.add_dependency(name, version.as_str(), kind);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// TOML manifests
+// ---------------------------------------------------------------------------
+
+fn toml_str(table: Option<&toml::Table>, key: &str) -> Option<String> {
+    table?.get(key)?.as_str().map(str::to_string)
+}
+
+fn toml_table<'a>(table: Option<&'a toml::Table>, key: &str) -> Option<&'a toml::Table> {
+    table?.get(key)?.as_table()
+}
+
+fn toml_strings(table: Option<&toml::Table>, key: &str) -> Vec<String> {
+    table
+        .and_then(|t| t.get(key))
+        .and_then(toml::Value::as_array)
+        .map(|a| a.iter().filter_map(toml::Value::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Add dependencies from a `name = "1.0"` / `name = { version = "1.0" }` table
+fn add_toml_dependencies(
+    m: &mut ProjectManifest,
+    deps: Option<&toml::Table>,
+    kind: DependencyKind,
+    skip: &[&str],
+) {
+    for (name, spec) in deps.into_iter().flatten() {
+        if skip.contains(&name.as_str()) {
+            continue;
+        }
+        let version = match spec {
+            toml::Value::String(v) => Some(v.as_str()),
+            toml::Value::Table(t) => t.get("version").and_then(toml::Value::as_str),
+            _ => None,
+        };
+        m.add_dependency(name, version, kind);
+    }
+}
+
+fn parse_cargo_toml(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let doc: toml::Table = content.parse()?;
+    let package = toml_table(Some(&doc), "package");
+    let workspace = toml_table(Some(&doc), "workspace");
+
+    m.name = toml_str(package, "name");
+    // `version.workspace = true` inherits; fall back to the workspace version
+    m.version = toml_str(package, "version")
+        .or_else(|| toml_str(toml_table(workspace, "package"), "version"));
+    m.workspace_members = toml_strings(workspace, "members");
+
+    add_toml_dependencies(m, toml_table(Some(&doc), "dependencies"), DependencyKind::Runtime, &[]);
+    add_toml_dependencies(m, toml_table(Some(&doc), "dev-dependencies"), DependencyKind::Dev, &[]);
+    add_toml_dependencies(m, toml_table(Some(&doc), "build-dependencies"), DependencyKind::Build, &[]);
+    add_toml_dependencies(m, toml_table(workspace, "dependencies"), DependencyKind::Runtime, &[]);
+
+    if let Some(rust_version) = toml_str(package, "rust-version") {
+        m.target_frameworks.push(format!("rust{}", rust_version));
+    }
+    Ok(())
+}
+
+fn parse_pyproject(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let doc: toml::Table = content.parse()?;
+    let project = toml_table(Some(&doc), "project");
+    let tool = toml_table(Some(&doc), "tool");
+    let poetry = toml_table(tool, "poetry");
+
+    m.name = toml_str(project, "name").or_else(|| toml_str(poetry, "name"));
+    m.version = toml_str(project, "version").or_else(|| toml_str(poetry, "version"));
+    m.workspace_members = toml_strings(toml_table(toml_table(tool, "uv"), "workspace"), "members");
+
+    // PEP 621
+    for req in toml_strings(project, "dependencies") {
+        add_requirement(m, &req, DependencyKind::Runtime);
+    }
+    for (_, group) in toml_table(project, "optional-dependencies").into_iter().flatten() {
+        for req in group.as_array().into_iter().flatten().filter_map(toml::Value::as_str) {
+            add_requirement(m, req, DependencyKind::Runtime);
+        }
+    }
+    // PEP 735 dependency groups
+    for (_, group) in toml_table(Some(&doc), "dependency-groups").into_iter().flatten() {
+        for req in group.as_array().into_iter().flatten().filter_map(toml::Value::as_str) {
+            add_requirement(m, req, DependencyKind::Dev);
+        }
+    }
+
+    // Poetry
+    add_toml_dependencies(m, toml_table(poetry, "dependencies"), DependencyKind::Runtime, &["python"]);
+    add_toml_dependencies(m, toml_table(poetry, "dev-dependencies"), DependencyKind::Dev, &[]);
+    for (_, group) in toml_table(poetry, "group").into_iter().flatten() {
+        let deps = group.as_table().and_then(|g| g.get("dependencies")).and_then(toml::Value::as_table);
+        add_toml_dependencies(m, deps, DependencyKind::Dev, &[]);
+    }
+
+    let requires_python = toml_str(project, "requires-python")
+        .or_else(|| toml_str(toml_table(poetry, "dependencies"), "python"));
+    if let Some(requires) = requires_python {
+        m.target_frameworks.push(format!("python{}", requires));
+    }
+    Ok(())
+}
+
+fn parse_pipfile(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let doc: toml::Table = content.parse()?;
+    add_toml_dependencies(m, toml_table(Some(&doc), "packages"), DependencyKind::Runtime, &[]);
+    add_toml_dependencies(m, toml_table(Some(&doc), "dev-packages"), DependencyKind::Dev, &[]);
+    if let Some(python) = toml_str(toml_table(Some(&doc), "requires"), "python_version") {
+        m.target_frameworks.push(format!("python{}", python));
+    }
+    Ok(())
+}
+
+/// Split a PEP 508 requirement (`requests[socks]>=2.0; python_version<"3.8"`)
+/// into name and version specifier.
+fn split_requirement(req: &str) -> Option<(&str, Option<&str>)> {
+    let req = req.split(';').next()?.trim();
+    let name_end = req
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(req.len());
+    let name = &req[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = req[name_end..].trim_start();
+    if rest.starts_with('[') {
+        rest = rest.find(']').map_or("", |i| rest[i + 1..].trim_start());
+    }
+    // Direct references (`name @ url`) carry no version
+    let version = (!rest.is_empty() && !rest.starts_with('@')).then_some(rest);
+    Some((name, version))
+}
+
+fn add_requirement(m: &mut ProjectManifest, req: &str, kind: DependencyKind) {
+    if let Some((name, version)) = split_requirement(req) {
+        m.add_dependency(name, version, kind);
+    }
+}
+
+fn parse_requirements(content: &str, m: &mut ProjectManifest) {
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or("").trim();
+        // Skip comments and pip options (-r, -e, --index-url, ...)
+        if line.is_empty() || line.starts_with('#') || line.starts_with('-') {
+            continue;
+        }
+        add_requirement(m, line, DependencyKind::Runtime);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Line-based manifests
+// ---------------------------------------------------------------------------
+
+fn parse_go_mod(content: &str, m: &mut ProjectManifest) {
+    let mut block: Option<&str> = None;
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(keyword) = block {
+            if line == ")" {
+                block = None;
+            } else if keyword == "require" {
+                add_go_requirement(m, line);
+            } else if keyword == "use" {
+                m.workspace_members.push(line.trim_matches('"').to_string());
+            }
+            continue;
+        }
+
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match keyword {
+            _ if rest == "(" => block = Some(keyword),
+            "module" => m.name = Some(rest.trim_matches('"').to_string()),
+            "go" => m.target_frameworks.push(format!("go{}", rest)),
+            "require" => add_go_requirement(m, rest),
+            // go.work only
+            "use" => m.workspace_members.push(rest.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+}
+
+fn add_go_requirement(m: &mut ProjectManifest, spec: &str) {
+    let mut parts = spec.split_whitespace();
+    if let Some(path) = parts.next() {
+        m.add_dependency(path, parts.next(), DependencyKind::Runtime);
+    }
+}
+
+/// First single- or double-quoted string on a line
+fn first_quoted(line: &str) -> Option<&str> {
+    let start = line.find(['"', '\''])?;
+    let quote = line[start..].chars().next()?;
+    let rest = &line[start + 1..];
+    rest.find(quote).map(|end| &rest[..end])
+}
+
+fn parse_gradle(content: &str, m: &mut ProjectManifest) {
+    const CONFIGURATIONS: &[(&str, DependencyKind)] = &[
+        ("implementation", DependencyKind::Runtime),
+        ("api", DependencyKind::Runtime),
+        ("runtimeOnly", DependencyKind::Runtime),
+        ("compile", DependencyKind::Runtime),
+        ("compileOnly", DependencyKind::Build),
+        ("annotationProcessor", DependencyKind::Build),
+        ("kapt", DependencyKind::Build),
+        ("ksp", DependencyKind::Build),
+        ("testImplementation", DependencyKind::Dev),
+        ("testRuntimeOnly", DependencyKind::Dev),
+        ("testCompileOnly", DependencyKind::Dev),
+        ("androidTestImplementation", DependencyKind::Dev),
+    ];
+
+    let mut group = None;
+    for line in content.lines() {
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix("group") {
+            if rest.trim_start().starts_with('=') {
+                group = first_quoted(rest).map(str::to_string);
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("version") {
+            if rest.trim_start().starts_with('=') {
+                m.version = first_quoted(rest).map(str::to_string);
+            }
+            continue;
+        }
+
+        let word_end = line
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(line.len());
+        let Some(&(_, kind)) = CONFIGURATIONS.iter().find(|(c, _)| *c == &line[..word_end]) else {
+            continue;
+        };
+        // Only string coordinates (`group:artifact:version`); project() and
+        // platform() references are skipped.
+        let args = line[word_end..].trim_start();
+        let args = args.strip_prefix('(').unwrap_or(args).trim_start();
+        if !args.starts_with(['"', '\'']) {
+            continue;
+        }
+        let Some(coords) = first_quoted(args) else {
+            continue;
+        };
+        let mut parts = coords.split(':');
+        if let (Some(g), Some(a)) = (parts.next(), parts.next()) {
+            m.add_dependency(&format!("{}:{}", g, a), parts.next(), kind);
+        }
+    }
+
+    // Gradle projects are named after their directory unless settings say otherwise
+    let dir_name = m.project_root().file_name().map(|n| n.to_string_lossy().into_owned());
+    m.name = match (group, dir_name) {
+        (Some(g), Some(d)) => Some(format!("{}:{}", g, d)),
+        (None, d) => d,
+        (Some(_), None) => None,
+    };
+}
+
+fn parse_gemfile(content: &str, m: &mut ProjectManifest) {
+    // Depth of `group ... do` blocks that only apply to development/test
+    let mut dev_depth = 0usize;
+    let mut depth = 0usize;
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with("group ") && line.ends_with(" do") {
+            depth += 1;
+            if dev_depth == 0 && (line.contains(":development") || line.contains(":test")) {
+                dev_depth = depth;
+            }
+            continue;
+        }
+        if line.ends_with(" do") {
+            depth += 1;
+            continue;
+        }
+        if line == "end" {
+            if depth == dev_depth {
+                dev_depth = 0;
+            }
+            depth = depth.saturating_sub(1);
+            continue;
+        }
+
+        let Some(rest) = line.strip_prefix("gem ") else {
+            continue;
+        };
+        let mut args = rest.split(',');
+        let Some(name) = args.next().and_then(first_quoted) else {
+            continue;
+        };
+        let version = args.next().and_then(first_quoted);
+        let kind = if dev_depth > 0 || rest.contains("group: :development") || rest.contains("group: :test") {
+            DependencyKind::Dev
+        } else {
+            DependencyKind::Runtime
+        };
+        m.add_dependency(name, version, kind);
+    }
+}
+
+fn parse_pubspec(content: &str, m: &mut ProjectManifest) {
+    let mut section = "";
+    for raw in content.lines() {
+        let line = raw.split(" #").next().unwrap_or("");
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+
+        if indent == 0 {
+            section = key;
+            match key {
+                "name" => m.name = Some(value.to_string()),
+                "version" => m.version = Some(value.to_string()),
+                _ => {}
+            }
+            continue;
+        }
+        // Only direct children of a section (nested keys describe sources)
+        if indent != 2 {
+            continue;
+        }
+        match section {
+            "dependencies" => m.add_dependency(key, Some(value), DependencyKind::Runtime),
+            "dev_dependencies" => m.add_dependency(key, Some(value), DependencyKind::Dev),
+            // `sdk` is the Dart SDK; `flutter` is the Flutter SDK
+            "environment" => {
+                let runtime = if key == "sdk" { "dart" } else { key };
+                m.target_frameworks.push(format!("{}{}", runtime, value));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_conda_env(content: &str, m: &mut ProjectManifest) {
+    let mut section = "";
+    // Indent of the `- pip:` entry while inside its nested list
+    let mut pip_indent: Option<usize> = None;
+    for raw in content.lines() {
+        let line = raw.split(" #").next().unwrap_or("");
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 && !trimmed.starts_with('-') {
+            let (key, value) = trimmed.split_once(':').unwrap_or((trimmed, ""));
+            section = key;
+            pip_indent = None;
+            if key == "name" {
+                m.name = Some(value.trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+            }
+            continue;
+        }
+        if section != "dependencies" {
+            continue;
+        }
+        let Some(item) = trimmed.strip_prefix('-').map(str::trim) else {
+            continue;
+        };
+        if pip_indent.is_some_and(|pip| indent > pip) {
+            add_requirement(m, item, DependencyKind::Runtime);
+            continue;
+        }
+        pip_indent = None;
+        if item == "pip:" {
+            pip_indent = Some(indent);
+            continue;
+        }
+        // Drop a channel prefix such as `conda-forge::`
+        let spec = item.rsplit("::").next().unwrap_or(item);
+        match split_requirement(spec) {
+            Some(("python", Some(version))) => m.target_frameworks.push(format!("python{}", version)),
+            Some(("python", None)) => {}
+            Some((name, version)) => m.add_dependency(name, version, DependencyKind::Runtime),
+            None => {}
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// XML manifests
+// ---------------------------------------------------------------------------
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+#[derive(Default)]
+struct PendingDependency {
+    group: Option<String>,
+    artifact: Option<String>,
+    version: Option<String>,
+    scope: Option<String>,
+}
+
+fn parse_pom(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<String> = Vec::new();
+    let (mut group, mut parent_group, mut artifact) = (None, None, None);
+    let mut parent_version = None;
+    let mut dependency: Option<PendingDependency> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                if name == "dependency" && stack.join("/") == "project/dependencies" {
+                    dependency = Some(PendingDependency::default());
+                }
+                stack.push(name);
+            }
+            Event::End(_) => {
+                let closed = stack.pop();
+                if closed.as_deref() == Some("dependency") {
+                    if let Some(dep) = dependency.take() {
+                        let kind = match dep.scope.as_deref() {
+                            Some("test") => DependencyKind::Dev,
+                            Some("provided") => DependencyKind::Build,
+                            _ => DependencyKind::Runtime,
+                        };
+                        if let Some(artifact) = dep.artifact {
+                            let name = match dep.group {
+                                Some(g) => format!("{}:{}", g, artifact),
+                                None => artifact,
+                            };
+                            m.add_dependency(&name, dep.version.as_deref(), kind);
+                        }
+                    }
+                }
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?.into_owned();
+                match stack.join("/").as_str() {
+                    "project/groupId" => group = Some(text),
+                    "project/artifactId" => artifact = Some(text),
+                    "project/version" => m.version = Some(text),
+                    "project/parent/groupId" => parent_group = Some(text),
+                    "project/parent/version" => parent_version = Some(text),
+                    "project/modules/module" => m.workspace_members.push(text),
+                    "project/properties/maven.compiler.release"
+                    | "project/properties/maven.compiler.source"
+                    | "project/properties/java.version" => {
+                        let framework = format!("java{}", text);
+                        if !m.target_frameworks.contains(&framework) {
+                            m.target_frameworks.push(framework);
+                        }
+                    }
+                    path => {
+                        if let Some(dep) = dependency.as_mut() {
+                            match path.strip_prefix("project/dependencies/dependency/") {
+                                Some("groupId") => dep.group = Some(text),
+                                Some("artifactId") => dep.artifact = Some(text),
+                                Some("version") => dep.version = Some(text),
+                                Some("scope") => dep.scope = Some(text),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let group = group.or(parent_group);
+    m.name = match (group, artifact) {
+        (Some(g), Some(a)) => Some(format!("{}:{}", g, a)),
+        (None, a) => a,
+        (Some(_), None) => None,
+    };
+    if m.version.is_none() {
+        m.version = parent_version;
+    }
+    Ok(())
+}
+
+fn parse_msbuild(content: &str, m: &mut ProjectManifest) -> Result<()> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<String> = Vec::new();
+    let (mut assembly_name, mut package_id) = (None, None);
+    // PackageReference whose Version may still arrive as a child element
+    let mut reference: Option<(String, Option<String>)> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Empty(e) if local_name(&e) == "PackageReference" => {
+                if let Some(include) = attribute(&e, "Include") {
+                    m.add_dependency(&include, attribute(&e, "Version").as_deref(), DependencyKind::Runtime);
+                }
+            }
+            Event::Start(e) => {
+                let name = local_name(&e);
+                if name == "Project" && stack.is_empty() {
+                    m.sdk = attribute(&e, "Sdk");
+                }
+                if name == "PackageReference" {
+                    reference = attribute(&e, "Include").map(|i| (i, attribute(&e, "Version")));
+                }
+                stack.push(name);
+            }
+            Event::End(_) => {
+                let closed = stack.pop();
+                if closed.as_deref() == Some("PackageReference") {
+                    if let Some((include, version)) = reference.take() {
+                        m.add_dependency(&include, version.as_deref(), DependencyKind::Runtime);
+                    }
+                }
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?.into_owned();
+                match stack.last().map(String::as_str) {
+                    Some("TargetFramework") => m.target_frameworks.push(text),
+                    Some("TargetFrameworks") => m.target_frameworks.extend(
+                        text.split(';').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string),
+                    ),
+                    Some("AssemblyName") => assembly_name = Some(text),
+                    Some("PackageId") => package_id = Some(text),
+                    Some("Version") if reference.is_some() => {
+                        if let Some((_, version)) = reference.as_mut() {
+                            *version = Some(text);
+                        }
+                    }
+                    Some("Version") => m.version = Some(text),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    // Projects default to their file name as assembly name
+    m.name = package_id.or(assembly_name).or_else(|| {
+        m.path.file_stem().map(|s| s.to_string_lossy().into_owned())
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn parse(file_name: &str, content: &str) -> ProjectManifest {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(file_name);
+        std::fs::write(&path, content).unwrap();
+        parse_manifest(&path).unwrap().unwrap()
+    }
+
+    fn dep<'a>(m: &'a ProjectManifest, name: &str) -> &'a Dependency {
+        m.dependencies.iter().find(|d| d.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_package_json() {
+        let m = parse(
+            "package.json",
+            r#"{"name":"web","version":"1.2.0","workspaces":{"packages":["apps/*"]},
+                "dependencies":{"next":"^14.0.0"},"devDependencies":{"jest":"^29"},
+                "engines":{"node":">=18"}}"#,
+        );
+        assert_eq!(m.name.as_deref(), Some("web"));
+        assert_eq!(m.version.as_deref(), Some("1.2.0"));
+        assert_eq!(m.workspace_members, vec!["apps/*"]);
+        assert_eq!(dep(&m, "next").version.as_deref(), Some("^14.0.0"));
+        assert_eq!(dep(&m, "jest").kind, DependencyKind::Dev);
+        assert_eq!(m.target_frameworks, vec!["node>=18"]);
+    }
+
+    #[test]
+    fn test_conda_environment() {
+        let m = parse(
+            "environment.yml",
+            "name: science\nchannels:\n  - conda-forge\ndependencies:\n  - python=3.11\n  - conda-forge::numpy>=1.24\n  - pip\n  - pip:\n      - requests==2.31.0\n  - scipy\n",
+        );
+        assert_eq!(m.name.as_deref(), Some("science"));
+        assert_eq!(m.target_frameworks, vec!["python=3.11"]);
+        assert_eq!(dep(&m, "numpy").version.as_deref(), Some(">=1.24"));
+        assert_eq!(dep(&m, "requests").version.as_deref(), Some("==2.31.0"));
+        assert!(m.dependencies.iter().any(|d| d.name == "scipy"));
+        assert!(!m.dependencies.iter().any(|d| d.name == "conda-forge"));
+    }
+
+    #[test]
+    fn test_cargo_toml() {
+        let m = parse(
+            "Cargo.toml",
+            r#"
+[package]
+name = "app"
+version.workspace = true
+rust-version = "1.70"
+
+[workspace]
+members = ["crates/*"]
+package = { version = "0.3.0" }
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+local = { path = "../local" }
+
+[dev-dependencies]
+tempfile = "3"
+"#,
+        );
+        assert_eq!(m.name.as_deref(), Some("app"));
+        assert_eq!(m.version.as_deref(), Some("0.3.0"));
+        assert_eq!(m.workspace_members, vec!["crates/*"]);
+        assert_eq!(dep(&m, "serde").version.as_deref(), Some("1.0"));
+        assert_eq!(dep(&m, "local").version, None);
+        assert_eq!(dep(&m, "tempfile").kind, DependencyKind::Dev);
+        assert_eq!(m.target_frameworks, vec!["rust1.70"]);
+    }
+
+    #[test]
+    fn test_pyproject_and_requirements() {
+        let m = parse(
+            "pyproject.toml",
+            r#"
+[project]
+name = "svc"
+version = "0.1.0"
+requires-python = ">=3.10"
+dependencies = ["django>=4.2", "requests[socks] ; python_version < '3.12'"]
+"#,
+        );
+        assert_eq!(m.name.as_deref(), Some("svc"));
+        assert_eq!(dep(&m, "django").version.as_deref(), Some(">=4.2"));
+        assert_eq!(dep(&m, "requests").version, None);
+        assert_eq!(m.target_frameworks, vec!["python>=3.10"]);
+
+        let r = parse("requirements.txt", "# pinned\n-r base.txt\nflask==3.0.0  # web\nnumpy\n");
+        let names: Vec<&str> = r.dependencies.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["flask", "numpy"]);
+        assert_eq!(dep(&r, "flask").version.as_deref(), Some("==3.0.0"));
+    }
+
+    #[test]
+    fn test_go_mod() {
+        let m = parse(
+            "go.mod",
+            "module github.com/acme/api\n\ngo 1.21\n\nrequire (\n\tgithub.com/gin-gonic/gin v1.9.1\n\tgolang.org/x/net v0.17.0 // indirect\n)\n\nrequire github.com/google/uuid v1.4.0\n",
+        );
+        assert_eq!(m.name.as_deref(), Some("github.com/acme/api"));
+        assert_eq!(m.target_frameworks, vec!["go1.21"]);
+        assert_eq!(m.dependencies.len(), 3);
+        assert_eq!(dep(&m, "github.com/gin-gonic/gin").version.as_deref(), Some("v1.9.1"));
+    }
+
+    #[test]
+    fn test_go_work() {
+        let m = parse("go.work", "go 1.22\n\nuse (\n\t./api\n\t./worker // jobs\n)\n\nuse ./tools\n");
+        assert_eq!(m.kind, ManifestKind::GoWork);
+        assert_eq!(m.workspace_members, vec!["./api", "./worker", "./tools"]);
+        assert_eq!(m.target_frameworks, vec!["go1.22"]);
+    }
+
+    #[test]
+    fn test_pom_xml() {
+        let m = parse(
+            "pom.xml",
+            r#"<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <parent><groupId>com.acme</groupId><version>2.0.0</version></parent>
+  <artifactId>billing</artifactId>
+  <properties><java.version>17</java.version></properties>
+  <modules><module>core</module><module>api</module></modules>
+  <dependencies>
+    <dependency><groupId>org.springframework.boot</groupId><artifactId>spring-boot-starter-web</artifactId></dependency>
+    <dependency><groupId>junit</groupId><artifactId>junit</artifactId><version>4.13</version><scope>test</scope></dependency>
+  </dependencies>
+</project>"#,
+        );
+        assert_eq!(m.name.as_deref(), Some("com.acme:billing"));
+        assert_eq!(m.version.as_deref(), Some("2.0.0"));
+        assert_eq!(m.workspace_members, vec!["core", "api"]);
+        assert_eq!(m.target_frameworks, vec!["java17"]);
+        assert_eq!(dep(&m, "junit:junit").kind, DependencyKind::Dev);
+        assert!(m.dependencies.iter().any(|d| d.name == "org.springframework.boot:spring-boot-starter-web"));
+    }
+
+    #[test]
+    fn test_csproj() {
+        let m = parse(
+            "Api.csproj",
+            r#"<Project Sdk="Microsoft.NET.Sdk.Web">
+  <PropertyGroup><TargetFrameworks>net8.0;net6.0</TargetFrameworks><Version>1.4.0</Version></PropertyGroup>
+  <ItemGroup>
+    <PackageReference Include="Serilog" Version="3.1.1" />
+    <PackageReference Include="Dapper"><Version>2.1.0</Version></PackageReference>
+  </ItemGroup>
+</Project>"#,
+        );
+        assert_eq!(m.name.as_deref(), Some("Api"));
+        assert_eq!(m.version.as_deref(), Some("1.4.0"));
+        assert_eq!(m.target_frameworks, vec!["net8.0", "net6.0"]);
+        assert_eq!(m.sdk.as_deref(), Some("Microsoft.NET.Sdk.Web"));
+        assert_eq!(dep(&m, "Serilog").version.as_deref(), Some("3.1.1"));
+        assert_eq!(dep(&m, "Dapper").version.as_deref(), Some("2.1.0"));
+    }
+
+    #[test]
+    fn test_line_based_manifests() {
+        let gradle = parse(
+            "build.gradle.kts",
+            "group = \"com.acme\"\nversion = \"0.9\"\ndependencies {\n    implementation(\"io.ktor:ktor-server-core:2.3.0\")\n    testImplementation(kotlin(\"test\"))\n    implementation(project(\":core\"))\n}\n",
+        );
+        assert_eq!(gradle.version.as_deref(), Some("0.9"));
+        assert_eq!(gradle.dependencies.len(), 1);
+        assert_eq!(dep(&gradle, "io.ktor:ktor-server-core").version.as_deref(), Some("2.3.0"));
+
+        let gemfile = parse(
+            "Gemfile",
+            "source 'https://rubygems.org'\ngem 'rails', '~> 7.1'\ngroup :development, :test do\n  gem 'rspec-rails'\nend\ngem 'pg'\n",
+        );
+        assert_eq!(dep(&gemfile, "rails").version.as_deref(), Some("~> 7.1"));
+        assert_eq!(dep(&gemfile, "rspec-rails").kind, DependencyKind::Dev);
+        assert_eq!(dep(&gemfile, "pg").kind, DependencyKind::Runtime);
+
+        let pubspec = parse(
+            "pubspec.yaml",
+            "name: app\nversion: 1.0.0+1\nenvironment:\n  sdk: '>=3.0.0 <4.0.0'\ndependencies:\n  flutter:\n    sdk: flutter\n  http: ^1.1.0\ndev_dependencies:\n  lints: ^3.0.0\n",
+        );
+        assert_eq!(pubspec.name.as_deref(), Some("app"));
+        assert_eq!(pubspec.target_frameworks, vec!["dart>=3.0.0 <4.0.0"]);
+        assert_eq!(pubspec.dependencies.len(), 3);
+        assert_eq!(dep(&pubspec, "flutter").version, None);
+        assert_eq!(dep(&pubspec, "lints").kind, DependencyKind::Dev);
+    }
+
+    #[test]
+    fn test_non_manifest_config_ignored() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("tsconfig.json");
+        std::fs::write(&path, "{}").unwrap();
+        assert!(parse_manifest(&path).unwrap().is_none());
+    }
+}
@@ -2,65 +2,240 @@
 //!
 //! Manages the execution of SCIP indexers for different languages.
 
+use crate::cache::{CacheKey, ScipCache};
+use crate::compdb;
+use crate::config::WORK_DIR_NAME;
 use crate::detect::{Language, LanguageInfo};
+use crate::identity::{self, IdentityMode, PackageIdentity};
+use crate::incremental::{self, IndexPatch};
+use crate::manifest::{ManifestKind, ProjectManifest};
+use crate::outdir;
+use crate::pathfilter::PathFilter;
+use crate::pyenv;
+use crate::scip;
+use crate::scratch;
+use crate::tsconfig::{self, GeneratedTsconfig};
 use anyhow::{anyhow, Context, Result};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
 use tracing::{debug, info, warn};
 
 /// Result of running an indexer
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct IndexerResult {
     pub language: Language,
     pub scip_path: PathBuf,
     pub success: bool,
     pub error: Option<String>,
+    /// The previous run's index was re-used because the inputs did not change
+    pub reused: bool,
+    /// The index was restored from the SCIP cache instead of running the indexer
+    pub cached: bool,
+    /// Only changed files were re-indexed, their documents spliced into the
+    /// previous index
+    pub patched: bool,
+    /// Some units (Go modules, Cargo workspaces) failed and are missing from
+    /// the index; `error` names them
+    pub partial: bool,
 }
 
+/// How the program of an indexer invocation was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinarySource {
+    /// The bundled indexers directory
+    Bundled,
+    /// A binary on PATH
+    Path,
+    /// The npm package, run through `npx`
+    Npx,
+    /// A global `dotnet tool` install (`~/.dotnet/tools`)
+    DotnetGlobalTool,
+    /// `dotnet tool run`, from a local tool manifest
+    DotnetToolRun,
+    /// Not found anywhere; launching it will fail
+    Missing,
+}
+
+/// One indexer process: what runs, where, and the index it writes
+#[derive(Debug, Clone, Serialize)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub source: BinarySource,
+    pub args: Vec<String>,
+    pub working_dir: PathBuf,
+    pub env: BTreeMap<String, String>,
+    pub output: PathBuf,
+}
+
+impl Invocation {
+    /// The full argv, program first
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.program.to_string_lossy().into_owned())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+
+    fn execute(&self) -> Result<()> {
+        debug!("Executing: {:?} {:?} in {:?}", self.program, self.args, self.working_dir);
+
+        let name = self.program.display();
+        let status = Command::new(&self.program)
+            .current_dir(&self.working_dir)
+            .args(&self.args)
+            .envs(&self.env)
+            .status()
+            .with_context(|| format!("Failed to run {}", name))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} exited with status: {:?}", name, status.code()))
+        }
+    }
+}
+
+/// What indexing one detected language would involve, worked out without
+/// running anything
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexerPlan {
+    pub language: Language,
+    pub indexer: Option<&'static str>,
+    /// The language's `.scip` file
+    pub output: PathBuf,
+    /// Why nothing would run for this language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+    /// The language whose run produces this language's index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_with: Option<Language>,
+    pub invocations: Vec<Invocation>,
+    /// Work done around the invocations (environment files, compilation
+    /// databases, merging, package identity)
+    pub steps: Vec<String>,
+}
+
+/// Suffix of the file an indexer writes before its index is validated
+const STAGED_SUFFIX: &str = ".partial.scip";
+
+/// .NET languages; scip-dotnet indexes all of them from one solution run
+const DOTNET_LANGUAGES: &[Language] = &[Language::CSharp, Language::VisualBasic, Language::FSharp];
+
 /// Orchestrates SCIP indexer execution
 pub struct IndexerOrchestrator {
     indexers_path: Option<PathBuf>,
     codebase_path: PathBuf,
     output_dir: PathBuf,
+    package_identity: IdentityMode,
+    package: Option<PackageIdentity>,
+    path_filter: PathFilter,
+    /// Previous indexes to re-use instead of running these languages' indexers
+    reuse: HashMap<Language, PathBuf>,
+    /// Previous indexes to patch instead of re-indexing every file
+    patches: HashMap<Language, IndexPatch>,
+    cache: Option<ScipCache>,
+    /// Work directory outside the codebase; indexers that write into the
+    /// codebase then run in a copy of it kept there
+    isolated_work_dir: Option<PathBuf>,
+    /// Outcome of syncing the scratch copy, done once per orchestrator
+    scratch_synced: OnceLock<Result<(), String>>,
 }
 
 impl IndexerOrchestrator {
     /// Create a new orchestrator
     pub fn new(codebase_path: PathBuf, indexers_path: Option<PathBuf>) -> Result<Self> {
-        let output_dir = codebase_path.join(".legend-indexer");
-
-        // Remove stale .scip files and detection report from previous runs
-        if output_dir.exists() {
-            for entry in std::fs::read_dir(&output_dir)
-                .context("Failed to read output directory")?
-                .flatten()
-            {
-                let path = entry.path();
-                let is_stale = path.extension().is_some_and(|ext| ext == "scip")
-                    || path.file_name().is_some_and(|n| n == "detection-report.json");
-                if is_stale {
-                    debug!("Removing stale file: {:?}", path);
-                    std::fs::remove_file(&path)
-                        .with_context(|| format!("Failed to remove stale file: {:?}", path))?;
-                }
-            }
-        }
+        let output_dir = codebase_path.join(WORK_DIR_NAME);
+        Self::new_in(codebase_path, indexers_path, output_dir)
+    }
 
+    /// Create an orchestrator writing indexes and generated inputs to
+    /// `output_dir` instead of `.legend-indexer/`
+    pub fn new_in(codebase_path: PathBuf, indexers_path: Option<PathBuf>, output_dir: PathBuf) -> Result<Self> {
+        // Previous indexes stay until this run has replaced them, so a failed
+        // run leaves them in place; `run_all` removes the ones it did not produce
         std::fs::create_dir_all(&output_dir)
             .context("Failed to create output directory")?;
 
         Ok(Self {
+            output_dir,
+            ..Self::for_plan(codebase_path, indexers_path)
+        })
+    }
+
+    /// Create an orchestrator that only plans: nothing is created or removed
+    pub fn for_plan(codebase_path: PathBuf, indexers_path: Option<PathBuf>) -> Self {
+        let output_dir = codebase_path.join(WORK_DIR_NAME);
+        Self::for_plan_in(codebase_path, indexers_path, output_dir)
+    }
+
+    /// Create an orchestrator that only plans, for outputs in `output_dir`
+    pub fn for_plan_in(codebase_path: PathBuf, indexers_path: Option<PathBuf>, output_dir: PathBuf) -> Self {
+        Self {
             indexers_path,
             codebase_path,
             output_dir,
-        })
+            package_identity: IdentityMode::default(),
+            package: None,
+            path_filter: PathFilter::default(),
+            reuse: HashMap::new(),
+            patches: HashMap::new(),
+            cache: None,
+            isolated_work_dir: None,
+            scratch_synced: OnceLock::new(),
+        }
+    }
+
+    /// Set how package identities are injected into the produced indexes
+    pub fn with_package_identity(mut self, mode: IdentityMode, package: Option<PackageIdentity>) -> Self {
+        self.package_identity = mode;
+        self.package = package;
+        self
+    }
+
+    /// Set the include/exclude patterns, applied to what each indexer indexes
+    pub fn with_path_filter(mut self, filter: PathFilter) -> Self {
+        self.path_filter = filter;
+        self
+    }
+
+    /// Re-use these previous indexes (whose inputs are unchanged) instead of
+    /// running the languages' indexers
+    pub fn with_reuse(mut self, previous: HashMap<Language, PathBuf>) -> Self {
+        self.reuse = previous;
+        self
+    }
+
+    /// Patch these previous indexes where the language's indexer can target
+    /// individual files; other languages are re-indexed in full
+    pub fn with_patches(mut self, patches: HashMap<Language, IndexPatch>) -> Self {
+        self.patches = patches;
+        self
+    }
+
+    /// Restore indexer outputs from `cache` when their key matches, and
+    /// store the ones that had to be produced
+    pub fn with_cache(mut self, cache: Option<ScipCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Leave the codebase untouched: indexers that write into it (builds,
+    /// lock files, scip-zig's `index.scip`) run in a copy of it under
+    /// `work_dir`, which must be outside the codebase
+    pub fn with_isolated_work_dir(mut self, work_dir: Option<PathBuf>) -> Self {
+        self.isolated_work_dir = work_dir;
+        self
     }
 
     /// Check if an indexer is available (either bundled or in PATH)
     pub fn is_indexer_available(&self, language: Language) -> bool {
-        self.get_bundled_path(language.scip_indexer()).is_some()
-            || self.find_indexer_in_path(language).is_some()
+        let Some(indexer) = language.scip_indexer() else {
+            return false;
+        };
+        self.get_bundled_path(indexer).is_some() || self.find_indexer_in_path(language).is_some()
     }
 
     /// Find an indexer binary in PATH
@@ -81,35 +256,67 @@ impl IndexerOrchestrator {
         None
     }
 
-    /// Run the appropriate indexer for a language
-    pub fn run_indexer(&self, language: Language) -> Result<IndexerResult> {
+    /// Run the appropriate indexer for a detected language
+    pub fn run_indexer(&self, lang_info: &LanguageInfo) -> Result<IndexerResult> {
+        let language = lang_info.language;
         info!("Running indexer for {:?}", language);
 
         let scip_output = self.scip_output_path(language);
-        let output_str = scip_output.to_str().unwrap();
+        let output = self.absolute(&scip_output);
+        // Indexers write here; the index replaces the previous one only once it is complete
+        let staged = self.absolute(&self.staged_output_path(language));
 
-        let result = match language {
-            Language::TypeScript | Language::JavaScript => {
-                self.run_typescript_indexer(&scip_output)
+        let caching = self.cache.as_ref().zip(self.cache_key(lang_info));
+        if let Some((cache, key)) = &caching {
+            // Entries are shared across checkouts; re-root at this one
+            if cache.restore(key, &staged) && self.promote(&staged, &output, true).is_ok() {
+                info!("{} index restored from the cache", language.display_name());
+                return Ok(IndexerResult {
+                    language,
+                    scip_path: scip_output,
+                    success: true,
+                    error: None,
+                    reused: false,
+                    cached: true,
+                    patched: false,
+                    partial: false,
+                });
             }
-            Language::Python => self.run_simple_indexer("scip-python", &["index", ".", "--output", output_str]),
-            Language::CSharp => self.run_dotnet_indexer(&scip_output),
-            Language::Java | Language::Kotlin | Language::Scala => {
-                self.run_simple_indexer("scip-java", &["index", "--output", output_str])
+        }
+
+        let result = match self.scratch_tree(language) {
+            Some(tree) => self.sync_scratch(&tree).and_then(|()| {
+                self.in_tree(&tree).execute_indexer(&rebase(lang_info, &self.codebase_path, &tree), &staged)
+            }),
+            None => self.execute_indexer(lang_info, &staged),
+        }
+        .and_then(|missing| {
+            self.promote(&staged, &output, self.scratch_tree(language).is_some())
+                .map(|()| missing)
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&staged);
+        }
+
+        // An index missing units is not cached; the next run retries them
+        if let (Ok(missing), Some((cache, key))) = (&result, &caching) {
+            if missing.is_empty() {
+                if let Err(e) = cache.store(key, &output) {
+                    warn!("Failed to cache the {} index: {:#}", language.display_name(), e);
+                }
             }
-            Language::Go => self.run_simple_indexer("scip-go", &["--output", output_str]),
-            Language::Rust => self.run_simple_indexer("rust-analyzer", &["scip", ".", "--output", output_str]),
-            Language::Ruby => self.run_simple_indexer("scip-ruby", &["--output", output_str]),
-            Language::Cpp | Language::C => self.run_simple_indexer("scip-clang", &["--output", output_str]),
-            _ => Err(anyhow!("Indexer for {:?} not yet implemented", language)),
-        };
+        }
 
         match result {
-            Ok(()) => Ok(IndexerResult {
+            Ok(missing) => Ok(IndexerResult {
                 language,
                 scip_path: scip_output,
                 success: true,
-                error: None,
+                error: (!missing.is_empty()).then(|| format!("Index is missing units: {}", missing.join("; "))),
+                reused: false,
+                cached: false,
+                patched: false,
+                partial: !missing.is_empty(),
             }),
             Err(e) => {
                 warn!("Indexer failed for {:?}: {}", language, e);
@@ -118,140 +325,863 @@ impl IndexerOrchestrator {
                     scip_path: scip_output,
                     success: false,
                     error: Some(e.to_string()),
+                    reused: false,
+                    cached: false,
+                    patched: false,
+                    partial: false,
                 })
             }
         }
     }
 
+    /// Run a language's indexer (in this orchestrator's codebase), writing
+    /// `staged`. Returns the units that failed and are missing from the index.
+    fn execute_indexer(&self, lang_info: &LanguageInfo, staged: &Path) -> Result<Vec<String>> {
+        let language = lang_info.language;
+        let complete = match language {
+            Language::Go => return self.run_per_unit(&self.go_invocations(lang_info, staged), staged, |_| Ok(())),
+            Language::Rust => {
+                return self.run_per_unit(&self.rust_invocations(lang_info, staged), staged, |inv| {
+                    cargo_metadata_check(&inv.working_dir, inv.env.contains_key("CARGO_NET_OFFLINE"))
+                })
+            }
+            Language::Python => self.run_python_indexer(lang_info, staged),
+            Language::Cpp | Language::C => self.run_clang_indexer(staged),
+            Language::Zig => self.run_zig_indexer(staged),
+            Language::TypeScript | Language::JavaScript => self.run_typescript_indexer(staged),
+            _ => self.indexer_invocation(language, staged).and_then(|inv| inv.execute()),
+        };
+        complete.map(|()| Vec::new())
+    }
+
+    /// The single invocation for languages that need no preparation
+    fn indexer_invocation(&self, language: Language, output: &Path) -> Result<Invocation> {
+        let output_str = output.to_string_lossy().into_owned();
+        match language {
+            Language::TypeScript | Language::JavaScript => {
+                Ok(self.typescript_invocation(self.typescript_config().as_ref(), output))
+            }
+            Language::CSharp | Language::VisualBasic | Language::FSharp => Ok(self.dotnet_invocation(output)),
+            Language::Java | Language::Kotlin | Language::Scala => Ok(self.simple_invocation(
+                &self.codebase_path,
+                "scip-java",
+                vec!["index".into(), "--output".into(), output_str],
+                output,
+            )),
+            Language::Ruby => Ok(self.simple_invocation(
+                &self.codebase_path,
+                "scip-ruby",
+                vec!["--output".into(), output_str],
+                output,
+            )),
+            _ if !language.has_scip_indexer() => Err(anyhow!(
+                "{} detected, no SCIP indexer available",
+                language.display_name()
+            )),
+            _ => Err(anyhow!("Indexer for {:?} not yet implemented", language)),
+        }
+    }
+
+    /// Work out what `run_indexer` would execute for a language
+    pub fn plan_indexer(&self, lang_info: &LanguageInfo) -> IndexerPlan {
+        let language = lang_info.language;
+        if let Some(tree) = self.scratch_tree(language) {
+            let mut plan = self.in_tree(&tree).plan_indexer(&rebase(lang_info, &self.codebase_path, &tree));
+            if plan.skipped.is_none() {
+                let tree = self.absolute(&tree);
+                plan.steps.insert(0, format!("sync a copy of the codebase to {}", tree.display()));
+                plan.steps.push(format!("set the project root to {}", scip::file_uri(&self.codebase_path)));
+            }
+            return plan;
+        }
+        let output = self.absolute(&self.scip_output_path(language));
+        let staged = self.absolute(&self.staged_output_path(language));
+        let mut steps = Vec::new();
+
+        let invocations = match language {
+            Language::Python => {
+                let env_file = self.python_environment(lang_info).map(|env| {
+                    let file = self.absolute(&self.output_dir.join("python-environment.json"));
+                    steps.push(format!(
+                        "write {:?} environment {} to {}",
+                        env.kind,
+                        env.prefix.display(),
+                        file.display()
+                    ));
+                    file
+                });
+                Ok(vec![self.python_invocation(lang_info, env_file.as_deref(), self.python_target().as_deref(), &staged)])
+            }
+            Language::Go => Ok(self.go_invocations(lang_info, &staged)),
+            Language::Rust => {
+                let invocations = self.rust_invocations(lang_info, &staged);
+                for inv in &invocations {
+                    let offline = if inv.env.contains_key("CARGO_NET_OFFLINE") { " --offline" } else { "" };
+                    steps.push(format!(
+                        "check `cargo metadata --format-version 1{}` in {}",
+                        offline,
+                        inv.working_dir.display()
+                    ));
+                }
+                Ok(invocations)
+            }
+            Language::Cpp | Language::C => {
+                let db = compdb::planned(
+                    &self.codebase_path,
+                    &self.absolute(&self.output_dir.join("compdb")),
+                    &self.path_filter,
+                );
+                let source_path = db.filtered_from.as_ref().unwrap_or(&db.path);
+                steps.push(match db.source {
+                    compdb::CompdbSource::Existing => format!("use compilation database {}", source_path.display()),
+                    source => format!("generate compilation database {} ({:?})", source_path.display(), source),
+                });
+                if db.filtered_from.is_some() {
+                    steps.push(format!("drop excluded entries into {}", db.path.display()));
+                }
+                Ok(vec![self.clang_invocation(&db.path, &staged)])
+            }
+            Language::Zig => self.zig_invocation().map(|inv| {
+                steps.push(format!("move {} to {}", inv.output.display(), staged.display()));
+                vec![inv]
+            }),
+            Language::TypeScript | Language::JavaScript => {
+                let config = self.typescript_config();
+                if let Some(config) = &config {
+                    steps.push(format!("write {} with the include/exclude patterns", config.path().display()));
+                }
+                Ok(vec![self.typescript_invocation(config.as_ref(), &staged)])
+            }
+            _ => self.indexer_invocation(language, &staged).map(|inv| vec![inv]),
+        };
+
+        let (invocations, skipped) = match invocations {
+            Ok(invocations) => (invocations, None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        if invocations.len() > 1 {
+            steps.push(format!("merge {} unit indexes into {}", invocations.len(), staged.display()));
+        }
+        if skipped.is_none() {
+            steps.push(format!("validate {} and move it to {}", staged.display(), output.display()));
+        }
+        IndexerPlan {
+            language,
+            indexer: language.scip_indexer(),
+            output,
+            skipped,
+            shared_with: None,
+            invocations,
+            steps,
+        }
+    }
+
     /// Run indexers for all detected languages
     pub fn run_all(&self, languages: &[LanguageInfo]) -> Vec<IndexerResult> {
         let mut results = Vec::new();
 
+        // One scip-dotnet run covers every .NET language; its index is written
+        // under the first requested .NET language and shared by the others
+        let dotnet_primary = dotnet_primary(languages);
+        let mut dotnet_result: Option<IndexerResult> = None;
+
         for lang_info in languages {
-            if !self.is_indexer_available(lang_info.language) {
-                warn!(
-                    "Indexer for {:?} not available. Install with: {}",
-                    lang_info.language,
-                    lang_info.language.install_command()
-                );
+            let is_dotnet = DOTNET_LANGUAGES.contains(&lang_info.language);
+            if let Some(result) = (!is_dotnet).then(|| self.reuse_previous(lang_info.language)).flatten() {
+                results.push(result);
+                continue;
+            }
+            if let Some(reason) = self.skip_reason(lang_info.language) {
                 results.push(IndexerResult {
                     language: lang_info.language,
                     scip_path: PathBuf::new(),
                     success: false,
-                    error: Some("Indexer not installed".to_string()),
+                    error: Some(reason),
+                    reused: false,
+                    cached: false,
+                    patched: false,
+                    partial: false,
                 });
                 continue;
             }
 
-            match self.run_indexer(lang_info.language) {
-                Ok(result) => results.push(result),
-                Err(e) => {
-                    results.push(IndexerResult {
-                        language: lang_info.language,
-                        scip_path: PathBuf::new(),
-                        success: false,
-                        error: Some(e.to_string()),
-                    });
-                }
+            if let Some(primary) = dotnet_primary.filter(|_| DOTNET_LANGUAGES.contains(&lang_info.language)) {
+                let shared = dotnet_result.get_or_insert_with(|| {
+                    // The shared index is only current if every .NET language is unchanged
+                    let all_unchanged = languages
+                        .iter()
+                        .filter(|l| DOTNET_LANGUAGES.contains(&l.language))
+                        .all(|l| self.reuse.contains_key(&l.language));
+                    all_unchanged
+                        .then(|| self.reuse_previous(primary.language))
+                        .flatten()
+                        .unwrap_or_else(|| self.run_indexer_or_failure(primary))
+                });
+                results.push(IndexerResult {
+                    language: lang_info.language,
+                    ..shared.clone()
+                });
+                continue;
+            }
+
+            if let Some(result) = self.patch_previous(lang_info) {
+                results.push(result);
+                continue;
             }
+            results.push(self.run_indexer_or_failure(lang_info));
         }
 
+        self.drop_excluded_documents(&results);
+        self.inject_package_identity(languages, &results);
+        self.remove_stale_indexes(&results);
         results
     }
 
-    /// Run scip-typescript indexer (special: monorepo detection + npx fallback)
-    fn run_typescript_indexer(&self, output: &Path) -> Result<()> {
-        let has_root_tsconfig = self.codebase_path.join("tsconfig.json").exists();
+    /// Work out what `run_all` would execute, without running anything
+    pub fn plan_all(&self, languages: &[LanguageInfo]) -> Vec<IndexerPlan> {
+        let dotnet_primary = dotnet_primary(languages);
 
-        let output_str = output.to_str().unwrap();
-        let mut args = vec!["index", "--output", output_str];
-        if !has_root_tsconfig {
-            debug!("No root tsconfig.json found, using --infer-tsconfig for monorepo support");
-            args.push("--infer-tsconfig");
+        languages
+            .iter()
+            .map(|lang_info| {
+                let language = lang_info.language;
+                if let Some(reason) = self.skip_reason(language) {
+                    return IndexerPlan {
+                        language,
+                        indexer: language.scip_indexer(),
+                        output: self.absolute(&self.scip_output_path(language)),
+                        skipped: Some(reason),
+                        shared_with: None,
+                        invocations: Vec::new(),
+                        steps: Vec::new(),
+                    };
+                }
+
+                let mut plan = match dotnet_primary.filter(|_| DOTNET_LANGUAGES.contains(&language)) {
+                    Some(primary) if primary.language != language => IndexerPlan {
+                        language,
+                        shared_with: Some(primary.language),
+                        invocations: Vec::new(),
+                        steps: Vec::new(),
+                        ..self.plan_indexer(primary)
+                    },
+                    Some(primary) => self.plan_indexer(primary),
+                    None => self.plan_indexer(lang_info),
+                };
+                if plan.skipped.is_none() && plan.shared_with.is_none() && !self.path_filter.is_empty() {
+                    plan.steps.push("drop documents excluded by the include/exclude patterns".to_string());
+                }
+                if plan.skipped.is_none() && plan.shared_with.is_none() && self.package_identity != IdentityMode::Off {
+                    let source = match &self.package {
+                        Some(package) => package.to_string(),
+                        None => "detected manifests".to_string(),
+                    };
+                    plan.steps.push(format!(
+                        "{} package identities from {}",
+                        if self.package_identity == IdentityMode::Fill { "fill in" } else { "override" },
+                        source
+                    ));
+                }
+                plan
+            })
+            .collect()
+    }
+
+    /// Put a language's previous index in place, if it is being re-used
+    fn reuse_previous(&self, language: Language) -> Option<IndexerResult> {
+        let previous = self.reuse.get(&language)?;
+        let scip_path = self.scip_output_path(language);
+        if previous != &scip_path {
+            if let Err(e) = outdir::copy_atomic(previous, &scip_path) {
+                warn!("Cannot re-use previous {} index {:?}: {}", language.display_name(), previous, e);
+                return None;
+            }
         }
+        info!("{} inputs unchanged; re-using the previous index", language.display_name());
+        Some(IndexerResult {
+            language,
+            scip_path,
+            success: true,
+            error: None,
+            reused: true,
+            cached: false,
+            patched: false,
+            partial: false,
+        })
+    }
 
-        // Try bundled first
-        if let Some(bundled) = self.get_bundled_path("scip-typescript") {
-            return self.execute_indexer(bundled.to_str().unwrap(), &args);
+    /// Re-index only a language's changed files and splice their documents
+    /// into its previous index, if there is a patch for it and its indexer
+    /// can target files. Falls back to a full run (None) on any failure.
+    fn patch_previous(&self, lang_info: &LanguageInfo) -> Option<IndexerResult> {
+        let language = lang_info.language;
+        let patch = self.patches.get(&language)?;
+        if !matches!(language, Language::Python | Language::TypeScript | Language::JavaScript) {
+            return None;
         }
+        let scip_path = self.scip_output_path(language);
+        match self.patch_index(lang_info, patch, &self.absolute(&scip_path)) {
+            Ok(()) => {
+                info!(
+                    "{}: re-indexed {} changed files, dropped {} deleted",
+                    language.display_name(),
+                    patch.changed.len(),
+                    patch.removed.len()
+                );
+                Some(IndexerResult {
+                    language,
+                    scip_path,
+                    success: true,
+                    error: None,
+                    reused: false,
+                    cached: false,
+                    patched: true,
+                    partial: false,
+                })
+            }
+            Err(e) => {
+                warn!("Cannot patch the previous {} index ({:#}); re-indexing everything", language.display_name(), e);
+                None
+            }
+        }
+    }
 
-        // Try npx
-        if which::which("npx").is_ok() {
-            debug!("Using npx to run scip-typescript");
-            let mut npx_args = vec!["@sourcegraph/scip-typescript"];
-            npx_args.extend(args.iter());
+    fn patch_index(&self, lang_info: &LanguageInfo, patch: &IndexPatch, output: &Path) -> Result<()> {
+        let language = lang_info.language;
+        let changed: HashSet<String> = patch.changed.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        let removed: HashSet<String> = patch.removed.iter().map(|p| p.to_string_lossy().into_owned()).collect();
 
-            let status = Command::new("npx")
-                .current_dir(&self.codebase_path)
-                .args(&npx_args)
-                .status()
-                .context("Failed to run npx scip-typescript")?;
+        let mut index = scip::Index::read(&patch.previous)?;
+        let replaced = global_definitions(index.documents.iter().filter(|doc| {
+            let path = doc.relative_path();
+            changed.contains(&path) || removed.contains(&path)
+        }));
+        index.retain_documents(|path| !changed.contains(path) && !removed.contains(path));
+        let mut defined = BTreeSet::new();
+        if !changed.is_empty() {
+            let partial = self.absolute(&self.output_dir.join(format!("{}.patch.scip", language.scip_output_stem())));
+            let run = match language {
+                Language::Python => {
+                    let env_file = self.write_python_environment(lang_info);
+                    let target = common_ancestor(&patch.changed);
+                    self.python_invocation(lang_info, env_file.as_deref(), Some(&target), &partial).execute()
+                }
+                _ => {
+                    let config = tsconfig::for_files(
+                        &self.absolute(&self.codebase_path),
+                        &self.absolute(&self.output_dir.join("typescript-patch")),
+                        &patch.changed,
+                    );
+                    config.write()?;
+                    self.typescript_invocation(Some(&config), &partial).execute()
+                }
+            };
+            let fresh = run.and_then(|()| scip::Index::read(&partial));
+            let _ = std::fs::remove_file(&partial);
+            let mut fresh = fresh?;
 
-            if status.success() {
-                return Ok(());
+            // The indexer may have emitted documents for unchanged files too
+            fresh.retain_documents(|path| changed.contains(path));
+            if fresh.documents.len() < changed.len() {
+                let indexed: HashSet<String> = fresh.documents.iter().map(scip::Document::relative_path).collect();
+                let mut missing: Vec<&String> = changed.difference(&indexed).collect();
+                missing.sort();
+                return Err(anyhow!("no documents for {:?}", missing));
             }
+            // The previous index went through package identity injection;
+            // compare like with like (a differing identity means a full run)
+            let manifests: Vec<_> = lang_info.manifests.iter().collect();
+            identity::apply(&mut fresh, &self.codebase_path, &manifests, self.package_identity, self.package.as_ref());
+            defined = global_definitions(&fresh.documents);
+            index.merge(fresh);
         }
+        // Unchanged documents refer to what the replaced ones defined; the
+        // splice only equals a full run if those definitions are the same
+        if defined != replaced {
+            return Err(anyhow!(
+                "the changed files define different symbols ({} added, {} removed)",
+                defined.difference(&replaced).count(),
+                replaced.difference(&defined).count()
+            ));
+        }
+        index.write(output)
+    }
 
-        // Try direct command
-        self.execute_indexer("scip-typescript", &args)
+    /// What the language's indexer output is cached under; None when
+    /// caching is off or nothing would run
+    fn cache_key(&self, lang_info: &LanguageInfo) -> Option<CacheKey> {
+        self.cache.as_ref()?;
+        let plan = self.plan_indexer(lang_info);
+        if plan.skipped.is_some() {
+            return None;
+        }
+        let language = lang_info.language;
+        let inputs = incremental::fingerprint(&self.codebase_path, &[language], &self.path_filter);
+        let work_dir = self.isolated_work_dir.as_ref().unwrap_or(&self.output_dir);
+        Some(CacheKey::new(
+            &plan,
+            &inputs[&language],
+            &self.absolute(&self.codebase_path),
+            &self.absolute(work_dir),
+        ))
     }
 
-    /// Run a simple indexer: try bundled path first, then fall back to PATH
-    fn run_simple_indexer(&self, binary: &str, args: &[&str]) -> Result<()> {
-        if let Some(bundled) = self.get_bundled_path(binary) {
-            return self.execute_indexer(bundled.to_str().unwrap(), args);
+    /// Why a language would not be indexed at all, logging the reason
+    fn skip_reason(&self, language: Language) -> Option<String> {
+        if !language.has_scip_indexer() {
+            info!("{} detected, no SCIP indexer available; skipping", language.display_name());
+            return Some("Detected, no SCIP indexer available".to_string());
+        }
+        if !self.is_indexer_available(language) {
+            warn!(
+                "Indexer for {:?} not available. Install with: {}",
+                language,
+                language.install_command()
+            );
+            return Some("Indexer not installed".to_string());
         }
-        self.execute_indexer(binary, args)
+        None
     }
 
-    /// Run scip-dotnet indexer (special: solution file discovery + multiple fallbacks)
-    fn run_dotnet_indexer(&self, output: &Path) -> Result<()> {
-        let solution_file = self.find_dotnet_solution();
+    /// Remove documents the include/exclude patterns leave out from each
+    /// produced index, for indexers that could not be told about them
+    fn drop_excluded_documents(&self, results: &[IndexerResult]) {
+        if self.path_filter.is_empty() {
+            return;
+        }
+        let mut done = HashSet::new();
+        for result in results.iter().filter(|r| r.success && !r.reused) {
+            if !done.insert(&result.scip_path) {
+                continue;
+            }
+            let filtered = scip::Index::read(&result.scip_path).and_then(|mut index| {
+                let dropped = index.retain_documents(|path| !self.path_filter.is_excluded(Path::new(path)));
+                if dropped > 0 {
+                    index.write(&result.scip_path)?;
+                }
+                Ok(dropped)
+            });
+            match filtered {
+                Ok(0) => {}
+                Ok(dropped) => info!("Dropped {} excluded documents from {:?}", dropped, result.scip_path),
+                Err(e) => warn!("Could not filter documents of {:?}: {:#}", result.scip_path, e),
+            }
+        }
+    }
 
-        let mut args = vec!["index"];
-        let solution_str;
-        if let Some(ref sln) = solution_file {
-            solution_str = sln.to_string_lossy().to_string();
-            args.push(&solution_str);
+    /// Post-process each produced index so symbols carry the package identity
+    /// of the manifest that owns them (or the configured one)
+    fn inject_package_identity(&self, languages: &[LanguageInfo], results: &[IndexerResult]) {
+        if self.package_identity == IdentityMode::Off {
+            return;
+        }
+        let manifests: Vec<_> = languages.iter().flat_map(|l| &l.manifests).collect();
+        let mut done = HashSet::new();
+        for result in results.iter().filter(|r| r.success && !r.reused) {
+            if !done.insert(&result.scip_path) {
+                continue;
+            }
+            let rewritten = scip::Index::read(&result.scip_path).and_then(|mut index| {
+                let count = identity::apply(
+                    &mut index,
+                    &self.codebase_path,
+                    &manifests,
+                    self.package_identity,
+                    self.package.as_ref(),
+                );
+                if count > 0 {
+                    index.write(&result.scip_path)?;
+                }
+                Ok(count)
+            });
+            match rewritten {
+                Ok(count) => debug!("Package identity: rewrote {} symbols in {:?}", count, result.scip_path),
+                Err(e) => warn!("Package identity injection skipped for {:?}: {:#}", result.scip_path, e),
+            }
         }
-        args.push("--output");
-        let output_str = output.to_str().unwrap();
-        args.push(output_str);
+    }
 
-        if let Some(bundled) = self.get_bundled_path("scip-dotnet") {
-            return self.execute_indexer(bundled.to_str().unwrap(), &args);
+    /// Run the indexer for a language, turning a launch error into a failed result
+    fn run_indexer_or_failure(&self, lang_info: &LanguageInfo) -> IndexerResult {
+        self.run_indexer(lang_info).unwrap_or_else(|e| IndexerResult {
+            language: lang_info.language,
+            scip_path: PathBuf::new(),
+            success: false,
+            error: Some(e.to_string()),
+            reused: false,
+            cached: false,
+            patched: false,
+            partial: false,
+        })
+    }
+
+    /// The tsconfig carrying the include/exclude patterns to scip-typescript
+    fn typescript_config(&self) -> Option<GeneratedTsconfig> {
+        tsconfig::generate(
+            &self.absolute(&self.codebase_path),
+            &self.absolute(&self.output_dir.join("typescript")),
+            &self.path_filter,
+        )
+    }
+
+    /// Run scip-typescript, first writing its generated tsconfig
+    fn run_typescript_indexer(&self, output: &Path) -> Result<()> {
+        let config = self.typescript_config();
+        if let Some(config) = &config {
+            config.write()?;
+            debug!("Generated tsconfig: {:?}", config.path());
         }
+        self.typescript_invocation(config.as_ref(), output).execute()
+    }
 
-        if which::which("scip-dotnet").is_ok() {
-            return self.execute_indexer("scip-dotnet", &args);
+    /// scip-typescript (special: monorepo detection + npx fallback), on the
+    /// generated tsconfig when there is one
+    fn typescript_invocation(&self, config: Option<&GeneratedTsconfig>, output: &Path) -> Invocation {
+        let mut args = vec!["index".to_string()];
+        if let Some(config) = config {
+            args.push(config.dir.to_string_lossy().into_owned());
+        }
+        args.extend(["--output".into(), output.to_string_lossy().into_owned()]);
+        if config.is_none() && !self.codebase_path.join("tsconfig.json").exists() {
+            debug!("No root tsconfig.json found, using --infer-tsconfig for monorepo support");
+            args.push("--infer-tsconfig".into());
         }
+        self.indexer_invocation_with(Language::TypeScript, args, output)
+    }
 
-        // Try global dotnet tools location
-        let home = std::env::var("HOME").unwrap_or_default();
-        let global_tool = PathBuf::from(&home).join(".dotnet/tools/scip-dotnet");
-        if global_tool.exists() {
-            return self.execute_indexer(global_tool.to_str().unwrap(), &args);
+    /// An invocation of a language's indexer as `resolve_indexer` finds it,
+    /// with any launcher arguments (npx, `dotnet tool run`) prepended
+    fn indexer_invocation_with(&self, language: Language, mut args: Vec<String>, output: &Path) -> Invocation {
+        let binary = language.scip_indexer().unwrap_or_default();
+        let (program, source) = self.resolve_indexer(language).unwrap_or_else(|| self.resolve_binary(binary));
+        let launcher: &[&str] = match source {
+            BinarySource::Npx => &["@sourcegraph/scip-typescript"],
+            BinarySource::DotnetToolRun => &["tool", "run", "scip-dotnet", "--"],
+            _ => &[],
+        };
+        args.splice(0..0, launcher.iter().map(|a| a.to_string()));
+        Invocation {
+            program,
+            source,
+            args,
+            working_dir: self.codebase_path.clone(),
+            env: BTreeMap::new(),
+            output: output.to_path_buf(),
         }
+    }
 
-        // Fallback to dotnet tool run (requires local manifest)
-        let mut cmd_args: Vec<&str> = vec!["tool", "run", "scip-dotnet", "--"];
-        for arg in &args {
-            cmd_args.push(arg);
+    /// The program that would run a language's indexer, and how it was
+    /// found: bundled, then PATH, then the language's launcher fallbacks
+    pub fn resolve_indexer(&self, language: Language) -> Option<(PathBuf, BinarySource)> {
+        let binary = language.scip_indexer()?;
+        let resolved = self.resolve_binary(binary);
+        if resolved.1 != BinarySource::Missing {
+            return Some(resolved);
         }
 
-        let status = Command::new("dotnet")
-            .current_dir(&self.codebase_path)
-            .args(&cmd_args)
-            .status()
-            .context("Failed to run dotnet scip-dotnet")?;
+        match language {
+            Language::TypeScript | Language::JavaScript => {
+                if let Ok(npx) = which::which("npx") {
+                    debug!("Using npx to run scip-typescript");
+                    return Some((npx, BinarySource::Npx));
+                }
+            }
+            _ if DOTNET_LANGUAGES.contains(&language) => {
+                // Global dotnet tools location
+                let home = std::env::var("HOME").unwrap_or_default();
+                let global_tool = PathBuf::from(&home).join(".dotnet/tools/scip-dotnet");
+                if global_tool.exists() {
+                    return Some((global_tool, BinarySource::DotnetGlobalTool));
+                }
+                // dotnet tool run (requires a local tool manifest)
+                if let Ok(dotnet) = which::which("dotnet") {
+                    return Some((dotnet, BinarySource::DotnetToolRun));
+                }
+            }
+            _ => {}
+        }
+        Some(resolved)
+    }
 
-        if status.success() {
-            Ok(())
+    /// Find a binary: the bundled indexers directory first, then PATH
+    fn resolve_binary(&self, binary: &str) -> (PathBuf, BinarySource) {
+        if let Some(bundled) = self.get_bundled_path(binary) {
+            (self.absolute(&bundled), BinarySource::Bundled)
+        } else if let Ok(found) = which::which(binary) {
+            (found, BinarySource::Path)
         } else {
-            Err(anyhow!("scip-dotnet exited with non-zero status"))
+            (PathBuf::from(binary), BinarySource::Missing)
+        }
+    }
+
+    /// An indexer run from `dir`: the bundled binary if present, else PATH
+    fn simple_invocation(&self, dir: &Path, binary: &str, args: Vec<String>, output: &Path) -> Invocation {
+        let (program, source) = self.resolve_binary(binary);
+        Invocation {
+            program,
+            source,
+            args,
+            working_dir: dir.to_path_buf(),
+            env: BTreeMap::new(),
+            output: output.to_path_buf(),
         }
     }
 
+    /// The environment scip-python should resolve packages against: that of
+    /// the outermost pyproject.toml, else any one found
+    fn python_environment<'a>(&self, lang_info: &'a LanguageInfo) -> Option<&'a pyenv::PythonEnvironment> {
+        python_project(lang_info)
+            .and_then(|m| m.python_environment.as_ref())
+            .or_else(|| lang_info.manifests.iter().find_map(|m| m.python_environment.as_ref()))
+    }
+
+    /// Run scip-python, first writing its environment file
+    fn run_python_indexer(&self, lang_info: &LanguageInfo, output: &Path) -> Result<()> {
+        let env_file = self.write_python_environment(lang_info);
+        self.python_invocation(lang_info, env_file.as_deref(), self.python_target().as_deref(), output).execute()
+    }
+
+    /// Write the Python environment for scip-python's `--environment`, if
+    /// one was found
+    fn write_python_environment(&self, lang_info: &LanguageInfo) -> Option<PathBuf> {
+        self.python_environment(lang_info).and_then(|env| {
+            let file = self.absolute(&self.output_dir.join("python-environment.json"));
+            match pyenv::write_environment_json(env, &file) {
+                Ok(count) => {
+                    info!("Using {:?} environment {:?} ({} packages)", env.kind, env.prefix, count);
+                    Some(file)
+                }
+                Err(e) => {
+                    warn!("Ignoring Python environment {:?}: {}", env.prefix, e);
+                    None
+                }
+            }
+        })
+    }
+
+    /// Include patterns under one directory: index only that directory
+    fn python_target(&self) -> Option<PathBuf> {
+        self.path_filter.include_root().filter(|d| self.codebase_path.join(d).is_dir())
+    }
+
+    /// scip-python (special: project identity + environment from manifests)
+    fn python_invocation(
+        &self,
+        lang_info: &LanguageInfo,
+        env_file: Option<&Path>,
+        target: Option<&Path>,
+        output: &Path,
+    ) -> Invocation {
+        let mut args: Vec<String> = vec!["index".into(), ".".into()];
+
+        // The project identity comes from the outermost pyproject.toml
+        let project = python_project(lang_info);
+        if let Some(name) = project.and_then(|m| m.name.as_ref()) {
+            args.extend(["--project-name".into(), name.clone()]);
+        }
+        if let Some(version) = project.and_then(|m| m.version.as_ref()) {
+            args.extend(["--project-version".into(), version.clone()]);
+        }
+        if let Some(file) = env_file {
+            args.extend(["--environment".into(), file.to_string_lossy().into_owned()]);
+        }
+        if let Some(target) = target {
+            args.extend(["--target-only".into(), target.to_string_lossy().into_owned()]);
+        }
+
+        args.extend(["--output".into(), output.to_string_lossy().into_owned()]);
+        self.simple_invocation(&self.codebase_path, "scip-python", args, output)
+    }
+
+    /// scip-go (special: once per go.work workspace and per module outside
+    /// a workspace, merged into one index)
+    fn go_invocations(&self, lang_info: &LanguageInfo, output: &Path) -> Vec<Invocation> {
+        let mut units = go_units(lang_info);
+        if units.is_empty() {
+            // No go.mod at all (GOPATH layout): let scip-go try the root
+            units.push(self.codebase_path.clone());
+        }
+        self.unit_outputs(&units, output)
+            .into_iter()
+            .map(|(dir, part)| {
+                let args = vec!["--output".into(), part.to_string_lossy().into_owned()];
+                self.simple_invocation(&dir, "scip-go", args, &part)
+            })
+            .collect()
+    }
+
+    /// rust-analyzer (special: once per Cargo workspace root, offline when
+    /// the workspace vendors its dependencies)
+    fn rust_invocations(&self, lang_info: &LanguageInfo, output: &Path) -> Vec<Invocation> {
+        let mut units = rust_workspace_roots(lang_info);
+        if units.is_empty() {
+            units.push(self.codebase_path.clone());
+        }
+        self.unit_outputs(&units, output)
+            .into_iter()
+            .map(|(dir, part)| {
+                let args = vec!["scip".into(), ".".into(), "--output".into(), part.to_string_lossy().into_owned()];
+                let mut inv = self.simple_invocation(&dir, "rust-analyzer", args, &part);
+                if self.uses_vendored_sources(&dir) {
+                    inv.env.insert("CARGO_NET_OFFLINE".into(), "true".into());
+                }
+                inv
+            })
+            .collect()
+    }
+
+    /// Whether cargo is configured (via `.cargo/config.toml` between `dir` and
+    /// the codebase root) to replace crates.io with a local directory source
+    fn uses_vendored_sources(&self, dir: &Path) -> bool {
+        dir.ancestors()
+            .take_while(|d| d.starts_with(&self.codebase_path))
+            .flat_map(|d| [d.join(".cargo/config.toml"), d.join(".cargo/config")])
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .filter_map(|c| c.parse::<toml::Table>().ok())
+            .any(|config| {
+                config
+                    .get("source")
+                    .and_then(toml::Value::as_table)
+                    .is_some_and(|sources| sources.values().any(|s| s.get("directory").is_some()))
+            })
+    }
+
+    /// Where each unit directory's index is written: straight to `output`
+    /// when the only unit is the codebase root, else a part file to merge
+    fn unit_outputs(&self, units: &[PathBuf], output: &Path) -> Vec<(PathBuf, PathBuf)> {
+        if let [only] = units {
+            if only == &self.codebase_path {
+                return vec![(only.clone(), output.to_path_buf())];
+            }
+        }
+        let work_dir = self.absolute(&self.output_dir);
+        units
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| (dir.clone(), work_dir.join(format!("unit-{}.scip.part", i))))
+            .collect()
+    }
+
+    /// Run per-unit invocations and merge their indexes into `output`, with
+    /// document paths relative to the codebase root. `check` runs before
+    /// each invocation; its error fails that unit. Returns the units that
+    /// failed, if others succeeded.
+    fn run_per_unit(
+        &self,
+        invocations: &[Invocation],
+        output: &Path,
+        check: impl Fn(&Invocation) -> Result<()>,
+    ) -> Result<Vec<String>> {
+        if let [only] = invocations {
+            if only.output == output {
+                check(only)?;
+                return only.execute().map(|()| Vec::new());
+            }
+        }
+
+        let mut merged: Option<scip::Index> = None;
+        let mut failed = Vec::new();
+        for inv in invocations {
+            let dir = &inv.working_dir;
+            let rel = dir.strip_prefix(&self.codebase_path).unwrap_or(dir);
+            let rel = rel.to_string_lossy().replace('\\', "/");
+            info!("Indexing {}", if rel.is_empty() { "." } else { &rel });
+
+            let result = check(inv)
+                .and_then(|()| inv.execute())
+                .and_then(|()| scip::Index::read(&inv.output));
+            let _ = std::fs::remove_file(&inv.output);
+            match result {
+                Ok(mut index) => {
+                    index.prefix_paths(&rel);
+                    match merged.as_mut() {
+                        Some(all) => all.merge(index),
+                        None => merged = Some(index),
+                    }
+                }
+                Err(e) => {
+                    warn!("Indexing {:?} failed: {:#}", dir, e);
+                    failed.push(format!("{}: {:#}", if rel.is_empty() { "." } else { &rel }, e));
+                }
+            }
+        }
+
+        let mut merged = merged.ok_or_else(|| anyhow!("{}", failed.join("; ")))?;
+        if !failed.is_empty() {
+            warn!("Index is missing {} of {} units: {}", failed.len(), invocations.len(), failed.join("; "));
+        }
+        merged.set_project_root(&scip::file_uri(&self.codebase_path));
+        merged.write(output)?;
+        Ok(failed)
+    }
+
+    /// scip-dotnet (special: solution file discovery + multiple fallbacks)
+    fn dotnet_invocation(&self, output: &Path) -> Invocation {
+        let mut args = vec!["index".to_string()];
+        if let Some(sln) = self.find_dotnet_solution() {
+            args.push(sln.to_string_lossy().into_owned());
+        }
+        args.extend(["--output".into(), output.to_string_lossy().into_owned()]);
+        self.indexer_invocation_with(Language::CSharp, args, output)
+    }
+
+    /// Run scip-zig and move its index into place
+    fn run_zig_indexer(&self, output: &Path) -> Result<()> {
+        let inv = self.zig_invocation()?;
+        inv.execute()?;
+        std::fs::rename(&inv.output, output)
+            .with_context(|| format!("scip-zig did not produce {:?}", inv.output))
+    }
+
+    /// scip-zig (special: needs a root source file, writes index.scip to the cwd)
+    fn zig_invocation(&self) -> Result<Invocation> {
+        let entry = zig_entry_point(&self.codebase_path).ok_or_else(|| {
+            anyhow!(
+                "No Zig entry point found in {}: build.zig names no existing root_source_file \
+                 and none of {} exist",
+                self.codebase_path.display(),
+                ZIG_ENTRY_POINTS.join(", ")
+            )
+        })?;
+        let package = self
+            .codebase_path
+            .canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "root".to_string());
+
+        // scip-zig runs from the codebase, so relative paths would resolve twice
+        let args = vec![
+            "--root-path".into(),
+            self.absolute(&self.codebase_path).to_string_lossy().into_owned(),
+            "--pkg".into(),
+            package,
+            self.absolute(&entry).to_string_lossy().into_owned(),
+        ];
+        let produced = self.absolute(&self.codebase_path.join("index.scip"));
+        Ok(self.simple_invocation(&self.codebase_path, "scip-zig", args, &produced))
+    }
+
+    /// Run scip-clang once its compilation database is in place
+    fn run_clang_indexer(&self, output: &Path) -> Result<()> {
+        let db = compdb::resolve(
+            &self.codebase_path,
+            &self.absolute(&self.output_dir.join("compdb")),
+            &self.path_filter,
+        )?;
+        debug!("Compilation database ({:?}): {:?}", db.source, db.path);
+        self.clang_invocation(&db.path, output).execute()
+    }
+
+    /// scip-clang (special: needs a compilation database)
+    fn clang_invocation(&self, compdb_path: &Path, output: &Path) -> Invocation {
+        let args = vec![
+            format!("--compdb-path={}", compdb_path.display()),
+            format!("--index-output-path={}", output.display()),
+        ];
+        self.simple_invocation(&self.codebase_path, "scip-clang", args, output)
+    }
+
     /// Find a file with the given extension in a directory
     fn find_file_with_ext(&self, dir: &Path, ext: &str) -> Option<PathBuf> {
         std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
@@ -277,8 +1207,10 @@ impl IndexerOrchestrator {
             }
         }
 
-        // Check for .csproj in root
-        self.find_file_with_ext(&self.codebase_path, "csproj")
+        // Check for a project file in root (C#, then VB.NET, then F#)
+        ["csproj", "vbproj", "fsproj"]
+            .iter()
+            .find_map(|ext| self.find_file_with_ext(&self.codebase_path, ext))
     }
 
     /// Get path to bundled indexer if it exists
@@ -289,21 +1221,9 @@ impl IndexerOrchestrator {
         })
     }
 
-    /// Execute an indexer binary
-    fn execute_indexer(&self, binary: &str, args: &[&str]) -> Result<()> {
-        debug!("Executing: {} {:?}", binary, args);
-
-        let status = Command::new(binary)
-            .current_dir(&self.codebase_path)
-            .args(args)
-            .status()
-            .with_context(|| format!("Failed to run {}", binary))?;
-
-        if status.success() {
-            Ok(())
-        } else {
-            Err(anyhow!("{} exited with status: {:?}", binary, status.code()))
-        }
+    /// Absolute form of a path, so it survives indexers changing directory
+    fn absolute(&self, path: &Path) -> PathBuf {
+        std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
     }
 
     /// Get the output path for a given language's SCIP file
@@ -311,42 +1231,426 @@ impl IndexerOrchestrator {
         self.output_dir.join(format!("{}.scip", language.scip_output_stem()))
     }
 
+    /// Where a language's indexer writes before its index is validated; it
+    /// keeps the `.scip` extension, which scip-java reads the format from
+    fn staged_output_path(&self, language: Language) -> PathBuf {
+        self.output_dir.join(format!("{}{}", language.scip_output_stem(), STAGED_SUFFIX))
+    }
+
+    /// Check that a staged index decodes, then move it over the previous
+    /// one. With `reroot` (an index built in the scratch copy or restored
+    /// from the cache) its project root is set to the codebase.
+    fn promote(&self, staged: &Path, output: &Path, reroot: bool) -> Result<()> {
+        let mut index = scip::Index::read(staged).context("Indexer output is not a valid SCIP index")?;
+        if reroot {
+            index.set_project_root(&scip::file_uri(&self.codebase_path));
+            index.write(output)?;
+            let _ = std::fs::remove_file(staged);
+            return Ok(());
+        }
+        std::fs::rename(staged, output).with_context(|| format!("Failed to move {:?} to {:?}", staged, output))
+    }
+
+    /// Where a language's indexer runs when the codebase must stay untouched:
+    /// a copy named like the codebase, so tools deriving names from the
+    /// directory see the same one
+    fn scratch_tree(&self, language: Language) -> Option<PathBuf> {
+        let work_dir = self.isolated_work_dir.as_ref().filter(|_| writes_into_tree(language))?;
+        let name = self
+            .codebase_path
+            .canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_os_string()))
+            .unwrap_or_else(|| "root".into());
+        Some(work_dir.join(scratch::TREE_DIR).join(name))
+    }
+
+    /// Bring the scratch copy up to date, once per run
+    fn sync_scratch(&self, tree: &Path) -> Result<()> {
+        self.scratch_synced
+            .get_or_init(|| {
+                info!("Syncing a copy of the codebase to {:?}", tree);
+                scratch::sync(&self.codebase_path, tree, &self.path_filter).map(drop).map_err(|e| format!("{:#}", e))
+            })
+            .clone()
+            .map_err(|e| anyhow!("Cannot copy the codebase to {:?}: {}", tree, e))
+    }
+
+    /// This orchestrator's settings applied to the scratch copy at `tree`
+    fn in_tree(&self, tree: &Path) -> Self {
+        Self {
+            package_identity: self.package_identity,
+            package: self.package.clone(),
+            path_filter: self.path_filter.clone(),
+            ..Self::for_plan_in(tree.to_path_buf(), self.indexers_path.clone(), self.output_dir.clone())
+        }
+    }
+
+    /// Remove `.scip` files of languages this run did not index, and outputs
+    /// of interrupted runs; a failed language keeps its previous index
+    fn remove_stale_indexes(&self, results: &[IndexerResult]) {
+        let current: HashSet<&PathBuf> = results.iter().map(|r| &r.scip_path).collect();
+        let Ok(entries) = std::fs::read_dir(&self.output_dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().is_some_and(|ext| ext == "scip") && path.is_file() && !current.contains(&path) {
+                debug!("Removing stale file: {:?}", path);
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("Failed to remove stale file {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
     /// Get the output directory for SCIP files
     pub fn output_dir(&self) -> &Path {
         &self.output_dir
     }
 
-    /// Clean up generated SCIP files
+    /// Clean up generated SCIP files. An isolated work directory is kept:
+    /// its scratch copy and build outputs make the next run faster. While a
+    /// run lock is held, its file is left for the lock to remove on release.
     pub fn cleanup(&self) -> Result<()> {
-        if self.output_dir.exists() {
-            std::fs::remove_dir_all(&self.output_dir)?;
+        if self.isolated_work_dir.is_some() || !self.output_dir.exists() {
+            return Ok(());
+        }
+        let mut locked = false;
+        for entry in std::fs::read_dir(&self.output_dir)?.flatten() {
+            if entry.file_name() == outdir::LOCK_FILE {
+                locked = true;
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        if !locked {
+            std::fs::remove_dir(&self.output_dir)?;
         }
         Ok(())
     }
 }
 
-/// Check which indexers are available on the system
-pub fn check_available_indexers() -> HashMap<Language, bool> {
-    let temp_orchestrator = IndexerOrchestrator {
-        indexers_path: None,
-        codebase_path: PathBuf::from("."),
-        output_dir: PathBuf::from("."),
+/// Indexers that build the project or otherwise write into the codebase
+fn writes_into_tree(language: Language) -> bool {
+    matches!(
+        language,
+        Language::Java
+            | Language::Kotlin
+            | Language::Scala
+            | Language::CSharp
+            | Language::VisualBasic
+            | Language::FSharp
+            | Language::Rust
+            | Language::Zig
+    )
+}
+
+/// `lang_info` with its config files and manifests moved from `from` to `to`
+fn rebase(lang_info: &LanguageInfo, from: &Path, to: &Path) -> LanguageInfo {
+    let moved = |path: &Path| path.strip_prefix(from).map_or_else(|_| path.to_path_buf(), |rel| to.join(rel));
+    let mut info = lang_info.clone();
+    for path in &mut info.config_files {
+        *path = moved(path);
+    }
+    for manifest in &mut info.manifests {
+        manifest.path = moved(&manifest.path);
+    }
+    info
+}
+
+/// Symbols that documents define for use elsewhere (local symbols excluded)
+fn global_definitions<'a>(documents: impl IntoIterator<Item = &'a scip::Document>) -> BTreeSet<String> {
+    documents
+        .into_iter()
+        .flat_map(scip::Document::defined_symbols)
+        .filter(|symbol| !symbol.starts_with("local "))
+        .collect()
+}
+
+/// The deepest path containing every one of `paths` (relative); a single
+/// file is its own ancestor, and paths sharing nothing give the root (`.`)
+fn common_ancestor(paths: &[PathBuf]) -> PathBuf {
+    let mut common: Vec<_> = paths.first().map(|p| p.components().collect()).unwrap_or_default();
+    for path in &paths[1.min(paths.len())..] {
+        let shared = common.iter().zip(path.components()).take_while(|(a, b)| **a == *b).count();
+        common.truncate(shared);
+    }
+    let ancestor: PathBuf = common.into_iter().collect();
+    if ancestor.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        ancestor
+    }
+}
+
+/// The requested .NET language that runs scip-dotnet for all of them
+fn dotnet_primary(languages: &[LanguageInfo]) -> Option<&LanguageInfo> {
+    languages
+        .iter()
+        .filter(|l| DOTNET_LANGUAGES.contains(&l.language))
+        .min_by_key(|l| l.language)
+}
+
+/// The outermost pyproject.toml, which names the Python project
+fn python_project(lang_info: &LanguageInfo) -> Option<&ProjectManifest> {
+    lang_info
+        .manifests
+        .iter()
+        .filter(|m| m.kind == ManifestKind::Pyproject)
+        .min_by_key(|m| m.path.components().count())
+}
+
+/// Directories to run scip-go in: each go.work workspace, plus each go.mod
+/// module not already `use`d by one of them. `testdata` trees are skipped,
+/// as the go tool ignores them.
+fn go_units(lang_info: &LanguageInfo) -> Vec<PathBuf> {
+    let in_testdata = |p: &Path| p.components().any(|c| c.as_os_str() == "testdata");
+    let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+
+    let workspaces: Vec<_> = lang_info
+        .manifests
+        .iter()
+        .filter(|m| m.kind == ManifestKind::GoWork && !in_testdata(&m.path))
+        .collect();
+    let covered: HashSet<PathBuf> = workspaces
+        .iter()
+        .flat_map(|w| w.workspace_members.iter().map(|u| canonical(&w.project_root().join(u))))
+        .collect();
+
+    let mut units: Vec<PathBuf> = workspaces.iter().map(|w| w.project_root().to_path_buf()).collect();
+    units.extend(
+        lang_info
+            .config_files
+            .iter()
+            .filter(|p| p.file_name().is_some_and(|n| n == "go.mod") && !in_testdata(p))
+            .filter_map(|p| p.parent())
+            .filter(|dir| !covered.contains(&canonical(dir)))
+            .map(Path::to_path_buf),
+    );
+    units.sort();
+    units.dedup();
+    units
+}
+
+/// Cargo workspace roots to run rust-analyzer in: every `Cargo.toml` with a
+/// `[workspace]` table, plus packages not inside one. Vendored crates (which
+/// carry a `.cargo-checksum.json`) are not projects of their own.
+fn rust_workspace_roots(lang_info: &LanguageInfo) -> Vec<PathBuf> {
+    let manifests: Vec<&Path> = lang_info
+        .config_files
+        .iter()
+        .filter(|p| p.file_name().is_some_and(|n| n == "Cargo.toml"))
+        .filter(|p| !p.with_file_name(".cargo-checksum.json").exists())
+        .filter_map(|p| p.parent())
+        .collect();
+    let is_workspace = |dir: &Path| {
+        std::fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|c| c.parse::<toml::Table>().ok())
+            .is_some_and(|t| t.contains_key("workspace"))
     };
 
+    let workspaces: Vec<&Path> = manifests.iter().copied().filter(|d| is_workspace(d)).collect();
+    let mut roots: Vec<PathBuf> = manifests
+        .iter()
+        .filter(|dir| workspaces.contains(dir) || !workspaces.iter().any(|w| dir.starts_with(w)))
+        .map(|dir| dir.to_path_buf())
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Run `cargo metadata` the way rust-analyzer will, so a broken manifest,
+/// missing toolchain or unreachable registry is reported with cargo's own
+/// message instead of an indexer exit status
+fn cargo_metadata_check(dir: &Path, offline: bool) -> Result<()> {
+    let cargo = which::which("cargo")
+        .map_err(|_| anyhow!("cargo not found in PATH; rust-analyzer needs it to load the workspace"))?;
+    let mut cmd = Command::new(cargo);
+    cmd.current_dir(dir).args(["metadata", "--format-version", "1"]);
+    if offline {
+        cmd.arg("--offline");
+    }
+    let out = cmd
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to run cargo metadata")?;
+    if out.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let message: Vec<&str> = stderr.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    Err(anyhow!("cargo metadata failed: {}", message.join(" ")))
+}
+
+/// Check which indexers are available on the system
+pub fn check_available_indexers(indexers_path: Option<PathBuf>) -> HashMap<Language, bool> {
+    let temp_orchestrator = IndexerOrchestrator::for_plan(PathBuf::from("."), indexers_path);
+
     Language::ALL
         .iter()
         .map(|&lang| (lang, temp_orchestrator.is_indexer_available(lang)))
         .collect()
 }
 
+/// Conventional Zig root source files, relative to the codebase
+const ZIG_ENTRY_POINTS: &[&str] = &["src/main.zig", "src/root.zig", "main.zig"];
+
+/// The root source file of a Zig codebase: the first existing
+/// `root_source_file` named in `build.zig`, else a conventional one
+fn zig_entry_point(root: &Path) -> Option<PathBuf> {
+    let declared = std::fs::read_to_string(root.join("build.zig"))
+        .unwrap_or_default()
+        .split("root_source_file")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').nth(1).map(str::to_string))
+        .collect::<Vec<_>>();
+    declared
+        .iter()
+        .map(String::as_str)
+        .chain(ZIG_ENTRY_POINTS.iter().copied())
+        .map(|p| root.join(p))
+        .find(|p| p.extension().is_some_and(|e| e == "zig") && p.is_file())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_language_indexer_metadata() {
-        assert_eq!(Language::TypeScript.scip_indexer(), "scip-typescript");
+        assert_eq!(Language::TypeScript.scip_indexer(), Some("scip-typescript"));
         assert!(Language::TypeScript.is_bundled());
         assert!(!Language::Ruby.is_bundled());
+        assert_eq!(Language::Swift.scip_indexer(), None);
+    }
+
+    #[test]
+    fn test_language_without_indexer_reported_cleanly() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+        let info = LanguageInfo {
+            language: Language::Elixir,
+            file_count: 3,
+            config_files: Vec::new(),
+            manifests: Vec::new(),
+        };
+
+        assert!(!orch.is_indexer_available(Language::Elixir));
+        let results = orch.run_all(&[info]);
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].success);
+        assert_eq!(results[0].error.as_deref(), Some("Detected, no SCIP indexer available"));
+    }
+
+    #[test]
+    fn test_go_units_workspaces_and_modules() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write = |rel: &str, content: &str| {
+            let path = temp.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("platform/go.work", "go 1.22\n\nuse (\n\t./api\n\t./worker\n)\n");
+        write("platform/api/go.mod", "module acme/api\n");
+        write("platform/api/main.go", "package main");
+        write("platform/worker/go.mod", "module acme/worker\n");
+        write("platform/worker/main.go", "package main");
+        write("tools/go.mod", "module acme/tools\n");
+        write("tools/main.go", "package main");
+        write("tools/testdata/fixture/go.mod", "module fixture\n");
+
+        let report = crate::detect::LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let go = report.languages.iter().find(|l| l.language == Language::Go).unwrap();
+
+        assert_eq!(go_units(go), vec![temp.path().join("platform"), temp.path().join("tools")]);
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let paths = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(common_ancestor(&paths(&["pkg/a.py"])), PathBuf::from("pkg/a.py"));
+        assert_eq!(common_ancestor(&paths(&["pkg/sub/a.py", "pkg/b.py"])), PathBuf::from("pkg"));
+        assert_eq!(common_ancestor(&paths(&["a.py", "pkg/b.py"])), PathBuf::from("."));
+    }
+
+    #[test]
+    fn test_rust_workspace_roots() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write = |rel: &str, content: &str| {
+            let path = temp.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("engine/Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n");
+        write("engine/crates/core/Cargo.toml", "[package]\nname = \"core\"\n");
+        write("engine/crates/core/src/lib.rs", "");
+        write("engine/vendor/serde/Cargo.toml", "[package]\nname = \"serde\"\n");
+        write("engine/vendor/serde/.cargo-checksum.json", "{}");
+        write("tools/cli/Cargo.toml", "[package]\nname = \"cli\"\n");
+        write("tools/cli/src/main.rs", "fn main() {}");
+
+        let report = crate::detect::LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let rust = report.languages.iter().find(|l| l.language == Language::Rust).unwrap();
+
+        assert_eq!(
+            rust_workspace_roots(rust),
+            vec![temp.path().join("engine"), temp.path().join("tools/cli")]
+        );
+    }
+
+    #[test]
+    fn test_vendored_sources_detected_from_cargo_config() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = temp.path().join(".cargo/config.toml");
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(
+            &config,
+            "[source.crates-io]\nreplace-with = \"vendored-sources\"\n\n[source.vendored-sources]\ndirectory = \"vendor\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(temp.path().join("app")).unwrap();
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+
+        assert!(orch.uses_vendored_sources(&temp.path().join("app")));
+        std::fs::remove_file(&config).unwrap();
+        assert!(!orch.uses_vendored_sources(&temp.path().join("app")));
+    }
+
+    #[test]
+    fn test_zig_entry_point_from_build_zig() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write = |rel: &str, content: &str| {
+            let path = temp.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        assert_eq!(zig_entry_point(temp.path()), None);
+        write("main.zig", "pub fn main() void {}");
+        assert_eq!(zig_entry_point(temp.path()), Some(temp.path().join("main.zig")));
+
+        write("app/cli.zig", "pub fn main() void {}");
+        write(
+            "build.zig",
+            "const exe = b.addExecutable(.{\n    .name = \"app\",\n    .root_source_file = b.path(\"app/cli.zig\"),\n});\n",
+        );
+        assert_eq!(zig_entry_point(temp.path()), Some(temp.path().join("app/cli.zig")));
+    }
+
+    #[test]
+    fn test_dotnet_project_fallback_without_solution() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp.path().join("Lib.fsproj"), "<Project />").unwrap();
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+
+        assert_eq!(orch.find_dotnet_solution(), Some(temp.path().join("Lib.fsproj")));
     }
 }
@@ -0,0 +1,233 @@
+//! Output directory safety: atomic writes and the run lock
+//!
+//! Readers of `.legend-indexer/` and `--output` (IDE plugins, the Legend
+//! ingester, a concurrent `watch`) must never see half a file, so outputs are
+//! written next to their destination and renamed over it. Two runs on one
+//! directory would still interleave their files; each run holds an advisory
+//! lock on the directories it writes, and a second run waits for it or fails
+//! with a message naming the holder.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Lock file, inside each locked directory
+pub const LOCK_FILE: &str = ".legend-indexer.lock";
+
+/// How often a waiting run retries the lock
+const LOCK_POLL: Duration = Duration::from_millis(200);
+
+/// The temporary sibling `path` is written to before being renamed over it
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.partial", name, std::process::id()))
+}
+
+/// Write `contents` to `path` so readers see either the old file or the new
+/// one, never a partial write
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let partial = partial_path(path);
+    std::fs::write(&partial, contents).with_context(|| format!("Failed to write {:?}", partial))?;
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to move {:?} to {:?}", partial, path))
+}
+
+/// Copy `from` to `to` atomically (see [`write_atomic`])
+pub fn copy_atomic(from: &Path, to: &Path) -> Result<()> {
+    let partial = partial_path(to);
+    std::fs::copy(from, &partial).with_context(|| format!("Failed to copy {:?} to {:?}", from, partial))?;
+    std::fs::rename(&partial, to).with_context(|| format!("Failed to move {:?} to {:?}", partial, to))
+}
+
+/// Another run holds the lock
+#[derive(Debug)]
+pub struct LockBusy {
+    pub dir: PathBuf,
+    /// Process id the holder recorded, if readable
+    pub holder: Option<String>,
+}
+
+impl fmt::Display for LockBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Another legend-indexer run")?;
+        if let Some(pid) = &self.holder {
+            write!(f, " (pid {})", pid)?;
+        }
+        write!(
+            f,
+            " is using {}; wait for it to finish or pass --lock-timeout <SECONDS>",
+            self.dir.display()
+        )
+    }
+}
+
+impl std::error::Error for LockBusy {}
+
+/// An advisory lock on a directory, held until dropped. The lock file is
+/// removed on release.
+#[derive(Debug)]
+pub struct RunLock {
+    file: File,
+    dir: PathBuf,
+    remove_empty_dir: bool,
+}
+
+impl RunLock {
+    /// Lock `dir` (created if needed), waiting up to `timeout` for another
+    /// run holding it; fails with [`LockBusy`] after that
+    pub fn acquire(dir: &Path, timeout: Duration) -> Result<Self> {
+        let path = dir.join(LOCK_FILE);
+        let deadline = Instant::now() + timeout;
+        let mut waiting = false;
+        loop {
+            // The holder may remove the directory while others wait
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+            let opened = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&path);
+            let mut file = match opened {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Failed to open lock file {:?}", path)),
+            };
+            match file.try_lock() {
+                // The holder may have removed the directory before releasing
+                // the lock; this file is then no longer the lock file
+                Ok(()) if !is_same_file(&file, &path) => continue,
+                Ok(()) => {
+                    let _ = file.set_len(0).and_then(|()| write!(file, "{}", std::process::id()));
+                    return Ok(Self::new(file, dir));
+                }
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => {
+                    warn!("Cannot lock {:?} ({}); continuing without a lock", dir, e);
+                    return Ok(Self::new(file, dir));
+                }
+            }
+
+            if Instant::now() >= deadline {
+                let holder = std::fs::read_to_string(&path)
+                    .ok()
+                    .map(|pid| pid.trim().to_string())
+                    .filter(|pid| !pid.is_empty());
+                return Err(LockBusy {
+                    dir: dir.to_path_buf(),
+                    holder,
+                }
+                .into());
+            }
+            if !waiting {
+                info!("Waiting for another legend-indexer run to release {}", dir.display());
+                waiting = true;
+            }
+            std::thread::sleep(LOCK_POLL);
+        }
+    }
+
+    fn new(file: File, dir: &Path) -> Self {
+        Self {
+            file,
+            dir: dir.to_path_buf(),
+            remove_empty_dir: false,
+        }
+    }
+
+    /// Also remove the directory on release if nothing else is left in it
+    pub fn removing_empty_dir(mut self) -> Self {
+        self.remove_empty_dir = true;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Still locked here; a waiter that opened this file finds it is no
+        // longer the lock file and opens a new one
+        let path = self.dir.join(LOCK_FILE);
+        if is_same_file(&self.file, &path) {
+            let _ = std::fs::remove_file(&path);
+        }
+        if self.remove_empty_dir {
+            let _ = std::fs::remove_dir(&self.dir);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("report.json");
+        std::fs::write(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        copy_atomic(&path, &temp.path().join("copy.json")).unwrap();
+        let names: Vec<_> = std::fs::read_dir(temp.path()).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(names.len(), 2, "no partial files left: {:?}", names);
+    }
+
+    #[test]
+    fn test_second_lock_fails_until_released() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().join("out");
+        let lock = RunLock::acquire(&dir, Duration::ZERO).unwrap();
+        let err = RunLock::acquire(&dir, Duration::from_millis(300)).unwrap_err();
+        let busy = err.downcast_ref::<LockBusy>().expect("lock is busy");
+        assert_eq!(busy.holder, Some(std::process::id().to_string()));
+        assert!(err.to_string().contains("--lock-timeout"), "{}", err);
+        drop(lock);
+        assert!(RunLock::acquire(&dir, Duration::ZERO).is_ok());
+
+        // A holder that removes the directory does not leave waiters with a stale lock
+        let lock = RunLock::acquire(&dir, Duration::ZERO).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        drop(lock);
+        let relocked = RunLock::acquire(&dir, Duration::ZERO).unwrap();
+        assert!(relocked.dir().join(LOCK_FILE).is_file());
+        drop(relocked);
+        assert!(!dir.join(LOCK_FILE).exists(), "the lock file is removed on release");
+        RunLock::acquire(&dir, Duration::ZERO).unwrap().removing_empty_dir();
+        assert!(!dir.exists(), "the emptied directory is removed");
+    }
+
+    #[test]
+    fn test_waiter_locks_a_directory_removed_by_the_holder() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().join(".legend-indexer");
+        let lock = RunLock::acquire(&dir, Duration::ZERO).unwrap().removing_empty_dir();
+        let waiter = {
+            let dir = dir.clone();
+            std::thread::spawn(move || RunLock::acquire(&dir, Duration::from_secs(10)).map(drop))
+        };
+        std::thread::sleep(Duration::from_millis(300));
+        drop(lock);
+        waiter.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! Machine-readable command output
+//!
+//! With `--format json` every subcommand prints exactly one JSON document on
+//! stdout. Documents share an envelope: `schema_version` (bumped on any
+//! incompatible change), the `command` that produced it, the tool `version`,
+//! the command's own fields, and `warnings` logged during the run. Logs stay
+//! on stderr in both formats.
+
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Version of the JSON document layout
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON document on stdout
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown output format '{}' (expected json or text)", other)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// The fields every JSON document carries around a command's payload
+#[derive(Debug, Serialize)]
+pub struct Envelope<T> {
+    pub schema_version: u32,
+    pub command: &'static str,
+    pub version: &'static str,
+    #[serde(flatten)]
+    pub payload: T,
+    pub warnings: Vec<String>,
+}
+
+impl<T: Serialize> Envelope<T> {
+    /// Wrap `payload`, taking the warnings logged so far
+    pub fn new(command: &'static str, payload: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            command,
+            version: env!("CARGO_PKG_VERSION"),
+            payload,
+            warnings: take_warnings(),
+        }
+    }
+
+    /// Serialize and print to stdout
+    pub fn print(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        println!("{}", json);
+        Ok(())
+    }
+}
+
+/// Payload of a command that stopped on an error instead of producing its
+/// result
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    /// Always `error`
+    pub status: &'static str,
+    pub exit_code: u8,
+    pub message: String,
+}
+
+impl ErrorReport {
+    pub fn new(exit_code: u8, message: impl Into<String>) -> Self {
+        Self {
+            status: "error",
+            exit_code,
+            message: message.into(),
+        }
+    }
+}
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Record a warning for the JSON document without logging it
+pub fn push_warning(message: impl Into<String>) {
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(message.into());
+}
+
+/// Warnings recorded since the last call
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Tracing layer that records warning and error messages for the JSON
+/// document's `warnings`
+pub struct WarningCollector;
+
+impl<S: Subscriber> Layer<S> for WarningCollector {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() > Level::WARN {
+            return;
+        }
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        push_warning(message.0);
+    }
+}
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Include and exclude patterns for the files that get analyzed
+//!
+//! Paths are relative to the codebase root. With include patterns, only
+//! paths matching one of them (or lying under a matching directory) are
+//! considered. Exclude patterns are then applied in order and the last one
+//! that matches decides: a plain pattern excludes, a `!pattern` puts the path
+//! back, so `build/**` followed by `!build/generated-sources/**` keeps the
+//! generated sources while dropping the rest of `build/`.
+
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// One exclude rule
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: glob::Pattern,
+    /// `!pattern`: re-include what earlier rules excluded
+    negated: bool,
+}
+
+/// The effective include/exclude patterns
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    includes: Vec<glob::Pattern>,
+    excludes: Vec<Rule>,
+}
+
+impl PathFilter {
+    /// Compile the patterns; invalid ones are logged and ignored
+    pub fn new(include_patterns: &[String], exclude_patterns: &[String]) -> Self {
+        let includes = include_patterns.iter().filter_map(|p| compile(p)).collect();
+        let excludes = exclude_patterns
+            .iter()
+            .filter_map(|p| match p.strip_prefix('!') {
+                Some(negated) => compile(negated).map(|pattern| Rule { pattern, negated: true }),
+                None => compile(p).map(|pattern| Rule { pattern, negated: false }),
+            })
+            .collect();
+        Self { includes, excludes }
+    }
+
+    /// No patterns at all: nothing is excluded
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    /// Whether there are include patterns restricting analysis to sub-trees
+    pub fn has_includes(&self) -> bool {
+        !self.includes.is_empty()
+    }
+
+    /// The include patterns, as given
+    pub fn include_patterns(&self) -> Vec<&str> {
+        self.includes.iter().map(glob::Pattern::as_str).collect()
+    }
+
+    /// Exclude patterns an indexer can apply on its own: those no later
+    /// `!pattern` can override. The rest are left to document filtering.
+    pub fn forwardable_excludes(&self) -> Vec<&str> {
+        let last_negation = self.excludes.iter().rposition(|rule| rule.negated);
+        self.excludes
+            .iter()
+            .enumerate()
+            .filter(|&(i, rule)| !rule.negated && last_negation.is_none_or(|n| i > n))
+            .map(|(_, rule)| rule.pattern.as_str())
+            .collect()
+    }
+
+    /// The deepest directory holding everything the include patterns select:
+    /// their common literal prefix, up to the first wildcard
+    pub fn include_root(&self) -> Option<PathBuf> {
+        let mut prefixes = self.includes.iter().map(|p| {
+            p.as_str()
+                .split('/')
+                .take_while(|c| !c.contains(['*', '?', '[']))
+                .collect::<Vec<_>>()
+        });
+        let mut common = prefixes.next()?;
+        for prefix in prefixes {
+            let shared = common.iter().zip(&prefix).take_while(|(a, b)| a == b).count();
+            common.truncate(shared);
+        }
+        let root: PathBuf = common.into_iter().filter(|c| !c.is_empty()).collect();
+        (!root.as_os_str().is_empty()).then_some(root)
+    }
+
+    /// Check if a path (relative to the codebase root) is left out
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if !self.includes.is_empty() && !self.includes.iter().any(|p| include_matches(p, path)) {
+            return true;
+        }
+        self.excludes
+            .iter()
+            .rev()
+            .find(|rule| exclude_matches(&rule.pattern, path))
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    /// Whether everything under the directory `dir` (relative) is excluded,
+    /// so a walk need not enter it: a `dirname/**` exclude matches it and no
+    /// later `!pattern` could re-include part of it
+    pub fn excludes_dir(&self, dir: &Path) -> bool {
+        let last_negation = self.excludes.iter().rposition(|rule| rule.negated);
+        self.excludes[last_negation.map_or(0, |n| n + 1)..].iter().any(|rule| {
+            rule.pattern.as_str().strip_suffix("/**").is_some_and(|prefix| {
+                exclude_matches(&rule.pattern, dir) || glob::Pattern::new(prefix).is_ok_and(|p| p.matches_path(dir))
+            })
+        })
+    }
+}
+
+fn compile(pattern: &str) -> Option<glob::Pattern> {
+    glob::Pattern::new(pattern)
+        .map_err(|e| warn!("Ignoring invalid pattern {:?}: {}", pattern, e))
+        .ok()
+}
+
+/// Include patterns match the path or one of its parent directories, so
+/// `services/billing` and `services/billing/**` select the same sub-tree
+fn include_matches(pattern: &glob::Pattern, path: &Path) -> bool {
+    path.ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| pattern.matches_path(p))
+}
+
+/// Exclude patterns match the full relative path or the file name. A pattern
+/// like `dirname/**` also matches any path containing `dirname` as a
+/// component, so nested occurrences (e.g. `packages/foo/node_modules/bar/`)
+/// are caught.
+fn exclude_matches(pattern: &glob::Pattern, path: &Path) -> bool {
+    let path_str = path.to_string_lossy();
+    let file_name = path.file_name().unwrap_or_default().to_str().unwrap_or("");
+    if pattern.matches(&path_str) || pattern.matches(file_name) {
+        return true;
+    }
+
+    if let Some(dirname) = pattern.as_str().strip_suffix("/**") {
+        if !dirname.contains('/') {
+            return path.components().any(|c| c.as_os_str().to_str() == Some(dirname));
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(includes: &[&str], excludes: &[&str]) -> PathFilter {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        PathFilter::new(&strings(includes), &strings(excludes))
+    }
+
+    #[test]
+    fn test_negated_exclude_reincludes() {
+        let f = filter(&[], &["build/**", "!build/generated-sources/**"]);
+        assert!(f.is_excluded(Path::new("build/classes/App.class")));
+        assert!(f.is_excluded(Path::new("lib/build/out.js")));
+        assert!(!f.is_excluded(Path::new("build/generated-sources/Api.java")));
+        assert!(!f.is_excluded(Path::new("src/App.java")));
+
+        // The last matching rule wins
+        let f = filter(&[], &["!build/generated-sources/**", "build/**"]);
+        assert!(f.is_excluded(Path::new("build/generated-sources/Api.java")));
+    }
+
+    #[test]
+    fn test_excluded_dirs_are_pruned_unless_reincluded() {
+        let f = filter(&[], &["node_modules/**", "web/legacy/**", "*.min.js"]);
+        assert!(f.excludes_dir(Path::new("node_modules")));
+        assert!(f.excludes_dir(Path::new("packages/app/node_modules")));
+        assert!(f.excludes_dir(Path::new("web/legacy")));
+        assert!(!f.excludes_dir(Path::new("web")));
+
+        let f = filter(&[], &["build/**", "!build/generated-sources/**"]);
+        assert!(!f.excludes_dir(Path::new("build")));
+    }
+
+    #[test]
+    fn test_include_restricts_to_subtrees() {
+        let f = filter(&["services/billing/**", "libs/shared"], &["node_modules/**"]);
+        assert!(!f.is_excluded(Path::new("services/billing/main.go")));
+        assert!(!f.is_excluded(Path::new("libs/shared/util.ts")));
+        assert!(f.is_excluded(Path::new("services/auth/main.go")));
+        assert!(f.is_excluded(Path::new("services/billing/node_modules/x/index.js")));
+        assert!(f.has_includes());
+    }
+
+    #[test]
+    fn test_forwardable_parts() {
+        let f = filter(&["services/billing/**", "services/billing/api/*.go"], &["dist/**", "build/**", "!build/gen/**", "*.min.js"]);
+        assert_eq!(f.forwardable_excludes(), vec!["*.min.js"]);
+        assert_eq!(f.include_root(), Some(PathBuf::from("services/billing")));
+        assert_eq!(filter(&["a/**", "b/**"], &[]).include_root(), None);
+        assert_eq!(filter(&[], &["dist/**"]).forwardable_excludes(), vec!["dist/**"]);
+    }
+
+    #[test]
+    fn test_empty_filter_excludes_nothing() {
+        let f = PathFilter::default();
+        assert!(f.is_empty());
+        assert!(!f.is_excluded(Path::new("node_modules/x.js")));
+    }
+}
@@ -0,0 +1,298 @@
+//! Python environment resolution
+//!
+//! scip-python resolves third-party symbols against the packages installed in
+//! a Python environment. We locate the environment a project is developed in
+//! (in-project virtualenv, Poetry, uv, Pipenv or conda) and describe its
+//! installed packages in the JSON format scip-python's `--environment` expects.
+
+use crate::manifest::{ManifestKind, ProjectManifest};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The tool that manages a Python environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PythonEnvKind {
+    Venv,
+    Poetry,
+    Uv,
+    Pipenv,
+    Conda,
+}
+
+/// A Python environment found for a project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PythonEnvironment {
+    pub kind: PythonEnvKind,
+    /// Environment prefix (the directory containing `bin/` or `Scripts/`)
+    pub prefix: PathBuf,
+}
+
+/// One package entry in scip-python's environment file
+#[derive(Debug, Serialize)]
+struct EnvironmentPackage {
+    name: String,
+    version: String,
+    files: Vec<String>,
+}
+
+/// Find the environment for the project described by a Python manifest
+pub fn discover(manifest: &ProjectManifest) -> Option<PythonEnvironment> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    discover_in(manifest, home.as_deref())
+}
+
+fn discover_in(manifest: &ProjectManifest, home: Option<&Path>) -> Option<PythonEnvironment> {
+    let root = manifest.project_root();
+    let env = |kind, prefix| Some(PythonEnvironment { kind, prefix });
+
+    if manifest.kind == ManifestKind::CondaEnv {
+        let name = manifest.name.as_deref()?;
+        return conda_env(name, home).and_then(|p| env(PythonEnvKind::Conda, p));
+    }
+
+    // uv, Poetry (`virtualenvs.in-project`) and Pipenv (`PIPENV_VENV_IN_PROJECT`)
+    // all use an in-project `.venv`; the lock file tells which tool made it
+    let in_project_kind = if root.join("uv.lock").is_file() {
+        PythonEnvKind::Uv
+    } else if root.join("poetry.lock").is_file() {
+        PythonEnvKind::Poetry
+    } else if root.join("Pipfile.lock").is_file() || manifest.kind == ManifestKind::Pipfile {
+        PythonEnvKind::Pipenv
+    } else {
+        PythonEnvKind::Venv
+    };
+    if in_project_kind == PythonEnvKind::Uv {
+        if let Some(dir) = std::env::var_os("UV_PROJECT_ENVIRONMENT") {
+            let prefix = root.join(dir);
+            if is_venv(&prefix) {
+                return env(PythonEnvKind::Uv, prefix);
+            }
+        }
+    }
+    for dir in [".venv", "venv"] {
+        let prefix = root.join(dir);
+        if is_venv(&prefix) {
+            return env(in_project_kind, prefix);
+        }
+    }
+
+    match in_project_kind {
+        PythonEnvKind::Poetry => {
+            let name = manifest.name.as_deref()?;
+            poetry_env(name, home).and_then(|p| env(PythonEnvKind::Poetry, p))
+        }
+        PythonEnvKind::Pipenv => {
+            let dir_name = root.canonicalize().ok()?.file_name()?.to_string_lossy().into_owned();
+            pipenv_env(&dir_name, home).and_then(|p| env(PythonEnvKind::Pipenv, p))
+        }
+        _ => None,
+    }
+}
+
+fn is_venv(prefix: &Path) -> bool {
+    prefix.join("pyvenv.cfg").is_file()
+}
+
+/// Named conda environment from the usual install locations
+fn conda_env(name: &str, home: Option<&Path>) -> Option<PathBuf> {
+    let mut bases: Vec<PathBuf> = Vec::new();
+    // The active environment itself, or its parent install's envs/
+    if let Some(active) = std::env::var_os("CONDA_PREFIX").map(PathBuf::from) {
+        if active.file_name().is_some_and(|n| n == name) {
+            return Some(active);
+        }
+        bases.push(active.join("envs"));
+    }
+    if let Some(home) = home {
+        for install in ["miniconda3", "anaconda3", "miniforge3", "mambaforge", ".conda"] {
+            bases.push(home.join(install).join("envs"));
+        }
+    }
+    bases.push(PathBuf::from("/opt/conda/envs"));
+
+    bases
+        .into_iter()
+        .map(|base| base.join(name))
+        .find(|p| p.join("conda-meta").is_dir())
+}
+
+/// Poetry keeps environments in its cache as `<name>-<hash>-py<version>`
+fn poetry_env(name: &str, home: Option<&Path>) -> Option<PathBuf> {
+    let caches = [
+        std::env::var_os("POETRY_CACHE_DIR").map(PathBuf::from),
+        std::env::var_os("XDG_CACHE_HOME").map(|d| PathBuf::from(d).join("pypoetry")),
+        home.map(|h| h.join(".cache/pypoetry")),
+    ];
+    let normalized = name.to_lowercase().replace(['_', '.'], "-");
+    caches
+        .into_iter()
+        .flatten()
+        .find_map(|cache| latest_prefixed(&cache.join("virtualenvs"), &format!("{}-", normalized)))
+}
+
+/// Pipenv keeps environments under `WORKON_HOME` as `<dirname>-<hash>`
+fn pipenv_env(dir_name: &str, home: Option<&Path>) -> Option<PathBuf> {
+    let bases = [
+        std::env::var_os("WORKON_HOME").map(PathBuf::from),
+        home.map(|h| h.join(".local/share/virtualenvs")),
+    ];
+    bases
+        .into_iter()
+        .flatten()
+        .find_map(|base| latest_prefixed(&base, &format!("{}-", dir_name)))
+}
+
+/// The last (by name) virtualenv in `dir` whose name starts with `prefix`
+fn latest_prefixed(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with(prefix))
+                && is_venv(p)
+        })
+        .max()
+}
+
+/// `site-packages` directories of an environment
+fn site_packages(prefix: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(prefix.join("lib"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("python"))
+        .map(|e| e.path().join("site-packages"))
+        .chain(std::iter::once(prefix.join("Lib").join("site-packages")))
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Write the environment's installed packages as a scip-python environment
+/// file. Returns the number of packages written.
+pub fn write_environment_json(env: &PythonEnvironment, output: &Path) -> Result<usize> {
+    let mut packages = Vec::new();
+    for site in site_packages(&env.prefix) {
+        let mut dist_infos: Vec<PathBuf> = std::fs::read_dir(&site)
+            .with_context(|| format!("Failed to read {:?}", site))?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "dist-info"))
+            .collect();
+        dist_infos.sort();
+        packages.extend(dist_infos.iter().filter_map(|d| read_dist_info(d)));
+    }
+
+    if packages.is_empty() {
+        return Err(anyhow!("no installed packages found in {:?}", env.prefix));
+    }
+
+    let json = serde_json::to_string_pretty(&packages)
+        .context("Failed to serialize Python environment")?;
+    std::fs::write(output, json).with_context(|| format!("Failed to write {:?}", output))?;
+    Ok(packages.len())
+}
+
+/// Name, version and Python files of one installed distribution
+fn read_dist_info(dist_info: &Path) -> Option<EnvironmentPackage> {
+    let metadata = std::fs::read_to_string(dist_info.join("METADATA")).ok()?;
+    let header = |key: &str| {
+        metadata
+            .lines()
+            .take_while(|l| !l.is_empty())
+            .find_map(|l| l.strip_prefix(key).map(|v| v.trim().to_string()))
+    };
+    let name = header("Name:")?;
+    let version = header("Version:")?;
+
+    // RECORD is CSV: path,hash,size; paths are relative to site-packages
+    let files = std::fs::read_to_string(dist_info.join("RECORD"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split(',').next())
+        .filter(|f| !f.starts_with("..") && (f.ends_with(".py") || f.ends_with(".pyi")))
+        .map(str::to_string)
+        .collect();
+
+    Some(EnvironmentPackage { name, version, files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        let path = root.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn manifest(root: &Path, file: &str) -> ProjectManifest {
+        parse_manifest(&root.join(file)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_in_project_venv_kind_from_lock_file() {
+        let temp = TempDir::new().unwrap();
+        write(temp.path(), "pyproject.toml", "[project]\nname = \"app\"\n");
+        write(temp.path(), ".venv/pyvenv.cfg", "home = /usr/bin");
+        let m = manifest(temp.path(), "pyproject.toml");
+
+        assert_eq!(discover_in(&m, None).unwrap().kind, PythonEnvKind::Venv);
+
+        write(temp.path(), "uv.lock", "");
+        let env = discover_in(&m, None).unwrap();
+        assert_eq!(env.kind, PythonEnvKind::Uv);
+        assert_eq!(env.prefix, temp.path().join(".venv"));
+    }
+
+    #[test]
+    fn test_poetry_and_conda_environments_outside_project() {
+        let home = TempDir::new().unwrap();
+        let temp = TempDir::new().unwrap();
+        write(temp.path(), "pyproject.toml", "[tool.poetry]\nname = \"My_App\"\n");
+        write(temp.path(), "poetry.lock", "");
+        write(home.path(), ".cache/pypoetry/virtualenvs/my-app-Ab12Cd34-py3.11/pyvenv.cfg", "");
+        write(temp.path(), "environment.yml", "name: science\ndependencies:\n  - numpy\n");
+        write(home.path(), "miniconda3/envs/science/conda-meta/history", "");
+
+        let poetry = discover_in(&manifest(temp.path(), "pyproject.toml"), Some(home.path())).unwrap();
+        assert_eq!(poetry.kind, PythonEnvKind::Poetry);
+        assert!(poetry.prefix.ends_with("my-app-Ab12Cd34-py3.11"));
+
+        let conda = discover_in(&manifest(temp.path(), "environment.yml"), Some(home.path())).unwrap();
+        assert_eq!(conda.kind, PythonEnvKind::Conda);
+        assert_eq!(conda.prefix, home.path().join("miniconda3/envs/science"));
+    }
+
+    #[test]
+    fn test_environment_json_lists_installed_packages() {
+        let temp = TempDir::new().unwrap();
+        let site = "lib/python3.11/site-packages";
+        write(
+            temp.path(),
+            &format!("{}/requests-2.31.0.dist-info/METADATA", site),
+            "Metadata-Version: 2.1\nName: requests\nVersion: 2.31.0\n\nBody",
+        );
+        write(
+            temp.path(),
+            &format!("{}/requests-2.31.0.dist-info/RECORD", site),
+            "requests/__init__.py,sha256=x,100\nrequests-2.31.0.dist-info/METADATA,,\n../../bin/tool,,\n",
+        );
+        let env = PythonEnvironment { kind: PythonEnvKind::Venv, prefix: temp.path().to_path_buf() };
+        let output = temp.path().join("env.json");
+
+        assert_eq!(write_environment_json(&env, &output).unwrap(), 1);
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(json[0]["name"], "requests");
+        assert_eq!(json[0]["version"], "2.31.0");
+        assert_eq!(json[0]["files"], serde_json::json!(["requests/__init__.py"]));
+    }
+}
@@ -0,0 +1,432 @@
+//! Minimal SCIP index handling
+//!
+//! Reads and writes `.scip` files at the protobuf wire level. Only the parts
+//! of the schema we post-process (index metadata, document paths, symbol
+//! strings) are decoded; everything else is carried through byte-for-byte, so rewriting
+//! an index never loses fields from newer SCIP versions.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+// Field numbers from scip.proto
+const INDEX_METADATA: u32 = 1;
+const INDEX_DOCUMENTS: u32 = 2;
+const INDEX_EXTERNAL_SYMBOLS: u32 = 3;
+const METADATA_PROJECT_ROOT: u32 = 3;
+const DOCUMENT_RELATIVE_PATH: u32 = 1;
+const DOCUMENT_OCCURRENCES: u32 = 2;
+const DOCUMENT_SYMBOLS: u32 = 3;
+const OCCURRENCE_SYMBOL: u32 = 2;
+const OCCURRENCE_SYMBOL_ROLES: u32 = 3;
+const SYMBOL_INFORMATION_SYMBOL: u32 = 1;
+const SYMBOL_INFORMATION_RELATIONSHIPS: u32 = 4;
+const SYMBOL_INFORMATION_ENCLOSING_SYMBOL: u32 = 8;
+const RELATIONSHIP_SYMBOL: u32 = 1;
+const ROLE_DEFINITION: u64 = 1;
+
+/// Payload of one protobuf field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Fixed32(u32),
+}
+
+/// One protobuf field as it appears on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub number: u32,
+    pub value: FieldValue,
+}
+
+impl Field {
+    pub fn bytes(number: u32, bytes: Vec<u8>) -> Self {
+        Self { number, value: FieldValue::Bytes(bytes) }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.value {
+            FieldValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).ok_or_else(|| anyhow!("truncated varint"))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("varint too long")
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = pos.checked_add(len).filter(|&end| end <= buf.len());
+    let end = end.ok_or_else(|| anyhow!("truncated field"))?;
+    let slice = &buf[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+/// Decode a message into its fields, in wire order
+pub fn decode_fields(buf: &[u8]) -> Result<Vec<Field>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos)?;
+        let number = u32::try_from(key >> 3).context("field number out of range")?;
+        let value = match key & 7 {
+            0 => FieldValue::Varint(read_varint(buf, &mut pos)?),
+            1 => FieldValue::Fixed64(u64::from_le_bytes(take(buf, &mut pos, 8)?.try_into()?)),
+            2 => {
+                let len = usize::try_from(read_varint(buf, &mut pos)?)?;
+                FieldValue::Bytes(take(buf, &mut pos, len)?.to_vec())
+            }
+            5 => FieldValue::Fixed32(u32::from_le_bytes(take(buf, &mut pos, 4)?.try_into()?)),
+            wire => bail!("unsupported wire type {} for field {}", wire, number),
+        };
+        fields.push(Field { number, value });
+    }
+    Ok(fields)
+}
+
+/// Encode fields back into a message
+pub fn encode_fields(fields: &[Field]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in fields {
+        let key = u64::from(field.number) << 3;
+        match &field.value {
+            FieldValue::Varint(v) => {
+                write_varint(&mut out, key);
+                write_varint(&mut out, *v);
+            }
+            FieldValue::Fixed64(v) => {
+                write_varint(&mut out, key | 1);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            FieldValue::Bytes(b) => {
+                write_varint(&mut out, key | 2);
+                write_varint(&mut out, b.len() as u64);
+                out.extend_from_slice(b);
+            }
+            FieldValue::Fixed32(v) => {
+                write_varint(&mut out, key | 5);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+/// First string value of field `number`
+fn get_string(fields: &[Field], number: u32) -> Option<String> {
+    fields
+        .iter()
+        .filter(|f| f.number == number)
+        .find_map(Field::as_bytes)
+        .map(|b| String::from_utf8_lossy(b).into_owned())
+}
+
+/// Replace every occurrence of field `number` with one string value
+fn set_string(fields: &mut Vec<Field>, number: u32, value: &str) {
+    let at = fields.iter().position(|f| f.number == number).unwrap_or(fields.len());
+    fields.retain(|f| f.number != number);
+    fields.insert(at.min(fields.len()), Field::bytes(number, value.as_bytes().to_vec()));
+}
+
+/// Rewrite the string fields `numbers` in place; returns how many changed
+fn rewrite_strings(fields: &mut [Field], numbers: &[u32], f: &dyn Fn(&str) -> Option<String>) -> usize {
+    let mut changed = 0;
+    for field in fields.iter_mut().filter(|fl| numbers.contains(&fl.number)) {
+        if let FieldValue::Bytes(bytes) = &mut field.value {
+            if let Some(new) = std::str::from_utf8(bytes).ok().and_then(f) {
+                *bytes = new.into_bytes();
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+/// Rewrite the embedded message in each field `number` with `rewrite`,
+/// re-encoding only messages that changed. Undecodable messages are kept.
+fn rewrite_nested(fields: &mut [Field], number: u32, rewrite: &dyn Fn(&mut [Field]) -> usize) -> usize {
+    let mut changed = 0;
+    for field in fields.iter_mut().filter(|fl| fl.number == number) {
+        if let FieldValue::Bytes(bytes) = &mut field.value {
+            let Ok(mut inner) = decode_fields(bytes) else {
+                continue;
+            };
+            let n = rewrite(&mut inner);
+            if n > 0 {
+                *bytes = encode_fields(&inner);
+                changed += n;
+            }
+        }
+    }
+    changed
+}
+
+/// Rewrite every symbol reference inside a `SymbolInformation`
+fn rewrite_symbol_information(fields: &mut [Field], f: &dyn Fn(&str) -> Option<String>) -> usize {
+    rewrite_strings(fields, &[SYMBOL_INFORMATION_SYMBOL, SYMBOL_INFORMATION_ENCLOSING_SYMBOL], f)
+        + rewrite_nested(fields, SYMBOL_INFORMATION_RELATIONSHIPS, &|rel| {
+            rewrite_strings(rel, &[RELATIONSHIP_SYMBOL], f)
+        })
+}
+
+/// One document of an index; only its path is decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub fields: Vec<Field>,
+}
+
+impl Document {
+    pub fn relative_path(&self) -> String {
+        get_string(&self.fields, DOCUMENT_RELATIVE_PATH).unwrap_or_default()
+    }
+
+    pub fn set_relative_path(&mut self, path: &str) {
+        set_string(&mut self.fields, DOCUMENT_RELATIVE_PATH, path);
+    }
+
+    /// Symbols this document defines: its `SymbolInformation` entries and
+    /// occurrences with the definition role
+    pub fn defined_symbols(&self) -> Vec<String> {
+        let nested = |number: u32| {
+            self.fields
+                .iter()
+                .filter(move |f| f.number == number)
+                .filter_map(Field::as_bytes)
+                .filter_map(|b| decode_fields(b).ok())
+        };
+        let mut symbols: Vec<String> = nested(DOCUMENT_SYMBOLS)
+            .filter_map(|info| get_string(&info, SYMBOL_INFORMATION_SYMBOL))
+            .collect();
+        symbols.extend(nested(DOCUMENT_OCCURRENCES).filter_map(|occ| {
+            let is_definition = occ.iter().any(|f| {
+                f.number == OCCURRENCE_SYMBOL_ROLES
+                    && matches!(f.value, FieldValue::Varint(roles) if roles & ROLE_DEFINITION != 0)
+            });
+            is_definition.then(|| get_string(&occ, OCCURRENCE_SYMBOL)).flatten()
+        }));
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Replace symbol strings wherever they occur in the document;
+    /// `f` returns the new symbol or `None` to keep it. Returns the number of
+    /// strings replaced.
+    pub fn rewrite_symbols(&mut self, f: &dyn Fn(&str) -> Option<String>) -> usize {
+        rewrite_nested(&mut self.fields, DOCUMENT_OCCURRENCES, &|occ| {
+            rewrite_strings(occ, &[OCCURRENCE_SYMBOL], f)
+        }) + rewrite_nested(&mut self.fields, DOCUMENT_SYMBOLS, &|info| rewrite_symbol_information(info, f))
+    }
+}
+
+/// A SCIP index with metadata and documents decoded one level deep
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    pub metadata: Vec<Field>,
+    pub documents: Vec<Document>,
+    /// Encoded `SymbolInformation` messages
+    pub external_symbols: Vec<Vec<u8>>,
+    /// Fields this module does not interpret
+    pub other: Vec<Field>,
+}
+
+impl Index {
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut index = Index::default();
+        for field in decode_fields(buf)? {
+            match (field.number, &field.value) {
+                // Repeated occurrences of a message field merge
+                (INDEX_METADATA, FieldValue::Bytes(b)) => index.metadata.extend(decode_fields(b)?),
+                (INDEX_DOCUMENTS, FieldValue::Bytes(b)) => {
+                    index.documents.push(Document { fields: decode_fields(b)? })
+                }
+                (INDEX_EXTERNAL_SYMBOLS, FieldValue::Bytes(b)) => index.external_symbols.push(b.clone()),
+                _ => index.other.push(field),
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut fields = Vec::with_capacity(1 + self.documents.len() + self.external_symbols.len());
+        if !self.metadata.is_empty() {
+            fields.push(Field::bytes(INDEX_METADATA, encode_fields(&self.metadata)));
+        }
+        fields.extend(self.documents.iter().map(|d| Field::bytes(INDEX_DOCUMENTS, encode_fields(&d.fields))));
+        fields.extend(self.external_symbols.iter().map(|s| Field::bytes(INDEX_EXTERNAL_SYMBOLS, s.clone())));
+        fields.extend(self.other.iter().cloned());
+        encode_fields(&fields)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let buf = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        Self::decode(&buf).with_context(|| format!("Failed to decode SCIP index {:?}", path))
+    }
+
+    /// Write the index atomically: readers never see a partial file
+    pub fn write(&self, path: &Path) -> Result<()> {
+        crate::outdir::write_atomic(path, self.encode())
+    }
+
+    pub fn project_root(&self) -> Option<String> {
+        get_string(&self.metadata, METADATA_PROJECT_ROOT)
+    }
+
+    pub fn set_project_root(&mut self, uri: &str) {
+        set_string(&mut self.metadata, METADATA_PROJECT_ROOT, uri);
+    }
+
+    /// Prefix every document path with `dir` (a `/`-separated relative path)
+    pub fn prefix_paths(&mut self, dir: &str) {
+        let dir = dir.trim_matches('/');
+        if dir.is_empty() {
+            return;
+        }
+        for doc in &mut self.documents {
+            let path = format!("{}/{}", dir, doc.relative_path());
+            doc.set_relative_path(&path);
+        }
+    }
+
+    /// Keep only the documents whose path `keep` accepts. Returns the
+    /// number dropped.
+    pub fn retain_documents(&mut self, keep: impl Fn(&str) -> bool) -> usize {
+        let before = self.documents.len();
+        self.documents.retain(|doc| keep(&doc.relative_path()));
+        before - self.documents.len()
+    }
+
+    /// Append another index's documents and external symbols. Documents
+    /// already present (by path) and external symbols already declared are
+    /// kept from `self`.
+    pub fn merge(&mut self, other: Index) {
+        let mut paths: HashSet<String> = self.documents.iter().map(Document::relative_path).collect();
+        for doc in other.documents {
+            if paths.insert(doc.relative_path()) {
+                self.documents.push(doc);
+            }
+        }
+
+        let mut symbols: HashSet<Option<String>> =
+            self.external_symbols.iter().map(|s| external_symbol_name(s)).collect();
+        for symbol in other.external_symbols {
+            if symbols.insert(external_symbol_name(&symbol)) {
+                self.external_symbols.push(symbol);
+            }
+        }
+
+        if self.metadata.is_empty() {
+            self.metadata = other.metadata;
+        }
+    }
+}
+
+impl Index {
+    /// Replace symbol strings across all documents and external symbols;
+    /// see [`Document::rewrite_symbols`]
+    pub fn rewrite_symbols(&mut self, f: &dyn Fn(&str) -> Option<String>) -> usize {
+        let mut changed: usize = self.documents.iter_mut().map(|d| d.rewrite_symbols(f)).sum();
+        for encoded in &mut self.external_symbols {
+            let Ok(mut info) = decode_fields(encoded) else {
+                continue;
+            };
+            let n = rewrite_symbol_information(&mut info, f);
+            if n > 0 {
+                *encoded = encode_fields(&info);
+                changed += n;
+            }
+        }
+        changed
+    }
+}
+
+fn external_symbol_name(encoded: &[u8]) -> Option<String> {
+    decode_fields(encoded).ok().and_then(|f| get_string(&f, SYMBOL_INFORMATION_SYMBOL))
+}
+
+/// `file://` URI for a directory, as used in `Metadata.project_root`
+pub fn file_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    format!("file://{}", path.to_string_lossy().replace('\\', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Document with a path, one opaque occurrence and a language
+    fn document(path: &str) -> Vec<u8> {
+        encode_fields(&[
+            Field::bytes(DOCUMENT_RELATIVE_PATH, path.as_bytes().to_vec()),
+            Field::bytes(2, vec![0x0a, 0x01, 0x00]),
+            Field::bytes(4, b"go".to_vec()),
+        ])
+    }
+
+    fn index(root: &str, docs: &[&str], externals: &[&str]) -> Vec<u8> {
+        let metadata = encode_fields(&[
+            Field { number: 1, value: FieldValue::Varint(0) },
+            Field::bytes(METADATA_PROJECT_ROOT, root.as_bytes().to_vec()),
+        ]);
+        let mut fields = vec![Field::bytes(INDEX_METADATA, metadata)];
+        fields.extend(docs.iter().map(|d| Field::bytes(INDEX_DOCUMENTS, document(d))));
+        fields.extend(externals.iter().map(|s| {
+            Field::bytes(INDEX_EXTERNAL_SYMBOLS, encode_fields(&[Field::bytes(1, s.as_bytes().to_vec())]))
+        }));
+        encode_fields(&fields)
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let buf = index("file:///repo", &["main.go", "pkg/util.go"], &["ext a"]);
+        let decoded = Index::decode(&buf).unwrap();
+        assert_eq!(decoded.documents.len(), 2);
+        assert_eq!(decoded.project_root().as_deref(), Some("file:///repo"));
+        assert_eq!(decoded.encode(), buf);
+    }
+
+    #[test]
+    fn test_merge_prefixes_and_dedups() {
+        let mut api = Index::decode(&index("file:///repo/api", &["main.go"], &["ext a", "ext b"])).unwrap();
+        let mut worker = Index::decode(&index("file:///repo/worker", &["main.go"], &["ext b"])).unwrap();
+        api.prefix_paths("api");
+        worker.prefix_paths("worker/");
+        api.merge(worker);
+        api.set_project_root("file:///repo");
+
+        let merged = Index::decode(&api.encode()).unwrap();
+        let paths: Vec<String> = merged.documents.iter().map(Document::relative_path).collect();
+        assert_eq!(paths, vec!["api/main.go", "worker/main.go"]);
+        assert_eq!(merged.external_symbols.len(), 2);
+        assert_eq!(merged.project_root().as_deref(), Some("file:///repo"));
+        // Untouched document fields survive
+        assert_eq!(merged.documents[1].fields[2], Field::bytes(4, b"go".to_vec()));
+    }
+
+    #[test]
+    fn test_truncated_input_is_an_error() {
+        let buf = index("file:///repo", &["main.go"], &[]);
+        assert!(Index::decode(&buf[..buf.len() - 3]).is_err());
+    }
+}
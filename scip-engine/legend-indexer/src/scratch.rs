@@ -0,0 +1,136 @@
+//! Scratch copy of the codebase
+//!
+//! Some indexers build the project to index it: scip-java runs Maven or
+//! Gradle, scip-dotnet restores and builds, rust-analyzer runs cargo (which
+//! may rewrite `Cargo.lock` and fills `target/`), and scip-zig writes
+//! `index.scip` to its working directory. With `--work-dir` nothing may be
+//! written into the analysed tree, which may be a read-only mount, so these
+//! indexers run in a copy of it kept in the work directory.
+//!
+//! The copy is synced rather than recreated: files whose size and
+//! modification time match the codebase are left alone, so build outputs in
+//! the copy survive between runs. Files are copied with `std::fs::copy`,
+//! which on Linux uses `copy_file_range`; copy-on-write filesystems (btrfs,
+//! XFS) share the data instead of duplicating it.
+
+use crate::incremental::collect_files;
+use crate::pathfilter::PathFilter;
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Directory of the work directory holding scratch copies
+pub const TREE_DIR: &str = "tree";
+
+/// The list of files the last sync copied, next to the copy
+fn synced_list(dest: &Path) -> PathBuf {
+    let name = dest.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    dest.with_file_name(format!("{}.synced", name))
+}
+
+/// Bring `dest` up to date with the codebase at `root`, skipping `.git`, the
+/// in-tree work directory and directories `filter` excludes whole (such as
+/// `node_modules/`). Files the build created in the copy are kept; files
+/// removed from the codebase are removed from it. Returns how many files
+/// were copied.
+pub fn sync(root: &Path, dest: &Path, filter: &PathFilter) -> Result<usize> {
+    let list = synced_list(dest);
+    let previous: BTreeSet<PathBuf> = std::fs::read_to_string(&list)
+        .map(|text| text.lines().map(PathBuf::from).collect())
+        .unwrap_or_default();
+    let mut files = Vec::new();
+    collect_files(root, root, filter, &mut files);
+    std::fs::create_dir_all(dest).with_context(|| format!("Failed to create {:?}", dest))?;
+
+    let mut copied = 0;
+    for rel in &files {
+        let (from, to) = (root.join(rel), dest.join(rel));
+        if is_current(&from, &to) {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        copy_entry(&from, &to).with_context(|| format!("Failed to copy {:?} to {:?}", from, to))?;
+        copied += 1;
+    }
+
+    let current: BTreeSet<PathBuf> = files.into_iter().collect();
+    for removed in previous.difference(&current) {
+        let _ = std::fs::remove_file(dest.join(removed));
+    }
+    let listing: Vec<String> = current.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    crate::outdir::write_atomic(&list, listing.join("\n"))?;
+    debug!("Synced {} of {} files to {:?}", copied, current.len(), dest);
+    Ok(copied)
+}
+
+/// Whether the copy of a file (or symlink) matches the original
+fn is_current(from: &Path, to: &Path) -> bool {
+    let (Ok(source), Ok(copy)) = (std::fs::symlink_metadata(from), std::fs::symlink_metadata(to)) else {
+        return false;
+    };
+    if source.file_type().is_symlink() {
+        return copy.file_type().is_symlink() && std::fs::read_link(from).ok() == std::fs::read_link(to).ok();
+    }
+    copy.is_file() && source.len() == copy.len() && source.modified().ok() == copy.modified().ok()
+}
+
+/// Copy a file with its modification time, or recreate a symlink
+fn copy_entry(from: &Path, to: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(to) {
+        Ok(existing) if existing.is_dir() => std::fs::remove_dir_all(to)?,
+        Ok(_) => std::fs::remove_file(to)?,
+        Err(_) => {}
+    }
+    let metadata = std::fs::symlink_metadata(from)?;
+    #[cfg(unix)]
+    if metadata.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    if let Ok(modified) = metadata.modified() {
+        std::fs::File::open(to)?.set_modified(modified)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_keeps_build_outputs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let (root, dest) = (temp.path().join("repo"), temp.path().join("tree/repo"));
+        let write = |path: &Path, content: &str| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(&root.join("src/main.rs"), "fn main() {}");
+        write(&root.join("Cargo.toml"), "[package]");
+        write(&root.join(".git/HEAD"), "ref");
+        write(&root.join(".legend-indexer/go.scip"), "");
+        write(&root.join("web/node_modules/left-pad/index.js"), "");
+        let filter = PathFilter::new(&[], &["node_modules/**".to_string()]);
+
+        assert_eq!(sync(&root, &dest, &filter).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(dest.join("src/main.rs")).unwrap(), "fn main() {}");
+        assert!(!dest.join(".git").exists() && !dest.join(".legend-indexer").exists());
+        assert!(!dest.join("web").exists(), "excluded directories are not copied");
+
+        // The build writes into the copy; the next sync only fixes what differs
+        write(&dest.join("target/debug/app"), "binary");
+        write(&dest.join("Cargo.toml"), "[package] # rewritten by the build");
+        std::fs::remove_file(root.join("src/main.rs")).unwrap();
+        write(&root.join("src/lib.rs"), "pub fn f() {}");
+        assert_eq!(sync(&root, &dest, &filter).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(dest.join("Cargo.toml")).unwrap(), "[package]");
+        assert!(dest.join("src/lib.rs").is_file());
+        assert!(!dest.join("src/main.rs").exists(), "removed from the codebase");
+        assert!(dest.join("target/debug/app").is_file(), "build outputs survive");
+        assert_eq!(sync(&root, &dest, &filter).unwrap(), 0);
+    }
+}
@@ -0,0 +1,261 @@
+//! Generated tsconfig for scip-typescript
+//!
+//! scip-typescript indexes whatever its tsconfig selects, so directories the
+//! include/exclude patterns drop (`dist/`, `node_modules/`) would still be
+//! indexed. We hand it a generated tsconfig instead: one that extends the
+//! project's root tsconfig when there is one, keeps its exclusions and adds
+//! ours, with every path resolved against the codebase root.
+
+use crate::pathfilter::PathFilter;
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// What tsc excludes when a tsconfig has no `exclude` of its own
+const TSC_DEFAULT_EXCLUDES: &[&str] = &["node_modules", "bower_components", "jspm_packages"];
+
+/// A tsconfig to write before running scip-typescript
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedTsconfig {
+    /// Project directory to pass to scip-typescript; holds `tsconfig.json`
+    pub dir: PathBuf,
+    pub contents: Value,
+}
+
+impl GeneratedTsconfig {
+    pub fn path(&self) -> PathBuf {
+        self.dir.join("tsconfig.json")
+    }
+
+    pub fn write(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {:?}", self.dir))?;
+        let json = serde_json::to_string_pretty(&self.contents)?;
+        std::fs::write(self.path(), json)
+            .with_context(|| format!("Failed to write {:?}", self.path()))
+    }
+}
+
+/// The tsconfig applying `filter` to the codebase at `root` (absolute),
+/// placed under `work_dir`. None when there is nothing to apply or the root
+/// tsconfig cannot be read; document filtering still applies then.
+pub fn generate(root: &Path, work_dir: &Path, filter: &PathFilter) -> Option<GeneratedTsconfig> {
+    let excludes = filter.forwardable_excludes();
+    if excludes.is_empty() && !filter.has_includes() {
+        return None;
+    }
+
+    let base_path = root.join("tsconfig.json");
+    let base = if base_path.is_file() {
+        let parsed = std::fs::read_to_string(&base_path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&strip_jsonc(&text)).ok());
+        match parsed {
+            Some(Value::Object(base)) => Some(base),
+            _ => {
+                warn!("Could not parse {:?}; exclusions apply to the index only", base_path);
+                return None;
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut config = Map::new();
+    let mut exclude: Vec<String> = Vec::new();
+    match &base {
+        Some(base) => {
+            config.insert("extends".into(), json!(base_path.to_string_lossy()));
+            // Setting `exclude` replaces the base's, including tsc's defaults
+            match base.get("exclude").and_then(Value::as_array) {
+                Some(own) => exclude.extend(own.iter().filter_map(Value::as_str).map(|p| rooted(root, p))),
+                None => {
+                    exclude.extend(TSC_DEFAULT_EXCLUDES.iter().map(|p| rooted(root, p)));
+                    if let Some(out_dir) = base.get("compilerOptions").and_then(|o| o.get("outDir")).and_then(Value::as_str) {
+                        exclude.push(rooted(root, out_dir));
+                    }
+                }
+            }
+        }
+        // What --infer-tsconfig would use
+        None => {
+            config.insert("compilerOptions".into(), json!({ "allowJs": true }));
+        }
+    }
+    exclude.extend(excludes.iter().map(|p| ts_glob(root, p)));
+    config.insert("exclude".into(), json!(exclude));
+
+    // Globs in the base resolve against it; without any, tsc would default
+    // to the generated tsconfig's own directory
+    let base_selects = base
+        .as_ref()
+        .is_some_and(|b| b.contains_key("include") || b.contains_key("files"));
+    if filter.has_includes() {
+        let include: Vec<String> = filter.include_patterns().iter().map(|p| rooted(root, p)).collect();
+        config.insert("include".into(), json!(include));
+    } else if !base_selects {
+        config.insert("include".into(), json!([rooted(root, "**/*")]));
+    }
+
+    Some(GeneratedTsconfig {
+        dir: work_dir.to_path_buf(),
+        contents: Value::Object(config),
+    })
+}
+
+/// A tsconfig selecting only `files` (relative to `root`), for re-indexing
+/// changed files: it extends the root tsconfig when that can be read, so
+/// the files compile with the project's options
+pub fn for_files(root: &Path, work_dir: &Path, files: &[PathBuf]) -> GeneratedTsconfig {
+    let base_path = root.join("tsconfig.json");
+    let mut config = Map::new();
+    let base_parses = std::fs::read_to_string(&base_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&strip_jsonc(&text)).ok())
+        .is_some_and(|base| base.is_object());
+    if base_parses {
+        config.insert("extends".into(), json!(base_path.to_string_lossy()));
+    } else {
+        config.insert("compilerOptions".into(), json!({ "allowJs": true }));
+    }
+    let files: Vec<String> = files.iter().map(|f| rooted(root, &f.to_string_lossy())).collect();
+    config.insert("files".into(), json!(files));
+    config.insert("include".into(), json!([]));
+
+    GeneratedTsconfig {
+        dir: work_dir.to_path_buf(),
+        contents: Value::Object(config),
+    }
+}
+
+/// A path or glob relative to the codebase root, made absolute
+fn rooted(root: &Path, pattern: &str) -> String {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches("/**");
+    format!("{}/{}", root.to_string_lossy().trim_end_matches('/'), pattern)
+}
+
+/// An exclude pattern as a tsconfig glob. Patterns without a directory part
+/// (`node_modules/**`, `*.min.js`) match at any depth, as they do in detection.
+fn ts_glob(root: &Path, pattern: &str) -> String {
+    let dir_part = pattern.trim_end_matches("/**");
+    if dir_part.contains('/') {
+        rooted(root, pattern)
+    } else {
+        rooted(root, &format!("**/{}", dir_part))
+    }
+}
+
+/// tsconfig files are JSON with comments and trailing commas
+fn strip_jsonc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    // A comma is written once we know it is not trailing
+    let mut pending_comma = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            (',', _) => pending_comma = true,
+            _ if c.is_whitespace() => out.push(c),
+            _ => {
+                if std::mem::take(&mut pending_comma) && c != '}' && c != ']' {
+                    out.push(',');
+                }
+                in_string = c == '"';
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extends_root_tsconfig() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("tsconfig.json"),
+            "{\n  // app config\n  \"compilerOptions\": { \"outDir\": \"lib\", },\n  /* sources */ \"include\": [\"src\"],\n}\n",
+        )
+        .unwrap();
+        let filter = PathFilter::new(&[], &["node_modules/**".into(), "dist/**".into(), "src/gen/**".into()]);
+
+        let generated = generate(root, &root.join(".work"), &filter).unwrap();
+        let r = root.to_string_lossy();
+        assert_eq!(generated.contents["extends"], json!(root.join("tsconfig.json").to_string_lossy()));
+        assert!(generated.contents.get("include").is_none(), "inherits the base include");
+        let exclude: Vec<&str> = generated.contents["exclude"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        assert!(exclude.contains(&format!("{}/lib", r).as_str()), "{:?}", exclude);
+        assert!(exclude.contains(&format!("{}/**/dist", r).as_str()), "{:?}", exclude);
+        assert!(exclude.contains(&format!("{}/src/gen", r).as_str()), "{:?}", exclude);
+    }
+
+    #[test]
+    fn test_generated_without_root_tsconfig() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        let filter = PathFilter::new(&["packages/web/**".into()], &["*.min.js".into()]);
+
+        let generated = generate(root, &root.join(".work"), &filter).unwrap();
+        let r = root.to_string_lossy();
+        assert_eq!(generated.contents["compilerOptions"]["allowJs"], json!(true));
+        assert_eq!(generated.contents["include"], json!([format!("{}/packages/web", r)]));
+        assert_eq!(generated.contents["exclude"], json!([format!("{}/**/*.min.js", r)]));
+
+        assert!(generate(root, &root.join(".work"), &PathFilter::default()).is_none());
+    }
+
+    #[test]
+    fn test_for_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        let r = root.to_string_lossy();
+        let files = [PathBuf::from("src/app.ts")];
+
+        let generated = for_files(root, &root.join(".work"), &files);
+        assert_eq!(generated.contents["compilerOptions"]["allowJs"], json!(true));
+        assert_eq!(generated.contents["files"], json!([format!("{}/src/app.ts", r)]));
+        assert_eq!(generated.contents["include"], json!([]));
+
+        std::fs::write(root.join("tsconfig.json"), "{ \"include\": [\"src\"], }").unwrap();
+        let generated = for_files(root, &root.join(".work"), &files);
+        assert!(generated.contents["extends"].as_str().unwrap().ends_with("tsconfig.json"));
+        assert!(generated.contents.get("compilerOptions").is_none());
+    }
+
+    #[test]
+    fn test_strip_jsonc() {
+        let text = "{\"a\": \"http://x\", // c\n \"b\": [1, 2,], /* d */ }";
+        let value: Value = serde_json::from_str(&strip_jsonc(text)).unwrap();
+        assert_eq!(value, json!({ "a": "http://x", "b": [1, 2] }));
+    }
+}
@@ -0,0 +1,239 @@
+//! Watch mode
+//!
+//! `watch` keeps a codebase's indexes current while it is edited. The tree is
+//! polled rather than subscribed to: each poll compares every file's size and
+//! modification time with the previous snapshot, which is cheap for a working
+//! copy and behaves the same on every platform and mount (including Docker
+//! bind mounts, where change notifications are unreliable). A burst of
+//! changes, such as a branch switch or a formatter run, becomes one cycle
+//! once the tree has been quiet for the debounce period.
+//!
+//! Each cycle re-indexes only the languages with a changed input, into a
+//! staging directory. An index replaces the published one only after it
+//! decodes as a SCIP index, so a failed run leaves the last good one in place.
+
+use crate::detect::Language;
+use crate::incremental::collect_files;
+use crate::orchestrate::IndexerResult;
+use crate::outdir;
+use crate::pathfilter::PathFilter;
+use crate::scip;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::debug;
+
+/// Staging directory for indexes being produced, inside the work directory
+pub const STAGING_DIR: &str = "watch";
+
+/// Size and modification time of every file the patterns keep
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot(BTreeMap<PathBuf, (u64, Option<SystemTime>)>);
+
+impl Snapshot {
+    /// Snapshot the codebase at `root`, leaving out excluded files and
+    /// anything under `skip` (relative directories, e.g. an output directory
+    /// inside the codebase)
+    pub fn take(root: &Path, filter: &PathFilter, skip: &[PathBuf]) -> Self {
+        let mut files = Vec::new();
+        collect_files(root, root, filter, &mut files);
+        Self(
+            files
+                .into_iter()
+                .filter(|rel| !filter.is_excluded(rel) && !skip.iter().any(|dir| rel.starts_with(dir)))
+                .map(|rel| {
+                    let metadata = root.join(&rel).symlink_metadata().ok();
+                    let stamp = metadata.map_or((0, None), |m| (m.len(), m.modified().ok()));
+                    (rel, stamp)
+                })
+                .collect(),
+        )
+    }
+
+    /// Files added, modified or deleted since `earlier`
+    pub fn changes_since(&self, earlier: &Snapshot) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .0
+            .iter()
+            .filter(|(path, stamp)| earlier.0.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .chain(earlier.0.keys().filter(|p| !self.0.contains_key(*p)).cloned())
+            .collect();
+        changed.sort();
+        changed
+    }
+}
+
+/// Polls a codebase for changes
+#[derive(Debug)]
+pub struct Watcher {
+    root: PathBuf,
+    filter: PathFilter,
+    skip: Vec<PathBuf>,
+    poll: Duration,
+    debounce: Duration,
+    snapshot: Snapshot,
+}
+
+impl Watcher {
+    /// Start watching from the codebase's current state
+    pub fn new(root: PathBuf, filter: PathFilter, skip: Vec<PathBuf>, poll: Duration, debounce: Duration) -> Self {
+        let snapshot = Snapshot::take(&root, &filter, &skip);
+        Self {
+            root,
+            filter,
+            skip,
+            poll,
+            debounce,
+            snapshot,
+        }
+    }
+
+    /// Block until files change and then stay unchanged for the debounce
+    /// period; returns every file changed since the last call
+    pub fn wait_for_changes(&mut self) -> Vec<PathBuf> {
+        let mut latest = self.snapshot.clone();
+        let mut quiet_since: Option<Instant> = None;
+        loop {
+            std::thread::sleep(self.poll);
+            let current = Snapshot::take(&self.root, &self.filter, &self.skip);
+            if current != latest {
+                debug!("{} files changed, waiting for the tree to settle", current.changes_since(&latest).len());
+                latest = current;
+                quiet_since = Some(Instant::now());
+            } else if quiet_since.is_some_and(|t| t.elapsed() >= self.debounce) {
+                let changed = latest.changes_since(&self.snapshot);
+                self.snapshot = latest;
+                if !changed.is_empty() {
+                    return changed;
+                }
+                // Changed and changed back
+                quiet_since = None;
+                latest = self.snapshot.clone();
+            }
+        }
+    }
+}
+
+/// What a cycle did with one language's index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The new index replaced the published one
+    Published(PathBuf),
+    /// The indexer failed; the previous index is kept
+    Failed(String),
+    /// The indexer produced something that is not a SCIP index; the
+    /// previous index is kept
+    Invalid(String),
+}
+
+/// Move each successful result's index from the staging directory into
+/// `publish_dir`, once it decodes as a SCIP index
+pub fn publish(results: &[IndexerResult], publish_dir: &Path) -> Vec<(Language, Outcome)> {
+    // Languages sharing one index (.NET) publish it once
+    let mut published: HashMap<&Path, Outcome> = HashMap::new();
+    results
+        .iter()
+        .map(|result| {
+            if !result.success {
+                let error = result.error.clone().unwrap_or_else(|| "indexer failed".to_string());
+                return (result.language, Outcome::Failed(error));
+            }
+            let outcome = published
+                .entry(&result.scip_path)
+                .or_insert_with(|| match publish_index(&result.scip_path, publish_dir) {
+                    Ok(path) => Outcome::Published(path),
+                    Err(e) => Outcome::Invalid(format!("{:#}", e)),
+                })
+                .clone();
+            (result.language, outcome)
+        })
+        .collect()
+}
+
+fn publish_index(staged: &Path, publish_dir: &Path) -> Result<PathBuf> {
+    scip::Index::read(staged)?;
+    let name = staged.file_name().context("index path has no file name")?;
+    let dest = publish_dir.join(name);
+    std::fs::create_dir_all(publish_dir).with_context(|| format!("Failed to create {:?}", publish_dir))?;
+    // Renaming replaces the published index atomically; across file
+    // systems, copy next to it first
+    if std::fs::rename(staged, &dest).is_err() {
+        outdir::copy_atomic(staged, &dest)?;
+    }
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_changes() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        let write = |rel: &str, content: &str| {
+            let path = root.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("main.go", "package main");
+        write("old.go", "package main");
+        write("node_modules/x/index.js", "");
+        write("out/go.scip", "");
+        let filter = PathFilter::new(&[], &["node_modules/**".to_string()]);
+        let skip = [PathBuf::from("out")];
+
+        let before = Snapshot::take(root, &filter, &skip);
+        write("main.go", "package main // edited");
+        write("new.go", "package main");
+        std::fs::remove_file(root.join("old.go")).unwrap();
+        write("node_modules/x/index.js", "changed");
+        write("out/go.scip", "changed");
+        let after = Snapshot::take(root, &filter, &skip);
+
+        let paths = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(after.changes_since(&before), paths(&["main.go", "new.go", "old.go"]));
+        assert!(after.changes_since(&after).is_empty());
+    }
+
+    #[test]
+    fn test_publish_keeps_previous_index_unless_valid() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let staging = temp.path().join("staging");
+        let published = temp.path().join("published");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::create_dir_all(&published).unwrap();
+        std::fs::write(published.join("go.scip"), "previous").unwrap();
+        std::fs::write(published.join("python.scip"), "previous").unwrap();
+
+        let mut index = scip::Index::default();
+        index.set_project_root("file:///src");
+        index.write(&staging.join("go.scip")).unwrap();
+        // A truncated length-delimited field does not decode
+        std::fs::write(staging.join("python.scip"), [0x12, 0x40, 0x01]).unwrap();
+        let result = |language, name: &str| IndexerResult {
+            language,
+            scip_path: staging.join(name),
+            success: true,
+            error: None,
+            reused: false,
+            cached: false,
+            patched: false,
+            partial: false,
+        };
+        let results = [
+            result(Language::Go, "go.scip"),
+            result(Language::Python, "python.scip"),
+            IndexerResult { success: false, error: Some("exit 1".into()), ..result(Language::Ruby, "ruby.scip") },
+        ];
+
+        let outcomes = publish(&results, &published);
+        assert_eq!(outcomes[0], (Language::Go, Outcome::Published(published.join("go.scip"))));
+        assert!(matches!(outcomes[1], (Language::Python, Outcome::Invalid(_))));
+        assert_eq!(outcomes[2], (Language::Ruby, Outcome::Failed("exit 1".into())));
+        assert_eq!(scip::Index::read(&published.join("go.scip")).unwrap().project_root().as_deref(), Some("file:///src"));
+        assert_eq!(std::fs::read_to_string(published.join("python.scip")).unwrap(), "previous");
+    }
+}
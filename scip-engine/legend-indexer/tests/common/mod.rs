@@ -3,6 +3,7 @@ use std::fs;
 use std::path::Path;
 
 /// Skip a test if the given path does not exist (e.g. a repo not cloned locally).
+#[allow(unused_macros)]
 macro_rules! skip_unless {
     ($path:expr) => {
         if !std::path::Path::new($path).exists() {
@@ -11,8 +12,10 @@ macro_rules! skip_unless {
         }
     };
 }
+#[allow(unused_imports)]
 pub(crate) use skip_unless;
 
+#[allow(dead_code)]
 pub fn create_file(dir: &Path, relative: &str, content: &str) {
     let path = dir.join(relative);
     if let Some(parent) = path.parent() {
@@ -21,6 +24,17 @@ pub fn create_file(dir: &Path, relative: &str, content: &str) {
     fs::write(&path, content).unwrap();
 }
 
+#[allow(dead_code)]
 pub fn find_lang(langs: &[LanguageInfo], lang: Language) -> Option<&LanguageInfo> {
     langs.iter().find(|i| i.language == lang)
 }
+
+/// Install a shell script as a fake bundled indexer in `indexers_dir`.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn install_fake_indexer(indexers_dir: &Path, name: &str, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    let path = indexers_dir.join(name);
+    create_file(indexers_dir, name, &format!("#!/bin/sh\n{}\n", script));
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
@@ -3,7 +3,7 @@
 mod common;
 
 use common::create_file;
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
@@ -18,8 +18,7 @@ fn create_typescript_project(dir: &TempDir) {
 
 #[test]
 fn test_cli_help() {
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("--help")
         .assert()
         .success()
@@ -28,8 +27,7 @@ fn test_cli_help() {
 
 #[test]
 fn test_cli_version() {
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("--version")
         .assert()
         .success()
@@ -40,8 +38,7 @@ fn test_cli_version() {
 fn test_detect_empty_directory() {
     let temp_dir = TempDir::new().unwrap();
 
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("detect")
         .arg(temp_dir.path())
         .assert()
@@ -51,8 +48,7 @@ fn test_detect_empty_directory() {
 
 #[test]
 fn test_check_indexers() {
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("check-indexers")
         .assert()
         .success()
@@ -68,8 +64,7 @@ fn test_language_filter() {
 
     fs::write(temp_dir.path().join("script.py"), "print('hello')").unwrap();
 
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("detect")
         .arg(temp_dir.path())
         .assert()
@@ -85,8 +80,7 @@ fn test_exclude_patterns() {
 
     create_file(temp_dir.path(), "node_modules/dep/index.ts", "export const x = 1;");
 
-    Command::cargo_bin("legend-indexer")
-        .unwrap()
+    cargo_bin_cmd!("legend-indexer")
         .arg("detect")
         .arg(temp_dir.path())
         .assert()
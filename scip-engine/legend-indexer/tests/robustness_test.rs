@@ -7,8 +7,9 @@ mod common;
 
 use common::{create_file, find_lang};
 use legend_indexer::config::Config;
-use legend_indexer::detect::{Language, LanguageDetector};
+use legend_indexer::detect::{Language, LanguageDetector, SymlinkPolicy};
 use legend_indexer::orchestrate::IndexerOrchestrator;
+use legend_indexer::pyenv::PythonEnvKind;
 use std::fs;
 use tempfile::TempDir;
 
@@ -29,17 +30,31 @@ mod bug_fixes {
         fs::write(&stale, b"stale data").unwrap();
         assert!(stale.exists());
 
-        // Constructing a new orchestrator should clean it
-        let _orch = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+        // It stays until a run completes, then is removed
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+        assert!(stale.exists(), "Previous indexes are kept until the run is done");
+        orch.run_all(&[]);
         assert!(!stale.exists(), "Stale .scip file should have been removed");
 
         // Non-.scip files should be preserved
         let keep = legend_dir.join("notes.txt");
         fs::write(&keep, "keep me").unwrap();
-        let _orch2 = IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap();
+        IndexerOrchestrator::new(temp.path().to_path_buf(), None).unwrap().run_all(&[]);
         assert!(keep.exists(), "Non-.scip files should be preserved");
     }
 
+    #[test]
+    fn test_work_dir_not_detected() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "app.js", "module.exports = 1;");
+        create_file(temp.path(), ".legend-indexer/typescript/tsconfig.json", "{}");
+        create_file(temp.path(), ".legend-indexer/compdb/cmake-build/CMakeCCompilerId.c", "int main() {}");
+
+        let report = LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let langs: Vec<Language> = report.languages.iter().map(|l| l.language).collect();
+        assert_eq!(langs, vec![Language::JavaScript]);
+    }
+
     #[test]
     fn test_scip_output_stem_unique_per_language() {
         let stems: Vec<&str> = Language::ALL.iter().map(|l| l.scip_output_stem()).collect();
@@ -88,7 +103,11 @@ mod bug_fixes {
         let ts = find_lang(&report.languages, Language::TypeScript);
         assert!(ts.is_some());
         assert!(ts.unwrap().file_count >= 1);
-        assert!(report.walk_errors >= 1, "Broken symlink should produce walk error, got {}", report.walk_errors);
+        assert_eq!(
+            report.walk_errors.broken_symlinks, 1,
+            "Broken symlink should produce walk error, got {:?}",
+            report.walk_errors
+        );
     }
 }
 
@@ -170,6 +189,30 @@ mod detection {
         assert_eq!(filtered[0].language, Language::TypeScript);
     }
 
+    #[test]
+    fn test_include_and_negated_exclude_patterns() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "services/billing/main.go", "package main");
+        create_file(temp.path(), "services/billing/build/generated-sources/Api.java", "class Api {}");
+        create_file(temp.path(), "services/billing/build/classes/Stale.java", "class Stale {}");
+        create_file(temp.path(), "services/auth/main.py", "x = 1");
+
+        let mut config = Config {
+            include_patterns: vec!["services/billing/**".to_string()],
+            ..Config::default()
+        };
+        config.exclude_patterns.push("!services/billing/build/generated-sources/**".to_string());
+
+        let report = LanguageDetector::from_filter(config.path_filter())
+            .detect(temp.path())
+            .unwrap();
+
+        assert!(find_lang(&report.languages, Language::Go).is_some());
+        assert!(find_lang(&report.languages, Language::Python).is_none(), "outside the include");
+        let java = find_lang(&report.languages, Language::Java);
+        assert_eq!(java.map(|j| j.file_count), Some(1), "only the re-included generated source");
+    }
+
     #[test]
     fn test_all_default_exclude_patterns() {
         let temp = TempDir::new().unwrap();
@@ -286,6 +329,198 @@ mod detection {
         assert!(!ts.config_files.is_empty(), "tsconfig.json should be a config file for TS");
     }
 
+    #[test]
+    fn test_config_only_language_reported_as_configured_empty() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "app.py", "x = 1");
+        create_file(temp.path(), "tools/go.mod", "module example.com/tools");
+        create_file(temp.path(), "mobile/pubspec.yaml", "name: app");
+        create_file(temp.path(), "mobile/build/lib/main.dart", "void main() {}");
+
+        let detector = LanguageDetector::new(&["build/**".to_string()]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        assert_eq!(report.languages.len(), 1);
+        let empty: Vec<Language> = report.configured_empty.iter().map(|i| i.language).collect();
+        assert_eq!(empty, vec![Language::Go, Language::Dart]);
+        assert!(report.configured_empty.iter().all(|i| i.file_count == 0));
+        let go = find_lang(&report.configured_empty, Language::Go).unwrap();
+        assert_eq!(go.config_files, vec![temp.path().join("tools/go.mod")]);
+    }
+
+    #[test]
+    fn test_shared_config_attached_to_sibling_language() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "package.json", "{}");
+        create_file(temp.path(), "src/index.ts", "export const x = 1;");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        assert!(report.configured_empty.is_empty(), "package.json belongs to the TS project");
+        let ts = find_lang(&report.languages, Language::TypeScript).unwrap();
+        assert_eq!(ts.config_files, vec![temp.path().join("package.json")]);
+    }
+
+    #[test]
+    fn test_gradle_kotlin_dsl_is_kotlin_config() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "build.gradle.kts", "plugins { kotlin(\"jvm\") }");
+        create_file(temp.path(), "src/main/kotlin/App.kt", "fun main() {}");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let kotlin = find_lang(&report.languages, Language::Kotlin).unwrap();
+        assert_eq!(kotlin.config_files, vec![temp.path().join("build.gradle.kts")]);
+        assert!(find_lang(&report.languages, Language::Java).is_none());
+        assert!(report.configured_empty.is_empty());
+    }
+
+    #[test]
+    fn test_manifests_attached_to_language_info() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module github.com/acme/api\n\ngo 1.22\n");
+        create_file(temp.path(), "main.go", "package main");
+        create_file(temp.path(), "web/package.json", r#"{"name":"web","version":"2.0.0"}"#);
+        create_file(temp.path(), "web/tsconfig.json", "{}");
+        create_file(temp.path(), "web/index.ts", "export {};");
+        create_file(temp.path(), "web/broken/package.json", "{ not json");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let go = find_lang(&report.languages, Language::Go).unwrap();
+        assert_eq!(go.manifests.len(), 1);
+        assert_eq!(go.manifests[0].name.as_deref(), Some("github.com/acme/api"));
+        assert_eq!(go.manifests[0].target_frameworks, vec!["go1.22"]);
+
+        // tsconfig.json is a config file but not a manifest; the broken
+        // package.json is skipped rather than failing detection
+        let ts = find_lang(&report.languages, Language::TypeScript).unwrap();
+        assert_eq!(ts.config_files.len(), 3);
+        assert_eq!(ts.manifests.len(), 1);
+        assert_eq!(ts.manifests[0].version.as_deref(), Some("2.0.0"));
+    }
+
+    #[test]
+    fn test_frameworks_detected_per_project() {
+        use legend_indexer::framework::{Confidence, Framework};
+
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "api/go.mod", "module acme/api\n\nrequire github.com/gin-gonic/gin v1.9.1\n");
+        create_file(temp.path(), "api/main.go", "package main");
+        create_file(temp.path(), "web/package.json", r#"{"dependencies":{"@nestjs/core":"^10.0.0"}}"#);
+        create_file(temp.path(), "web/nest-cli.json", "{}");
+        create_file(temp.path(), "web/src/main.ts", "export {};");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let found: Vec<(Framework, Confidence, std::path::PathBuf)> = report
+            .frameworks
+            .iter()
+            .map(|f| (f.framework, f.confidence, f.location.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Framework::Gin, Confidence::Medium, temp.path().join("api")),
+                (Framework::NestJs, Confidence::High, temp.path().join("web")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_additional_languages_and_config_markers() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "ios/Package.swift", "// swift-tools-version:5.9");
+        create_file(temp.path(), "ios/Sources/App.swift", "print(1)");
+        create_file(temp.path(), "svc/mix.exs", "defmodule Svc.MixProject do end");
+        create_file(temp.path(), "svc/lib/svc.ex", "defmodule Svc do end");
+        create_file(temp.path(), "hs/app.cabal", "name: app");
+        create_file(temp.path(), "hs/Main.hs", "main = pure ()");
+        create_file(temp.path(), "nvim/init.lua", "print(1)");
+        create_file(temp.path(), "zig/build.zig", "const std = @import(\"std\");");
+        create_file(temp.path(), "scripts/deploy.sh", "#!/bin/sh");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        for lang in [Language::Swift, Language::Elixir, Language::Haskell, Language::Lua, Language::Zig, Language::Shell] {
+            assert!(find_lang(&report.languages, lang).is_some(), "{:?} should be detected", lang);
+        }
+        for ext in ["swift", "ex", "exs", "hs", "lua", "zig", "sh"] {
+            assert!(
+                !report.unrecognized_extensions.iter().any(|e| e.extension == ext),
+                ".{} should be recognized",
+                ext
+            );
+        }
+        assert_eq!(find_lang(&report.languages, Language::Swift).unwrap().config_files.len(), 1);
+        assert_eq!(find_lang(&report.languages, Language::Haskell).unwrap().config_files.len(), 1);
+        assert!(!Language::Shell.has_scip_indexer());
+        assert_eq!(Language::parse("bash"), Some(Language::Shell));
+    }
+
+    #[test]
+    fn test_python_environment_recorded_in_manifest() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "pyproject.toml", "[project]\nname = \"svc\"\nversion = \"0.4.0\"\n");
+        create_file(temp.path(), "uv.lock", "");
+        create_file(temp.path(), ".venv/pyvenv.cfg", "home = /usr/bin");
+        create_file(temp.path(), "svc/app.py", "x = 1");
+        create_file(temp.path(), "tools/requirements.txt", "click\n");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let python = find_lang(&report.languages, Language::Python).unwrap();
+        let pyproject = python.manifests.iter().find(|m| m.name.as_deref() == Some("svc")).unwrap();
+        let env = pyproject.python_environment.as_ref().unwrap();
+        assert_eq!(env.kind, PythonEnvKind::Uv);
+        assert_eq!(env.prefix, temp.path().join(".venv"));
+
+        // tools/ has no environment of its own
+        let tools = python.manifests.iter().find(|m| m.path.ends_with("tools/requirements.txt")).unwrap();
+        assert!(tools.python_environment.is_none());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["languages"][0]["manifests"][0]["python_environment"]["kind"], "uv");
+    }
+
+    #[test]
+    fn test_dotnet_languages_and_project_files() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "App.sln", "");
+        create_file(temp.path(), "Api/Api.csproj", "<Project Sdk=\"Microsoft.NET.Sdk\"></Project>");
+        create_file(temp.path(), "Api/Program.cs", "class P {}");
+        create_file(temp.path(), "Legacy/Legacy.vbproj", "<Project Sdk=\"Microsoft.NET.Sdk\"><PropertyGroup><TargetFramework>net8.0</TargetFramework></PropertyGroup></Project>");
+        create_file(temp.path(), "Legacy/Module1.vb", "Module Module1\nEnd Module");
+        create_file(temp.path(), "Core/Core.fsproj", "<Project Sdk=\"Microsoft.NET.Sdk\"></Project>");
+        create_file(temp.path(), "Core/Library.fs", "module Core");
+        create_file(temp.path(), "Core/Library.fsi", "module Core");
+        create_file(temp.path(), "scripts/build.fsx", "printfn \"hi\"");
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let vb = find_lang(&report.languages, Language::VisualBasic).unwrap();
+        assert_eq!(vb.file_count, 1);
+        assert_eq!(vb.config_files, vec![temp.path().join("Legacy/Legacy.vbproj")]);
+        assert_eq!(vb.manifests[0].target_frameworks, vec!["net8.0"]);
+
+        let fsharp = find_lang(&report.languages, Language::FSharp).unwrap();
+        assert_eq!(fsharp.file_count, 3);
+        assert_eq!(fsharp.config_files, vec![temp.path().join("Core/Core.fsproj")]);
+
+        let csharp = find_lang(&report.languages, Language::CSharp).unwrap();
+        assert_eq!(csharp.config_files.len(), 2);
+        assert_eq!(Language::FSharp.scip_indexer(), Language::CSharp.scip_indexer());
+        assert_eq!(Language::parse("vbnet"), Some(Language::VisualBasic));
+        assert_eq!(Language::parse("f#"), Some(Language::FSharp));
+    }
+
     #[test]
     fn test_detection_determinism_10_runs() {
         let temp = TempDir::new().unwrap();
@@ -318,6 +553,109 @@ mod detection {
             );
         }
     }
+
+    #[test]
+    fn test_parallel_walk_report_byte_identical() {
+        let temp = TempDir::new().unwrap();
+        for dir in ["svc/a", "svc/b", "svc/c", "web", "tools"] {
+            create_file(temp.path(), &format!("{}/main.go", dir), "package main");
+            create_file(temp.path(), &format!("{}/go.mod", dir), "module example");
+            create_file(temp.path(), &format!("{}/index.ts", dir), "const x = 1;");
+            create_file(temp.path(), &format!("{}/notes.md", dir), "# notes");
+            create_file(temp.path(), &format!("{}/data.yaml", dir), "k: v");
+        }
+
+        let detector = LanguageDetector::new(&[]);
+        let snapshot = || {
+            let mut report = detector.detect(temp.path()).unwrap();
+            report.walk_duration_ms = 0;
+            serde_json::to_string(&report).unwrap()
+        };
+
+        let baseline = snapshot();
+        for run in 1..=10 {
+            assert_eq!(baseline, snapshot(), "Detection run {} produced a different report", run);
+        }
+
+        let report = detector.detect(temp.path()).unwrap();
+        let go = find_lang(&report.languages, Language::Go).unwrap();
+        let mut sorted = go.config_files.clone();
+        sorted.sort();
+        assert_eq!(go.config_files, sorted, "Config files should be in path order");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_terminates() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "pkg/app.ts", "const x = 1;");
+        std::os::unix::fs::symlink(temp.path(), temp.path().join("pkg/loop")).unwrap();
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        let ts = find_lang(&report.languages, Language::TypeScript).unwrap();
+        assert_eq!(ts.file_count, 1);
+        assert_eq!(report.walk_errors.symlink_loops, 1, "Loop should be reported once as a walk error");
+        assert_eq!(report.walk_errors.total(), 1);
+    }
+
+    #[cfg(unix)]
+    fn create_escaping_symlink() -> (TempDir, TempDir) {
+        let outside = TempDir::new().unwrap();
+        create_file(outside.path(), "lib/external.ts", "export const e = 1;");
+
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "src/app.ts", "const x = 1;");
+        std::os::unix::fs::symlink(outside.path().join("lib"), temp.path().join("src/vendored")).unwrap();
+        (temp, outside)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escaping_root_skipped_by_default() {
+        let (temp, _outside) = create_escaping_symlink();
+
+        let detector = LanguageDetector::new(&[]);
+        let report = detector.detect(temp.path()).unwrap();
+
+        assert_eq!(report.symlink_policy, SymlinkPolicy::WithinRoot);
+        assert_eq!(find_lang(&report.languages, Language::TypeScript).unwrap().file_count, 1);
+        assert_eq!(report.escaping_symlinks.len(), 1);
+        assert_eq!(report.escaping_symlinks[0].path, std::path::Path::new("src/vendored"));
+        assert!(!report.escaping_symlinks[0].followed);
+        assert_eq!(report.skipped_symlinks, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy_all_follows_escaping_links() {
+        let (temp, _outside) = create_escaping_symlink();
+
+        let detector = LanguageDetector::new(&[]).with_symlink_policy(SymlinkPolicy::All);
+        let report = detector.detect(temp.path()).unwrap();
+
+        assert_eq!(find_lang(&report.languages, Language::TypeScript).unwrap().file_count, 2);
+        assert_eq!(report.escaping_symlinks.len(), 1);
+        assert!(report.escaping_symlinks[0].followed);
+        assert_eq!(report.skipped_symlinks, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy_never_skips_all_links() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "real.ts", "export const x = 1;");
+        std::os::unix::fs::symlink(temp.path().join("real.ts"), temp.path().join("link.ts")).unwrap();
+        std::os::unix::fs::symlink(temp.path().join("missing"), temp.path().join("broken")).unwrap();
+
+        let detector = LanguageDetector::new(&[]).with_symlink_policy(SymlinkPolicy::Never);
+        let report = detector.detect(temp.path()).unwrap();
+
+        assert_eq!(find_lang(&report.languages, Language::TypeScript).unwrap().file_count, 1);
+        assert_eq!(report.skipped_symlinks, 2);
+        assert_eq!(report.walk_errors.total(), 0, "Unfollowed links should not be resolved");
+    }
 }
 
 // ===========================================================================
@@ -343,6 +681,146 @@ mod orchestration {
         assert!(!temp.path().join(".legend-indexer").exists());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_go_modules_indexed_separately_and_merged() {
+        use legend_indexer::scip::{Document, Field, Index};
+
+        let temp = TempDir::new().unwrap();
+        let indexers = TempDir::new().unwrap();
+        // The fake scip-go copies the module's fixture to `--output <path>`
+        common::install_fake_indexer(indexers.path(), "scip-go", "cp \"$PWD/fixture.scip\" \"$2\"");
+        for module in ["api", "worker"] {
+            create_file(temp.path(), &format!("{}/go.mod", module), &format!("module acme/{}\n", module));
+            create_file(temp.path(), &format!("{}/main.go", module), "package main");
+            let fixture = Index {
+                documents: vec![Document { fields: vec![Field::bytes(1, b"main.go".to_vec())] }],
+                ..Default::default()
+            };
+            fixture.write(&temp.path().join(module).join("fixture.scip")).unwrap();
+        }
+
+        let report = LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), Some(indexers.path().to_path_buf())).unwrap();
+        let results = orch.run_all(&report.languages);
+
+        assert!(results[0].success, "{:?}", results[0].error);
+        let merged = Index::read(&results[0].scip_path).unwrap();
+        let paths: Vec<String> = merged.documents.iter().map(Document::relative_path).collect();
+        assert_eq!(paths, vec!["api/main.go", "worker/main.go"]);
+        assert!(merged.project_root().unwrap().starts_with("file://"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exclusions_forwarded_to_indexer_and_index() {
+        use legend_indexer::pathfilter::PathFilter;
+        use legend_indexer::scip::{Document, Field, Index};
+
+        let temp = TempDir::new().unwrap();
+        let indexers = TempDir::new().unwrap();
+        // The fake scip-typescript records its arguments and copies the fixture to `--output <path>`
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-typescript",
+            "echo \"$@\" > \"$PWD/args\"; cp \"$PWD/fixture.scip\" \"$4\"",
+        );
+        create_file(temp.path(), "tsconfig.json", "{ \"compilerOptions\": { \"strict\": true }, }");
+        let mut fixture = Index::default();
+        for path in ["src/app.ts", "dist/app.js", "src/gen/api.ts", "src/gen/keep.ts"] {
+            create_file(temp.path(), path, "export {}");
+            fixture.documents.push(Document { fields: vec![Field::bytes(1, path.as_bytes().to_vec())] });
+        }
+        fixture.write(&temp.path().join("fixture.scip")).unwrap();
+
+        let excludes: Vec<String> = ["src/gen/**", "!src/gen/keep.ts", "dist/**"].map(String::from).to_vec();
+        let filter = PathFilter::new(&[], &excludes);
+        let report = LanguageDetector::from_filter(filter.clone()).detect(temp.path()).unwrap();
+        let ts = report.languages.iter().find(|l| l.language == Language::TypeScript).unwrap();
+        assert_eq!(ts.file_count, 2);
+
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), Some(indexers.path().to_path_buf()))
+            .unwrap()
+            .with_path_filter(filter);
+        let results = orch.run_all(std::slice::from_ref(ts));
+        assert!(results[0].success, "{:?}", results[0].error);
+
+        // scip-typescript ran on a generated tsconfig extending the project's
+        let args = fs::read_to_string(temp.path().join("args")).unwrap();
+        let generated_dir = args.split_whitespace().nth(1).unwrap();
+        let generated: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(std::path::Path::new(generated_dir).join("tsconfig.json")).unwrap())
+                .unwrap();
+        assert!(generated["extends"].as_str().unwrap().ends_with("tsconfig.json"));
+        let exclude = generated["exclude"].to_string();
+        assert!(exclude.contains("**/dist"), "{}", exclude);
+        assert!(!exclude.contains("src/gen"), "overridden by a negation, left to document filtering: {}", exclude);
+
+        // Documents the patterns exclude are dropped from the index
+        let index = Index::read(&results[0].scip_path).unwrap();
+        let paths: Vec<String> = index.documents.iter().map(Document::relative_path).collect();
+        assert_eq!(paths, vec!["src/app.ts", "src/gen/keep.ts"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rust_cargo_metadata_failure_reported() {
+        let temp = TempDir::new().unwrap();
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "rust-analyzer", "exit 0");
+        create_file(temp.path(), "crates/app/Cargo.toml", "[package]\nname = \"app\"\nversion = \"0.1.0\"\n[dependencies]\nmissing-local = { path = \"../nope\" }\n");
+        create_file(temp.path(), "crates/app/src/lib.rs", "");
+
+        let report = LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let orch = IndexerOrchestrator::new(temp.path().to_path_buf(), Some(indexers.path().to_path_buf())).unwrap();
+        let results = orch.run_all(&report.languages);
+
+        assert!(!results[0].success);
+        let error = results[0].error.as_deref().unwrap();
+        assert!(error.contains("crates/app"), "{}", error);
+        assert!(error.contains("cargo metadata failed"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_matches_run_without_executing() {
+        use legend_indexer::orchestrate::BinarySource;
+
+        let temp = TempDir::new().unwrap();
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "rust-analyzer", "touch \"$PWD/ran\"");
+        common::install_fake_indexer(indexers.path(), "scip-dotnet", "touch \"$PWD/ran\"");
+        create_file(temp.path(), ".cargo/config.toml", "[source.vendored]\ndirectory = \"vendor\"\n");
+        create_file(temp.path(), "engine/Cargo.toml", "[workspace]\nmembers = [\"core\"]\n");
+        create_file(temp.path(), "engine/core/Cargo.toml", "[package]\nname = \"core\"\n");
+        create_file(temp.path(), "engine/core/src/lib.rs", "");
+        create_file(temp.path(), "tools/Cargo.toml", "[package]\nname = \"tools\"\n");
+        create_file(temp.path(), "tools/src/main.rs", "fn main() {}");
+        create_file(temp.path(), "App.sln", "");
+        create_file(temp.path(), "Program.cs", "class P {}");
+        create_file(temp.path(), "Util.fs", "module Util");
+
+        let report = LanguageDetector::new(&[]).detect(temp.path()).unwrap();
+        let orch = IndexerOrchestrator::for_plan(temp.path().to_path_buf(), Some(indexers.path().to_path_buf()));
+        let plans = orch.plan_all(&report.languages);
+
+        let rust = plans.iter().find(|p| p.language == Language::Rust).unwrap();
+        assert_eq!(rust.invocations.len(), 2);
+        assert_eq!(rust.invocations[0].working_dir, temp.path().join("engine"));
+        assert_eq!(rust.invocations[0].source, BinarySource::Bundled);
+        assert_eq!(rust.invocations[0].env.get("CARGO_NET_OFFLINE").map(String::as_str), Some("true"));
+        assert!(rust.steps.iter().any(|s| s.starts_with("merge 2 unit indexes")));
+
+        let csharp = plans.iter().find(|p| p.language == Language::CSharp).unwrap();
+        assert!(csharp.invocations[0].args.contains(&temp.path().join("App.sln").to_string_lossy().into_owned()));
+        let fsharp = plans.iter().find(|p| p.language == Language::FSharp).unwrap();
+        assert_eq!(fsharp.shared_with, Some(Language::CSharp));
+        assert_eq!(fsharp.output, csharp.output);
+
+        assert!(!temp.path().join(".legend-indexer").exists());
+        assert!(!temp.path().join("ran").exists() && !temp.path().join("engine/ran").exists());
+    }
+
     #[test]
     fn test_output_dir_preserved_without_cleanup() {
         let temp = TempDir::new().unwrap();
@@ -358,7 +836,7 @@ mod orchestration {
 // ===========================================================================
 mod cli {
     use super::*;
-    use assert_cmd::Command;
+    use assert_cmd::cargo::cargo_bin_cmd;
     use predicates::prelude::*;
 
     #[test]
@@ -368,15 +846,13 @@ mod cli {
         create_file(temp.path(), "script.py", "x = 1");
         create_file(temp.path(), "main.go", "package main");
 
-        let output1 = Command::cargo_bin("legend-indexer")
-            .unwrap()
+        let output1 = cargo_bin_cmd!("legend-indexer")
             .arg("detect")
             .arg(temp.path())
             .output()
             .unwrap();
 
-        let output2 = Command::cargo_bin("legend-indexer")
-            .unwrap()
+        let output2 = cargo_bin_cmd!("legend-indexer")
             .arg("detect")
             .arg(temp.path())
             .output()
@@ -406,8 +882,7 @@ mod cli {
         create_file(temp.path(), "script.py", "x = 1");
         create_file(temp.path(), "main.go", "package main");
 
-        Command::cargo_bin("legend-indexer")
-            .unwrap()
+        cargo_bin_cmd!("legend-indexer")
             .arg("detect")
             .arg(temp.path())
             .assert()
@@ -418,9 +893,926 @@ mod cli {
     }
 
     #[test]
-    fn test_cli_nonexistent_path() {
-        let result = Command::cargo_bin("legend-indexer")
+    fn test_cli_plan_json() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "pyproject.toml", "[project]\nname = \"app\"\nversion = \"1.0\"\n");
+        create_file(temp.path(), "app.py", "x = 1");
+
+        let output = cargo_bin_cmd!("legend-indexer")
+            .args(["--format", "json", "plan"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let python = &plan["languages"][0];
+        assert_eq!(python["language"], "python");
+        assert!(python["output"].as_str().unwrap().ends_with("python.scip"));
+        if python["skipped"].is_null() {
+            let args = python["invocations"][0]["args"].as_array().unwrap();
+            assert!(args.iter().any(|a| a == "--project-name"));
+        } else {
+            assert_eq!(python["skipped"], "Indexer not installed");
+        }
+        assert!(!temp.path().join(".legend-indexer").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_doctor_reports_outdated_indexer() {
+        let temp = TempDir::new().unwrap();
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "scip-go", "echo 'scip-go v0.0.3'");
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+
+        let output = cargo_bin_cmd!("legend-indexer")
+            .arg("--indexers-path")
+            .arg(indexers.path())
+            .args(["--format", "json", "doctor"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(report["command"], "doctor");
+        let checks = report["checks"].as_array().unwrap();
+        let scip_go = checks.iter().find(|c| c["name"] == "scip-go").unwrap();
+        assert_eq!(scip_go["status"], "fail");
+        assert!(scip_go["detail"].as_str().unwrap().contains("0.1.0 or newer is required"));
+        assert!(scip_go["remediation"].as_str().unwrap().contains("go install"));
+        assert!(checks.iter().any(|c| c["name"] == "output directory" && c["status"] == "ok"));
+        assert!(!checks.iter().any(|c| c["name"] == "scip-typescript"));
+        assert!(!temp.path().join(".legend-indexer").exists());
+    }
+
+    #[test]
+    fn test_cli_detect_applies_path_filters() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "src/app.go", "package main");
+        create_file(temp.path(), "legacy/old.py", "x = 1");
+        create_file(temp.path(), "web/node_modules/dep/index.ts", "export {}");
+
+        let output = cargo_bin_cmd!("legend-indexer")
+            .args(["--format", "json", "--exclude", "legacy/**", "detect"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let languages: Vec<&str> = json["detection"]["languages"]
+            .as_array()
             .unwrap()
+            .iter()
+            .map(|l| l["language"].as_str().unwrap())
+            .collect();
+        assert_eq!(languages, vec!["go"], "--exclude and the default excludes apply as in analyze");
+    }
+
+    #[test]
+    fn test_cli_json_format_documents() {
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "lib/app.ex", "defmodule App do end");
+
+        let json = |args: &[&str]| -> serde_json::Value {
+            let output = cargo_bin_cmd!("legend-indexer")
+                .args(["--format", "json", "--fail-on", "none"])
+                .args(args)
+                .output()
+                .unwrap();
+            // Elixir has no SCIP indexer, so analyze has nothing to index (exit 3)
+            assert!(matches!(output.status.code(), Some(0 | 3)), "{}", String::from_utf8_lossy(&output.stderr));
+            serde_json::from_slice(&output.stdout).expect("stdout is one JSON document")
+        };
+        let path = temp.path().to_str().unwrap();
+
+        let detect = json(&["detect", path]);
+        assert_eq!(detect["schema_version"], 1);
+        assert_eq!(detect["command"], "detect");
+        assert_eq!(detect["detection"]["languages"][0]["language"], "elixir");
+
+        let analyze = json(&["analyze", path]);
+        assert_eq!(analyze["command"], "analyze");
+        assert_eq!(analyze["results"][0]["success"], false);
+        assert_eq!(analyze["results"][0]["error"], "Detected, no SCIP indexer available");
+        assert_eq!(analyze["exit_code"], 3);
+        assert_eq!(analyze["produced"], serde_json::json!([]));
+        let warnings = analyze["warnings"].as_array().unwrap();
+        assert!(warnings.iter().any(|w| w == "No detected language has a SCIP indexer"));
+
+        let indexers = json(&["check-indexers"]);
+        let go = indexers["indexers"].as_array().unwrap().iter().find(|i| i["language"] == "go").unwrap();
+        assert_eq!(go["indexer"], "scip-go");
+        assert!(go["available"].is_boolean());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_analyze_exit_codes() {
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "scip-go", ": > \"$2\"");
+        common::install_fake_indexer(indexers.path(), "scip-ruby", "exit 1");
+        let analyze = |path: &std::path::Path, extra: &[&str]| {
+            cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .args(extra)
+                .arg("analyze")
+                .arg(path)
+                .output()
+                .unwrap()
+                .status
+                .code()
+        };
+
+        let empty = TempDir::new().unwrap();
+        assert_eq!(analyze(empty.path(), &[]), Some(3));
+        assert_eq!(analyze(&empty.path().join("missing"), &[]), Some(2));
+
+        let go = TempDir::new().unwrap();
+        create_file(go.path(), "go.mod", "module acme/app\n");
+        create_file(go.path(), "main.go", "package main");
+        assert_eq!(analyze(go.path(), &[]), Some(0));
+        assert_eq!(analyze(go.path(), &["-l", "python"]), Some(4));
+
+        // A language without a SCIP indexer only fails the run under `any`
+        create_file(go.path(), "lib/app.ex", "defmodule App do end");
+        assert_eq!(analyze(go.path(), &[]), Some(0));
+        assert_eq!(analyze(go.path(), &["--fail-on", "any"]), Some(5));
+        assert_eq!(analyze(go.path(), &["-l", "elixir"]), Some(3), "nothing to index is not a failure");
+
+        create_file(go.path(), "app.rb", "puts 1");
+        assert_eq!(analyze(go.path(), &[]), Some(5));
+        assert_eq!(analyze(go.path(), &["--fail-on", "none"]), Some(0));
+        assert_eq!(analyze(go.path(), &["-l", "ruby"]), Some(6));
+        assert_eq!(analyze(go.path(), &["-l", "ruby", "--fail-on", "none"]), Some(0));
+    }
+
+    #[test]
+    fn test_cli_failed_go_module_is_partial() {
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-go",
+            "case \"$PWD\" in */broken) echo 'build failed' >&2; exit 1;; esac\n: > \"$2\"",
+        );
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "api/go.mod", "module acme/api\n");
+        create_file(temp.path(), "api/main.go", "package main");
+        create_file(temp.path(), "broken/go.mod", "module acme/broken\n");
+        create_file(temp.path(), "broken/main.go", "package main");
+
+        let output = cargo_bin_cmd!("legend-indexer")
+            .arg("--indexers-path")
+            .arg(indexers.path())
+            .args(["--format", "json", "analyze"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(5), "a missing module is a partial failure");
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let go = &json["results"][0];
+        assert_eq!(go["success"], true);
+        assert_eq!(go["partial"], true);
+        assert!(go["error"].as_str().unwrap().contains("broken"), "{}", go);
+        assert!(temp.path().join(".legend-indexer/go.scip").is_file());
+    }
+
+    #[test]
+    fn test_cli_languages_validation() {
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "scip-go", ": > \"$2\"");
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+        create_file(temp.path(), "app.rb", "puts 1");
+        create_file(temp.path(), "lib/app.ex", "defmodule App do end");
+        let run = |args: &[&str]| {
+            cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .args(args)
+                .output()
+                .unwrap()
+        };
+
+        // Unknown names are rejected up front, with suggestions
+        let result = run(&["-l", "golang,typscript", "analyze", temp.path().to_str().unwrap()]);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert_eq!(result.status.code(), Some(2), "{}", stderr);
+        assert!(stderr.contains("unknown language 'typscript' (did you mean 'typescript'?)"), "{}", stderr);
+        assert!(!temp.path().join(".legend-indexer").exists());
+
+        let result = run(&["-l", "typscript", "plan", temp.path().to_str().unwrap()]);
+        assert_eq!(result.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&result.stderr).contains("did you mean 'typescript'?"));
+
+        // Known but not detected is reported, not confused with a typo
+        let result = run(&["-l", "python", "analyze", temp.path().to_str().unwrap()]);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert_eq!(result.status.code(), Some(4), "{}", stderr);
+        assert!(stderr.contains("Python was requested but not detected"), "{}", stderr);
+        assert!(!stderr.contains("unknown language"), "{}", stderr);
+
+        // all-available keeps only detected languages whose indexer is installed
+        let result = run(&["-l", "all-available", "--format", "json", "plan", temp.path().to_str().unwrap()]);
+        assert!(result.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+        let planned: Vec<&str> = doc["languages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["language"].as_str().unwrap())
+            .collect();
+        assert_eq!(planned, vec!["go"]);
+
+        let result = run(&["-l", "all-available", "analyze", temp.path().to_str().unwrap()]);
+        assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_incremental_reuses_unchanged_languages() {
+        let indexers = TempDir::new().unwrap();
+        let runs = indexers.path().join("runs");
+        // Each fake indexer logs its run and writes an empty index to `--output <path>`
+        for (name, tag) in [("scip-go", "go"), ("scip-typescript", "ts")] {
+            common::install_fake_indexer(
+                indexers.path(),
+                name,
+                &format!(
+                    "echo {} >> {:?}\nwhile [ $# -gt 0 ]; do [ \"$1\" = --output ] && : > \"$2\"; shift; done",
+                    tag, runs
+                ),
+            );
+        }
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+        create_file(temp.path(), "web/app.ts", "export {}");
+        let analyze = |extra: &[&str]| {
+            let result = cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .args(extra)
+                .args(["--format", "json", "analyze"])
+                .arg(temp.path())
+                .output()
+                .unwrap();
+            assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+            let ran = fs::read_to_string(&runs).unwrap_or_default();
+            fs::remove_file(&runs).ok();
+            let mut ran: Vec<String> = ran.lines().map(String::from).collect();
+            ran.sort();
+            let doc: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+            let reused = doc["results"].as_array().unwrap().iter().filter(|r| r["reused"] == true).count();
+            (ran, reused)
+        };
+
+        assert_eq!(analyze(&["--incremental"]), (vec!["go".to_string(), "ts".to_string()], 0));
+        assert!(temp.path().join(".legend-indexer/index-manifest.json").is_file());
+        assert_eq!(analyze(&["--incremental"]), (vec![], 2));
+        assert!(temp.path().join(".legend-indexer/go.scip").is_file());
+
+        create_file(temp.path(), "main.go", "package main // changed");
+        assert_eq!(analyze(&["--incremental"]), (vec!["go".to_string()], 1));
+        // Files the patterns exclude are not inputs
+        create_file(temp.path(), "dist/bundle.ts", "export {}");
+        assert_eq!(analyze(&["--incremental"]), (vec![], 2));
+        // Different patterns invalidate every index
+        assert_eq!(analyze(&["--incremental", "-e", "web/legacy/**"]).1, 0);
+        // Without --incremental everything runs
+        assert_eq!(analyze(&[]).1, 0);
+
+        // --changed-since asks git instead of hashing
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .current_dir(temp.path())
+                .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success());
+            assert!(status.unwrap_or(false), "git {:?}", args);
+        };
+        git(&["init", "-q"]);
+        git(&["add", "-A"]);
+        git(&["commit", "-qm", "initial"]);
+        assert_eq!(analyze(&["--incremental"]).1, 0);
+        // The work directory is not gitignored, but what it holds is not an input
+        assert_eq!(analyze(&["--changed-since", "HEAD"]), (vec![], 2));
+        let manifest = temp.path().join(".legend-indexer/index-manifest.json");
+        let recorded: serde_json::Value = serde_json::from_str(&fs::read_to_string(&manifest).unwrap()).unwrap();
+        assert!(recorded.get("uncommitted").is_none(), "{}", recorded);
+        create_file(temp.path(), "web/app.ts", "export const changed = 1");
+        // scip-typescript first runs on the changed file alone; the fake's empty
+        // index has no document for it, so a full run follows
+        assert_eq!(analyze(&["--changed-since", "HEAD"]), (vec!["ts".to_string(), "ts".to_string()], 1));
+
+        // The diff is taken against the ref: the commit the indexes were built from
+        create_file(temp.path(), "main.go", "package main // committed");
+        git(&["add", "main.go"]);
+        git(&["commit", "-qm", "go change"]);
+        assert!(analyze(&["--changed-since", "HEAD~1"]).0.contains(&"go".to_string()));
+        // Any other ref falls back to fingerprints, rather than diffing against a
+        // commit the indexes do not reflect
+        assert_eq!(analyze(&["--changed-since", "HEAD~1"]), (vec![], 2));
+        // A manifest that does not record its commit falls back to fingerprints
+        let mut recorded: serde_json::Value = serde_json::from_str(&fs::read_to_string(&manifest).unwrap()).unwrap();
+        assert!(recorded.as_object_mut().unwrap().remove("commit").is_some());
+        fs::write(&manifest, recorded.to_string()).unwrap();
+        assert_eq!(analyze(&["--changed-since", "HEAD"]), (vec![], 2));
+
+        let result = cargo_bin_cmd!("legend-indexer")
+            .arg("--indexers-path")
+            .arg(indexers.path())
+            .args(["--changed-since", "no-such-ref", "analyze"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&result.stderr).contains("no-such-ref"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_incremental_patches_changed_documents() {
+        use legend_indexer::scip::{encode_fields, Document, Field, FieldValue, Index};
+
+        let indexers = TempDir::new().unwrap();
+        let dir = indexers.path().to_str().unwrap();
+        // A full run copies full.scip; a run with --target-only copies patch.scip
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-python",
+            &format!(
+                "echo \"$@\" > {dir}/args\ncase \" $* \" in *\" --target-only \"*) src=patch ;; *) src=full ;; esac\n\
+                 while [ $# -gt 2 ]; do shift; done\ncp {dir}/$src.scip \"$2\""
+            ),
+        );
+        let fixture = |name: &str, docs: &[(&str, &str)]| {
+            let mut index = Index::default();
+            for (path, version) in docs {
+                index.documents.push(Document {
+                    fields: vec![Field::bytes(1, path.as_bytes().to_vec()), Field::bytes(4, version.as_bytes().to_vec())],
+                });
+            }
+            index.write(&indexers.path().join(name)).unwrap();
+        };
+        fixture("full.scip", &[("pkg/a.py", "v1"), ("pkg/b.py", "v1"), ("c.py", "v1")]);
+        // The partial run also emits an unchanged file, which must not replace the previous document
+        fixture("patch.scip", &[("pkg/a.py", "v2"), ("pkg/b.py", "v2")]);
+
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "pkg/a.py", "a = 1");
+        create_file(temp.path(), "pkg/b.py", "b = 1");
+        create_file(temp.path(), "c.py", "c = 1");
+        let analyze = || {
+            let result = cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .args(["--incremental", "--format", "json", "analyze"])
+                .arg(temp.path())
+                .output()
+                .unwrap();
+            assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+            let doc: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+            let index = Index::read(&temp.path().join(".legend-indexer/python.scip")).unwrap();
+            let docs: Vec<(String, String)> = index
+                .documents
+                .iter()
+                .map(|d| {
+                    let version = d.fields.iter().find_map(|f| match &f.value {
+                        FieldValue::Bytes(b) if f.number == 4 => Some(String::from_utf8_lossy(b).into_owned()),
+                        _ => None,
+                    });
+                    (d.relative_path(), version.unwrap())
+                })
+                .collect();
+            (doc["results"][0]["patched"].as_bool().unwrap(), docs)
+        };
+        let version = |docs: &[(String, String)], path: &str| {
+            docs.iter().find(|(p, _)| p == path).map(|(_, v)| v.clone())
+        };
+
+        let (patched, docs) = analyze();
+        assert!(!patched);
+        assert_eq!(docs.len(), 3);
+
+        create_file(temp.path(), "pkg/a.py", "a = 2");
+        fs::remove_file(temp.path().join("c.py")).unwrap();
+        let (patched, docs) = analyze();
+        assert!(patched);
+        assert!(fs::read_to_string(indexers.path().join("args")).unwrap().contains("--target-only pkg/a.py"));
+        assert_eq!(docs.len(), 2, "{:?}", docs);
+        assert_eq!(version(&docs, "pkg/a.py").as_deref(), Some("v2"));
+        assert_eq!(version(&docs, "pkg/b.py").as_deref(), Some("v1"));
+        assert_eq!(version(&docs, "c.py"), None);
+
+        // A changed file defining a new symbol could resolve references elsewhere: full run
+        let mut patch = Index::read(&indexers.path().join("patch.scip")).unwrap();
+        let symbol = encode_fields(&[Field::bytes(1, b"scip-python python acme 1.0 pkg/a/f().".to_vec())]);
+        patch.documents[0].fields.push(Field::bytes(3, symbol));
+        patch.write(&indexers.path().join("patch.scip")).unwrap();
+        create_file(temp.path(), "pkg/a.py", "a = 3\ndef f(): pass");
+        let (patched, docs) = analyze();
+        assert!(!patched);
+        assert_eq!(docs.len(), 3);
+
+        // A changed config file can affect every document: full run
+        create_file(temp.path(), "pyproject.toml", "[project]\nname = \"acme\"\n");
+        let (patched, docs) = analyze();
+        assert!(!patched);
+        assert_eq!(docs.len(), 3);
+
+        // Package identity rewrote the placeholder package of the previous
+        // index's symbols; the patch's own placeholders are rewritten the same way
+        let placeholder = encode_fields(&[Field::bytes(1, b"scip-python python . . pkg/a/g().".to_vec())]);
+        for name in ["full.scip", "patch.scip"] {
+            let mut index = Index::read(&indexers.path().join(name)).unwrap();
+            index.documents[0].fields.truncate(2);
+            index.documents[0].fields.push(Field::bytes(3, placeholder.clone()));
+            index.write(&indexers.path().join(name)).unwrap();
+        }
+        create_file(temp.path(), "pyproject.toml", "[project]\nname = \"acme\"\nversion = \"1.0\"\n");
+        let (patched, _) = analyze();
+        assert!(!patched);
+        let previous = Index::read(&temp.path().join(".legend-indexer/python.scip")).unwrap();
+        assert_eq!(previous.documents[0].defined_symbols(), vec!["scip-python python acme 1.0 pkg/a/g()."]);
+        create_file(temp.path(), "pkg/a.py", "a = 4\ndef g(): pass");
+        let (patched, docs) = analyze();
+        assert!(patched);
+        assert_eq!(version(&docs, "pkg/a.py").as_deref(), Some("v2"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_clang_gets_synthesized_compdb() {
+        let indexers = TempDir::new().unwrap();
+        let dir = indexers.path().to_str().unwrap();
+        // Record the database scip-clang is given and write an empty index
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-clang",
+            &format!(
+                "for arg in \"$@\"; do case \"$arg\" in\n\
+                 --compdb-path=*) echo \"${{arg#*=}}\" > {dir}/compdb-path; cp \"${{arg#*=}}\" {dir}/compdb.json ;;\n\
+                 --index-output-path=*) : > \"${{arg#*=}}\" ;;\n\
+                 esac; done"
+            ),
+        );
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "src/main.c", "#include \"util.h\"\nint main(void) { return 0; }");
+        create_file(temp.path(), "src/util.c", "int util(void) { return 1; }");
+        create_file(temp.path(), "include/util.h", "int util(void);");
+
+        let result = cargo_bin_cmd!("legend-indexer")
+            .arg("--indexers-path")
+            .arg(indexers.path())
+            .args(["-l", "c", "analyze"])
+            .arg(temp.path())
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+
+        // No database in the codebase and no CMake or Meson: one is synthesized in the work dir
+        let passed = fs::read_to_string(indexers.path().join("compdb-path")).unwrap();
+        assert_eq!(
+            fs::canonicalize(passed.trim()).unwrap(),
+            fs::canonicalize(temp.path().join(".legend-indexer/compdb/compile_commands.json")).unwrap()
+        );
+        let db: serde_json::Value = serde_json::from_str(&fs::read_to_string(indexers.path().join("compdb.json")).unwrap()).unwrap();
+        let files: Vec<&std::path::Path> = db.as_array().unwrap().iter().map(|e| std::path::Path::new(e["file"].as_str().unwrap())).collect();
+        assert_eq!(files.len(), 2, "{:?}", files);
+        assert!(files[0].ends_with("src/main.c") && files[1].ends_with("src/util.c"), "{:?}", files);
+        let arguments = db[0]["arguments"].as_array().unwrap();
+        assert!(arguments.iter().any(|a| a.as_str().unwrap().ends_with("include")), "{:?}", arguments);
+        assert!(temp.path().join(".legend-indexer/c.scip").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_cache_restore_reroots_index() {
+        use legend_indexer::scip::{file_uri, Index};
+
+        let indexers = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let checkouts = [TempDir::new().unwrap(), TempDir::new().unwrap()];
+        for checkout in &checkouts {
+            create_file(checkout.path(), "go.mod", "module acme/app\n");
+            create_file(checkout.path(), "main.go", "package main");
+        }
+        // The fake indexer roots its index at the first checkout, as scip-go would there
+        let mut fixture = Index::default();
+        fixture.set_project_root(&file_uri(checkouts[0].path()));
+        fixture.write(&indexers.path().join("fixture.scip")).unwrap();
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-go",
+            &format!("cp {:?} \"$2\"", indexers.path().join("fixture.scip")),
+        );
+
+        let cached: Vec<bool> = checkouts
+            .iter()
+            .map(|checkout| {
+                let result = cargo_bin_cmd!("legend-indexer")
+                    .arg("--indexers-path")
+                    .arg(indexers.path())
+                    .arg("--cache-dir")
+                    .arg(cache.path())
+                    .args(["--format", "json", "analyze"])
+                    .arg(checkout.path())
+                    .output()
+                    .unwrap();
+                assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+                let doc: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+                doc["results"][0]["cached"].as_bool().unwrap()
+            })
+            .collect();
+        assert_eq!(cached, vec![false, true]);
+
+        let restored = Index::read(&checkouts[1].path().join(".legend-indexer/go.scip")).unwrap();
+        assert_eq!(restored.project_root(), Some(file_uri(checkouts[1].path())), "not the checkout that stored it");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_cache_shared_between_checkouts() {
+        let indexers = TempDir::new().unwrap();
+        let runs = indexers.path().join("runs");
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-go",
+            // A 6-byte index with one document, `ab`
+            &format!("echo run >> {:?}\nprintf '\\022\\004\\012\\002ab' > \"$2\"", runs),
+        );
+        let cache = TempDir::new().unwrap();
+        let checkouts = [TempDir::new().unwrap(), TempDir::new().unwrap()];
+        for checkout in &checkouts {
+            create_file(checkout.path(), "go.mod", "module acme/app\n");
+            create_file(checkout.path(), "main.go", "package main");
+        }
+        let cli = |args: &[&str]| {
+            let result = cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .arg("--cache-dir")
+                .arg(cache.path())
+                .args(["--format", "json"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+            serde_json::from_slice::<serde_json::Value>(&result.stdout).unwrap()
+        };
+        let analyze = |checkout: &TempDir| {
+            let doc = cli(&["analyze", checkout.path().to_str().unwrap()]);
+            let runs = fs::read_to_string(&runs).map(|r| r.lines().count()).unwrap_or(0);
+            (runs, doc["results"][0]["cached"].as_bool().unwrap())
+        };
+
+        assert_eq!(analyze(&checkouts[0]), (1, false));
+        // Same files in another directory: restored, the indexer does not run
+        assert_eq!(analyze(&checkouts[1]), (1, true));
+        assert!(checkouts[1].path().join(".legend-indexer/go.scip").is_file());
+        create_file(checkouts[1].path(), "main.go", "package main // changed");
+        assert_eq!(analyze(&checkouts[1]), (2, false));
+
+        let stats = cli(&["cache", "stats"]);
+        assert_eq!(stats["command"], "cache-stats");
+        assert_eq!(stats["entries"], 2);
+        assert_eq!(stats["languages"]["go"]["entries"], 2);
+        assert!(stats["size"].as_u64().unwrap() > 0);
+
+        // Pruning evicts the least recently used entry first
+        let pruned = cli(&["--cache-max-size", "10", "cache", "prune"]);
+        assert_eq!(pruned["removed"]["entries"], 1);
+        assert_eq!(pruned["remaining"]["entries"], 1);
+        assert_eq!(analyze(&checkouts[1]), (2, true));
+        assert_eq!(cli(&["cache", "prune", "--all"])["remaining"]["entries"], 0);
+
+        let result = cargo_bin_cmd!("legend-indexer").args(["cache", "stats"]).output().unwrap();
+        assert!(!result.status.success());
+        assert!(String::from_utf8_lossy(&result.stderr).contains("--cache-dir"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_failed_run_keeps_previous_index() {
+        let indexers = TempDir::new().unwrap();
+        let dir = indexers.path().to_str().unwrap();
+        // With `garbage` the fake writes an invalid index; with `fail` it
+        // writes part of one and exits non-zero
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-go",
+            &format!(
+                "[ -e {dir}/garbage ] && {{ echo garbage > \"$2\"; exit 0; }}\n\
+                 [ -e {dir}/fail ] && {{ head -c 1 {dir}/fixture.scip > \"$2\"; exit 1; }}\n\
+                 cp {dir}/fixture.scip \"$2\""
+            ),
+        );
+        let mut fixture = legend_indexer::scip::Index::default();
+        fixture.set_project_root("file:///fixture");
+        fixture.write(&indexers.path().join("fixture.scip")).unwrap();
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+        let analyze = || {
+            cargo_bin_cmd!("legend-indexer")
+                .args(["--indexers-path", dir, "analyze"])
+                .arg(temp.path())
+                .output()
+                .unwrap()
+                .status
+                .code()
+        };
+        let work_dir = temp.path().join(".legend-indexer");
+        let index = work_dir.join("go.scip");
+
+        assert_eq!(analyze(), Some(0));
+        let good = fs::read(&index).unwrap();
+        for mode in ["garbage", "fail"] {
+            create_file(indexers.path(), mode, "");
+            assert_eq!(analyze(), Some(6), "{}", mode);
+            assert_eq!(fs::read(&index).unwrap(), good, "{}: previous index kept", mode);
+            let mut names: Vec<String> = fs::read_dir(&work_dir)
+                .unwrap()
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|n| n.contains(".scip"))
+                .collect();
+            names.sort();
+            assert_eq!(names, vec!["go.scip"], "{}: no staged output left", mode);
+            fs::remove_file(indexers.path().join(mode)).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_concurrent_run_waits_for_lock() {
+        use legend_indexer::outdir::RunLock;
+        use std::time::Duration;
+
+        let indexers = TempDir::new().unwrap();
+        common::install_fake_indexer(indexers.path(), "scip-go", ": > \"$2\"");
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+        let analyze = |timeout: &str| {
+            cargo_bin_cmd!("legend-indexer")
+                .arg("--indexers-path")
+                .arg(indexers.path())
+                .args(["--lock-timeout", timeout, "analyze"])
+                .arg(temp.path())
+                .output()
+                .unwrap()
+        };
+
+        // Another run holds the work directory: fail at once, naming it
+        let lock = RunLock::acquire(&temp.path().join(".legend-indexer"), Duration::ZERO).unwrap();
+        let result = analyze("0");
+        assert_eq!(result.status.code(), Some(7));
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(
+            stderr.contains(&format!("Another legend-indexer run (pid {}) is using", std::process::id())),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("--lock-timeout"), "{}", stderr);
+        assert!(!temp.path().join(".legend-indexer/go.scip").exists());
+
+        // With a timeout, the run waits until the lock is released
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(500));
+            drop(lock);
+        });
+        let result = analyze("30");
+        holder.join().unwrap();
+        assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+        assert!(temp.path().join(".legend-indexer/go.scip").is_file());
+
+        // Two runs copying to --output: the first removes the work directory
+        // while the second waits on its lock, which it then takes
+        common::install_fake_indexer(indexers.path(), "scip-go", "sleep 1\n: > \"$2\"");
+        let out = temp.path().join("out");
+        let run = || {
+            let (indexers, codebase, out) = (indexers.path().to_path_buf(), temp.path().to_path_buf(), out.clone());
+            std::thread::spawn(move || {
+                cargo_bin_cmd!("legend-indexer")
+                    .arg("--indexers-path")
+                    .arg(indexers)
+                    .args(["--lock-timeout", "30", "--output"])
+                    .arg(out)
+                    .arg("analyze")
+                    .arg(codebase)
+                    .output()
+                    .unwrap()
+            })
+        };
+        let (first, second) = (run(), run());
+        for result in [first.join().unwrap(), second.join().unwrap()] {
+            assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+        }
+        assert!(out.join("go.scip").is_file());
+        assert!(!out.join(".legend-indexer.lock").exists(), "the lock file is removed with the lock");
+        assert!(!temp.path().join(".legend-indexer").exists());
+    }
+
+    #[test]
+    fn test_cli_json_errors_are_documents() {
+        use legend_indexer::outdir::RunLock;
+        use std::time::Duration;
+
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "main.go", "package main");
+        create_file(temp.path(), "not-a-dir", "");
+        let json = |args: &[&str]| -> (Option<i32>, serde_json::Value) {
+            let output = cargo_bin_cmd!("legend-indexer")
+                .args(["--format", "json"])
+                .args(args)
+                .output()
+                .unwrap();
+            let document = serde_json::from_slice(&output.stdout).expect("stdout is one JSON document");
+            (output.status.code(), document)
+        };
+        let path = |rel: &str| temp.path().join(rel).to_string_lossy().into_owned();
+
+        for command in ["analyze", "watch"] {
+            let (code, error) = json(&[command, &path("missing")]);
+            assert_eq!(code, Some(2));
+            assert_eq!(error["command"], command);
+            assert_eq!(error["status"], "error");
+            assert_eq!(error["exit_code"], 2);
+            assert!(error["message"].as_str().unwrap().contains("is not a directory"), "{}", error);
+        }
+
+        for command in ["analyze", "plan", "doctor"] {
+            let (code, error) = json(&["-l", "typscript", command, &path("")]);
+            assert_eq!(code, Some(2));
+            assert_eq!(error["command"], command);
+            assert_eq!((error["status"].as_str(), error["exit_code"].as_u64()), (Some("error"), Some(2)));
+            assert!(error["message"].as_str().unwrap().contains("invalid --languages"), "{}", error);
+        }
+
+        let lock = RunLock::acquire(&temp.path().join(".legend-indexer"), Duration::ZERO).unwrap();
+        let (code, error) = json(&["analyze", &path("")]);
+        assert_eq!(code, Some(7));
+        assert_eq!((error["status"].as_str(), error["exit_code"].as_u64()), (Some("error"), Some(7)));
+        assert!(error["message"].as_str().unwrap().contains("--lock-timeout"), "{}", error);
+        drop(lock);
+
+        // Unexpected errors are documents too: the output directory is a file
+        let (code, error) = json(&["--output", &path("not-a-dir"), "analyze", &path("")]);
+        assert_eq!(code, Some(1));
+        assert_eq!((error["status"].as_str(), error["exit_code"].as_u64()), (Some("error"), Some(1)));
+        assert!(error["message"].as_str().unwrap().contains("not-a-dir"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_work_dir_leaves_codebase_untouched() {
+        let indexers = TempDir::new().unwrap();
+        let dir = indexers.path().to_str().unwrap();
+        let mut fixture = legend_indexer::scip::Index::default();
+        fixture.set_project_root("file:///fixture");
+        fixture.write(&indexers.path().join("fixture.scip")).unwrap();
+        common::install_fake_indexer(indexers.path(), "scip-go", &format!("cp {dir}/fixture.scip \"$2\""));
+        // Like the real one, the fake scip-zig writes index.scip to its working directory
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-zig",
+            &format!("echo \"$PWD\" > {dir}/zig-cwd\ncp {dir}/fixture.scip index.scip"),
+        );
+        let temp = TempDir::new().unwrap();
+        let codebase = temp.path().join("repo");
+        create_file(&codebase, "go.mod", "module acme/app\n");
+        create_file(&codebase, "main.go", "package main");
+        create_file(&codebase, "src/main.zig", "pub fn main() void {}");
+        fn files_under(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+            fs::read_dir(dir)
+                .unwrap()
+                .flatten()
+                .flat_map(|e| if e.path().is_dir() { files_under(&e.path()) } else { vec![e.path()] })
+                .collect()
+        }
+        let listing = || {
+            let mut files = files_under(&codebase);
+            files.sort();
+            files
+        };
+        let before = listing();
+        let (work_dir, out) = (temp.path().join("work"), temp.path().join("out"));
+
+        let result = cargo_bin_cmd!("legend-indexer")
+            .args(["--indexers-path", dir])
+            .arg("--work-dir")
+            .arg(&work_dir)
+            .arg("--output")
+            .arg(&out)
+            .arg("analyze")
+            .arg(&codebase)
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+        assert_eq!(listing(), before, "nothing written into the codebase");
+        assert!(out.join("go.scip").is_file());
+
+        // scip-zig ran in the scratch copy; its index is rooted at the codebase
+        let cwd = fs::read_to_string(indexers.path().join("zig-cwd")).unwrap();
+        assert!(cwd.trim().starts_with(work_dir.join("tree").to_str().unwrap()), "{}", cwd);
+        let zig = legend_indexer::scip::Index::read(&out.join("zig.scip")).unwrap();
+        assert_eq!(zig.project_root(), Some(legend_indexer::scip::file_uri(&codebase)));
+        assert!(work_dir.join("tree/repo/src/main.zig").is_file(), "the copy is kept for the next run");
+
+        // A work directory inside the codebase would be indexed and written to
+        let result = cargo_bin_cmd!("legend-indexer")
+            .arg("--work-dir")
+            .arg(codebase.join("work"))
+            .arg("analyze")
+            .arg(&codebase)
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&result.stderr).contains("inside the codebase"));
+        assert_eq!(listing(), before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_watch_reindexes_affected_languages() {
+        use std::io::BufRead;
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let indexers = TempDir::new().unwrap();
+        let dir = indexers.path().to_str().unwrap();
+        common::install_fake_indexer(
+            indexers.path(),
+            "scip-go",
+            &format!("[ -e {dir}/fail ] && exit 1\ncp {dir}/fixture.scip \"$2\""),
+        );
+        let mut fixture = legend_indexer::scip::Index::default();
+        fixture.set_project_root("file:///fixture");
+        fixture.write(&indexers.path().join("fixture.scip")).unwrap();
+        let temp = TempDir::new().unwrap();
+        create_file(temp.path(), "go.mod", "module acme/app\n");
+        create_file(temp.path(), "main.go", "package main");
+
+        struct KillOnDrop(std::process::Child);
+        impl Drop for KillOnDrop {
+            fn drop(&mut self) {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+        }
+        let mut child = KillOnDrop(
+            std::process::Command::new(env!("CARGO_BIN_EXE_legend-indexer"))
+                .args(["--indexers-path", dir, "watch"])
+                .arg(temp.path())
+                .args(["--poll-ms", "50", "--debounce-ms", "100"])
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .unwrap(),
+        );
+        let (tx, rx) = mpsc::channel();
+        let stdout = child.0.stdout.take().unwrap();
+        std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stdout).lines().map_while(Result::ok) {
+                let _ = tx.send(line);
+            }
+        });
+        let next_status = || {
+            let line = rx.recv_timeout(Duration::from_secs(60)).expect("watch status line");
+            assert!(line.starts_with("[watch] "), "{}", line);
+            line
+        };
+        let published = temp.path().join(".legend-indexer/go.scip");
+
+        assert!(next_status().contains("Watching"));
+        assert!(next_status().contains("Initial index: Go indexed"));
+        let good = fs::read(&published).unwrap();
+
+        create_file(temp.path(), "notes.md", "# Notes");
+        assert_eq!(next_status(), "[watch] notes.md changed: no index affected");
+
+        // A failing run keeps the last good index in place
+        create_file(indexers.path(), "fail", "");
+        create_file(temp.path(), "main.go", "package main // broken");
+        let status = next_status();
+        assert!(status.starts_with("[watch] main.go changed: Go failed (previous index kept)"), "{}", status);
+        assert_eq!(fs::read(&published).unwrap(), good);
+
+        fs::remove_file(indexers.path().join("fail")).unwrap();
+        create_file(temp.path(), "main.go", "package main // fixed");
+        assert!(next_status().starts_with("[watch] main.go changed: Go indexed"));
+    }
+
+    #[test]
+    fn test_cli_nonexistent_path() {
+        let result = cargo_bin_cmd!("legend-indexer")
             .arg("detect")
             .arg("/nonexistent/path/that/does/not/exist")
             .output()
@@ -447,8 +1839,7 @@ mod cli {
         create_file(temp.path(), "app.ts", "const x = 1;");
         create_file(temp.path(), "script.py", "x = 1");
 
-        let result = Command::cargo_bin("legend-indexer")
-            .unwrap()
+        let result = cargo_bin_cmd!("legend-indexer")
             .arg(temp.path())
             .arg("--languages")
             .arg("typescript")
@@ -472,8 +1863,7 @@ mod cli {
         create_file(temp.path(), "data.json", "{}");
         create_file(temp.path(), "README.md", "# Hello");
 
-        Command::cargo_bin("legend-indexer")
-            .unwrap()
+        cargo_bin_cmd!("legend-indexer")
             .arg("detect")
             .arg(temp.path())
             .assert()
@@ -558,7 +1948,7 @@ mod report {
         assert_eq!(report.total_files, 0);
         assert_eq!(report.supported_files, 0);
         assert_eq!(report.excluded_files, 0);
-        assert_eq!(report.walk_errors, 0);
+        assert_eq!(report.walk_errors.total(), 0);
         assert!(report.unrecognized_extensions.is_empty());
         assert!(report.languages.is_empty());
         assert!((report.coverage_percent - 0.0).abs() < f64::EPSILON);
@@ -169,7 +169,7 @@ if [ "$USE_LOCAL" = "1" ]; then
         cd "$CURRENT_DIR"
         echo -e "${GREEN}Indexer built successfully${NC}"
     fi
-    "$INDEXER_PATH" "$CODEBASE_PATH" --output "$OUTPUT_DIR"
+    "$INDEXER_PATH" "$CODEBASE_PATH" --output "$OUTPUT_DIR" || STATUS=$?
 else
     # Use Docker
     # Note: workspace is mounted read-write because indexers need to write temp files
@@ -177,9 +177,16 @@ else
         -v "$CODEBASE_PATH:/workspace" \
         -v "$OUTPUT_DIR:/output" \
         "$DOCKER_IMAGE" \
-        /workspace --output /output
+        /workspace --output /output || STATUS=$?
 fi
 
+# Exit code 5: some languages indexed, others not (see README "Exit codes")
+case "${STATUS:-0}" in
+    0) ;;
+    5) echo -e "${YELLOW}Warning: some languages could not be indexed${NC}" ;;
+    *) echo -e "${RED}Error: indexing failed (exit code $STATUS)${NC}"; exit "$STATUS" ;;
+esac
+
 # Check output
 SCIP_COUNT=$(find "$OUTPUT_DIR" -name "*.scip" 2>/dev/null | wc -l | tr -d ' ')
 
@@ -165,10 +165,11 @@ e2e_test_repo() {
     mkdir -p "$e2e_output"
 
     echo -e "${YELLOW}Indexing $name end-to-end...${NC}"
+    # Exit code 5 (some languages not indexed) still counts; the .scip files are checked below
     if docker run --rm \
         -v "$repo_path:/workspace" \
         -v "$e2e_output:/output" \
-        scip-engine /workspace --output /output 2>&1; then
+        scip-engine /workspace --output /output --fail-on none 2>&1; then
 
         SCIP_COUNT=$(find "$e2e_output" -name "*.scip" 2>/dev/null | wc -l | tr -d ' ')
         if [ "$SCIP_COUNT" -gt 0 ]; then